@@ -2,7 +2,7 @@ use std::ops::Add;
 
 pub type PlayerIndex = usize;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Vector2i {
     pub x: i32,
     pub y: i32,
@@ -17,6 +17,82 @@ pub enum Direction {
     MinusY,
 }
 
+impl Direction
+{
+    /// Returns all directions
+    pub fn all() -> [Direction; 4] {
+        [Direction::PlusX, Direction::MinusX, Direction::PlusY, Direction::MinusY]
+    }
+    /// Returns the direction of the unit offset. None if the offset is not
+    /// a single step along one of the axes
+    pub fn from_offset(offset : Vector2i) -> Option<Direction> {
+        Direction::all().into_iter().find(|direction| Vector2i::from_direction(*direction) == offset)
+    }
+    /// Returns the opposite direction
+    pub fn opposite(self) -> Direction {
+        match self {
+            Direction::PlusX => Direction::MinusX,
+            Direction::MinusX => Direction::PlusX,
+            Direction::PlusY => Direction::MinusY,
+            Direction::MinusY => Direction::PlusY,
+        }
+    }
+}
+
+/// Displays direction as an arrow-like character
+impl std::fmt::Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let symbol = match self {
+            Direction::PlusX => '>',
+            Direction::MinusX => '<',
+            Direction::PlusY => 'v',
+            Direction::MinusY => '^',
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+/// Plain red, green and blue components of the color
+pub type Rgb = (u8, u8, u8);
+
+/// Color of the player. Frontends map it to their own colors
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PlayerColor {
+    White,
+    Green,
+    Cyan,
+    Magenta,
+    Blue,
+    Yellow,
+    Red,
+}
+
+impl PlayerColor
+{
+    /// Returns all colors
+    pub fn all() -> [PlayerColor; 7] {
+        [PlayerColor::White, PlayerColor::Green, PlayerColor::Cyan, PlayerColor::Magenta,
+         PlayerColor::Blue, PlayerColor::Yellow, PlayerColor::Red]
+    }
+    /// Returns the color assigned to the player with given index by default
+    pub fn for_index(index : PlayerIndex) -> PlayerColor {
+        let colors = PlayerColor::all();
+        colors[index % colors.len()]
+    }
+    /// Returns the RGB value of the color, for frontends without the same palette
+    pub fn rgb(&self) -> Rgb {
+        match self {
+            PlayerColor::White => (255, 255, 255),
+            PlayerColor::Green => (0, 200, 0),
+            PlayerColor::Cyan => (0, 200, 200),
+            PlayerColor::Magenta => (200, 0, 200),
+            PlayerColor::Blue => (40, 90, 255),
+            PlayerColor::Yellow => (230, 210, 0),
+            PlayerColor::Red => (220, 30, 30),
+        }
+    }
+}
+
 impl Vector2i
 {
     /// Make unit X vector
@@ -48,6 +124,11 @@ impl Vector2i
             Direction::MinusY => -Vector2i::unit_y(),
         }
     }
+    /// Returns 4 orthogonal neighbors of the cell.
+    /// The order matches `Direction::all()`
+    pub fn neighbors(self) -> [Vector2i; 4] {
+        Direction::all().map(|direction| self + Vector2i::from_direction(direction))
+    }
 }
 
 impl Add for Vector2i {
@@ -142,4 +223,53 @@ mod tests {
         let b = -a;
         assert_eq!(b, Vector2i { x: -1, y: -2 });
     }
-}
\ No newline at end of file
+
+    // Test vector neighbors
+    #[test]
+    fn test_vector_neighbors() {
+        let a = Vector2i::new(2, 2);
+        assert_eq!(a.neighbors(), [
+            Vector2i::new(3, 2),
+            Vector2i::new(1, 2),
+            Vector2i::new(2, 3),
+            Vector2i::new(2, 1),
+        ]);
+        // Order matches directions
+        for (neighbor, direction) in a.neighbors().iter().zip(Direction::all()) {
+            assert_eq!(*neighbor, a + Vector2i::from_direction(direction));
+        }
+    }
+
+    // Test vector as a hash map key
+    #[test]
+    fn test_vector_hash_key() {
+        let mut map = std::collections::HashMap::new();
+        map.insert(Vector2i::new(1, 2), 'a');
+        map.insert(Vector2i::new(2, 1), 'b');
+        map.insert(Vector2i::new(1, 2), 'c');
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(&Vector2i::new(1, 2)), Some(&'c'));
+        assert_eq!(map.get(&Vector2i::new(2, 1)), Some(&'b'));
+        assert_eq!(map.get(&Vector2i::new(0, 0)), None);
+    }
+
+    // Test direction is recovered from unit offsets only
+    #[test]
+    fn test_direction_from_offset() {
+        for direction in Direction::all() {
+            assert_eq!(Direction::from_offset(Vector2i::from_direction(direction)), Some(direction));
+        }
+        assert_eq!(Direction::from_offset(Vector2i::new(0, 0)), None);
+        assert_eq!(Direction::from_offset(Vector2i::new(1, 1)), None);
+        assert_eq!(Direction::from_offset(Vector2i::new(0, -2)), None);
+    }
+
+    // Test direction display
+    #[test]
+    fn test_direction_display() {
+        assert_eq!(Direction::PlusX.to_string(), ">");
+        assert_eq!(Direction::MinusX.to_string(), "<");
+        assert_eq!(Direction::PlusY.to_string(), "v");
+        assert_eq!(Direction::MinusY.to_string(), "^");
+    }
+}
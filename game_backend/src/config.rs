@@ -0,0 +1,224 @@
+use crate::base::Vector2i;
+use std::time::Duration;
+
+/// Distribution of the pizza spawn positions over the free cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PizzaSpawnDist
+{
+    /// Every free cell is equally likely
+    Uniform,
+    /// Cells closer to the center of the field are more likely.
+    /// The weight falls linearly with the distance, so corners still get pizzas
+    CenterWeighted,
+}
+
+/// Reward for the player whose snake body killed other snake
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KillReward
+{
+    /// Score added to the killer
+    pub score : u32,
+    /// Number of cells the killer snake grows by
+    pub growth : u32,
+}
+
+/// Distances that keep new snakes and pizzas away from the snakes on the field,
+/// so nobody gets an instant feed or a spawn kill. Distances are manhattan.
+/// The rules are relaxed if there is no place that keeps them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpawnPolicy
+{
+    /// Minimal distance between the cells of the spawned snake and any living snake.
+    /// Registration fails if the spawn point is closer, respawn picks other spawn point.
+    /// 0 allows overlapping snakes
+    pub snake_distance : u32,
+    /// Minimal distance between a spawned pizza and any living snake head. 0 allows any free cell
+    pub pizza_distance : u32,
+}
+
+/// Penalty for the snake warped instead of dying
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WarpPenalty
+{
+    /// Percent of the snake length kept after the warp. Never below 2 cells
+    pub length_kept_percent : u32,
+    /// Score taken from the player. Score doesn't go below zero
+    pub score_loss : u32,
+}
+
+/// Settings of the game thumbnails for spectator overviews
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThumbnailConfig
+{
+    /// Number of steps between the thumbnails
+    pub interval : u64,
+    /// Largest size of the thumbnail, in cells
+    pub max_width : usize,
+    pub max_height : usize,
+}
+
+/// Order in which the actions of one step are applied.
+/// The actions are always predicted from the state at the start of the step,
+/// so the order never changes whether a snake moves, holds or dies. It decides
+/// the order of the death events and whether a killer dying on the same step
+/// is credited: the kill is credited only if the killer is still alive when the
+/// death of its victim is applied. So when two snakes kill each other,
+/// only the one applied later is credited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResolutionOrder
+{
+    /// Lower player index goes first
+    PlayerIndex,
+    /// Higher score goes first. Equal scores go in player index order
+    HighestScore,
+    /// Order is shuffled every step. Same seed gives the same orders
+    Shuffled,
+}
+
+/// What happens when several snakes are about to move into the same cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContestRule
+{
+    /// All competing snakes hold
+    Hold,
+    /// The longest snake moves and the shorter ones die. The longest snakes
+    /// of equal length die if `tie_dies` is set and hold otherwise
+    LongerWins { tie_dies : bool },
+}
+
+/// Extra condition that ends the game, on top of all players being dead
+/// and the board being full or stalled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameEndCondition
+{
+    /// Game ends when at most one player of several is alive
+    LastStanding,
+    /// Game ends when any player reaches given score
+    ScoreTarget(u32),
+    /// Game ends after given number of steps
+    StepLimit(u64),
+    /// Game ends when the last pizza of the limited supply is eaten,
+    /// see `GameConfig::pizza_supply`. Never triggers with unlimited supply
+    BoardCleared,
+}
+
+/// Game configuration. Start from `GameConfig::default()` and
+/// override the fields that matter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameConfig
+{
+    /// Size of the field
+    pub field_size : Vector2i,
+    /// Number of steps the cells vacated by snake tails are tracked for.
+    /// None disables tracking
+    pub trail_lifetime : Option<u32>,
+    /// Maximal number of direction changes queued per player. Queued directions
+    /// are applied one per step, so rapid turns register on consecutive steps.
+    /// 0 applies inputs immediately
+    pub input_buffer : usize,
+    /// Seed for the random generator. Same seed gives the same game.
+    /// None seeds from the system entropy
+    pub seed : Option<u64>,
+    /// Length of the spawned snakes. Clamped so the snake fits into the field
+    pub initial_length : u32,
+    /// Number of consecutive steps without any snake moving or dying
+    /// after which the game ends as a draw. None disables the check
+    pub stall_threshold : Option<u32>,
+    /// Maximal length of the snake. Snake at the cap still scores when it eats,
+    /// but doesn't grow. None means unlimited
+    pub max_snake_length : Option<usize>,
+    /// Distribution of the pizza spawn positions
+    pub pizza_spawn_dist : PizzaSpawnDist,
+    /// Eating pizza within this number of steps after the previous one increases
+    /// the score multiplier. Waiting longer resets it. None disables combos
+    pub combo_window : Option<u64>,
+    /// Minimal distances of the spawned snakes and pizzas to the snakes on the field
+    pub spawn_policy : SpawnPolicy,
+    /// Every this number of steps each pizza drifts one cell towards the
+    /// closest snake head, if the cell is empty. None keeps pizzas in place
+    pub pizza_gravity_interval : Option<u64>,
+    /// Sends the time spent on every update step. Meant for performance tuning
+    pub report_tick_timing : bool,
+    /// Snakes leaving the field enter it from the opposite edge instead of dying
+    pub wrap_around : bool,
+    /// Reward for killing other snake with own body. None disables the reward
+    pub kill_reward : Option<KillReward>,
+    /// Sends the heartbeat event with this interval, independently of the steps.
+    /// Lets clients detect the game is alive between updates. None disables heartbeats
+    pub heartbeat_interval : Option<Duration>,
+    /// Every this number of steps the outermost ring of the playable area turns
+    /// into walls. Snakes caught in the ring die. None keeps the arena size
+    pub shrink_interval : Option<u64>,
+    /// Time the update step and grid generation may take. Longer steps make the game
+    /// fall behind and are reported. Defaults to the update interval. None disables the check
+    pub tick_budget : Option<Duration>,
+    /// Order in which the actions of one step are applied, see `ResolutionOrder`
+    pub resolution_order : ResolutionOrder,
+    /// Outcome of snakes competing for the same cell, see `ContestRule`
+    pub contest_rule : ContestRule,
+    /// Dying snake is moved to a random free place instead, shortened and with
+    /// the score penalty. The snake dies only if there is no room for it. None disables warps
+    pub warp_on_death : Option<WarpPenalty>,
+    /// Number of updates the snakes hold still after the game loop starts. Inputs
+    /// are accepted, so players can pick the starting direction. None starts immediately
+    pub start_countdown : Option<u32>,
+    /// Maximal number of directions read from the player control between two steps.
+    /// The rest are dropped, so flooding clients can't slow the game down. None reads all
+    pub max_inputs_per_tick : Option<usize>,
+    /// Sends the downscaled grid on schedule, for cheap overviews of many games.
+    /// None disables thumbnails
+    pub thumbnail : Option<ThumbnailConfig>,
+    /// Body of the dead snake stays on the field as walls for this number of steps.
+    /// None removes the body right away
+    pub remains_lifetime : Option<u64>,
+    /// Number of pizzas the game spawns, for "clear the board" modes. Pizzas placed
+    /// by hand are not counted. None spawns pizzas forever
+    pub pizza_supply : Option<u32>,
+    /// Snakes grow when eating pizzas. Without growth eating only scores,
+    /// so the snakes keep their length for the whole game
+    pub grow_on_eat : bool,
+    /// Conditions that end the game besides the built-in ones. The first one
+    /// that triggers gives the game over reason
+    pub end_conditions : Vec<GameEndCondition>,
+    /// Number of steps the snake ignores turns after entering ice, see `Game::add_ice`
+    pub ice_slide_steps : u64,
+    /// Snakes die when running into their own body. Without it snakes pass through
+    /// themselves and only walls, hazards and other snakes kill
+    pub self_collision : bool,
+}
+
+impl Default for GameConfig {
+    fn default() -> GameConfig {
+        GameConfig {
+            field_size : Vector2i::new(20, 20),
+            trail_lifetime : None,
+            input_buffer : 0,
+            seed : None,
+            initial_length : 2,
+            stall_threshold : Some(40),
+            max_snake_length : None,
+            pizza_spawn_dist : PizzaSpawnDist::Uniform,
+            combo_window : None,
+            spawn_policy : SpawnPolicy { snake_distance : 2, pizza_distance : 0 },
+            pizza_gravity_interval : None,
+            report_tick_timing : false,
+            wrap_around : false,
+            kill_reward : None,
+            heartbeat_interval : None,
+            shrink_interval : None,
+            tick_budget : Some(Duration::from_millis(250)),
+            resolution_order : ResolutionOrder::PlayerIndex,
+            contest_rule : ContestRule::Hold,
+            warp_on_death : None,
+            start_countdown : None,
+            max_inputs_per_tick : Some(8),
+            thumbnail : None,
+            remains_lifetime : None,
+            pizza_supply : None,
+            grow_on_eat : true,
+            end_conditions : Vec::new(),
+            ice_slide_steps : 3,
+            self_collision : true,
+        }
+    }
+}
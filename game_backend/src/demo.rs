@@ -0,0 +1,86 @@
+use crate::base::{Direction, PlayerIndex, Vector2i};
+use crate::game::Game;
+use crate::grid;
+
+/// Greedy AI that plays for all players. Meant for the demo screen shown while
+/// nobody plays. Every step each snake turns towards the closest pizza,
+/// avoiding the moves that kill it or lock it in an area smaller than itself
+pub struct DemoController;
+
+impl DemoController {
+    /// Picks the direction for the player. Returns None if the player is dead
+    /// or there are no safe moves
+    pub fn choose_direction(game : &Game, player_index : PlayerIndex) -> Option<Direction> {
+        let head = game.snake_head(player_index)?;
+        let length = game.snake_length(player_index);
+        let pizzas = game.pizza_positions();
+        let grid = game.generate_grid();
+        Direction::all().into_iter()
+            .filter(|direction| game.is_move_safe_in(&grid, player_index, *direction))
+            .min_by_key(|direction| {
+                let new_head = game.wrap_pos(head + Vector2i::from_direction(*direction));
+                let area = grid::reachable_area(&grid, new_head);
+                let distance = pizzas.iter()
+                    .map(|pizza| (pizza.x - new_head.x).abs() + (pizza.y - new_head.y).abs())
+                    .min()
+                    .unwrap_or(0);
+                (area < length, distance, std::cmp::Reverse(area))
+            })
+    }
+
+    /// Turns every living snake to the direction picked by `choose_direction`
+    pub fn steer(game : &mut Game) {
+        for player_index in game.alive_player_indices() {
+            if let Some(direction) = Self::choose_direction(game, player_index) {
+                game.steer_player(player_index, direction);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // Test that the snake goes for the pizza and avoids the walls
+    #[test]
+    fn test_choose_direction() {
+        let game = crate::game::GameBuilder::new()
+            .field(6, 6)
+            .pizza(Vector2i::new(2, 5))
+            .snake(Vector2i::new(2, 2), Direction::PlusX, 2)
+            .build();
+        assert_eq!(DemoController::choose_direction(&game, 0), Some(Direction::PlusY));
+
+        let game = crate::game::GameBuilder::new()
+            .field(6, 6)
+            .pizza(Vector2i::new(5, 0))
+            .wall(Vector2i::new(3, 2))
+            .snake(Vector2i::new(2, 2), Direction::PlusX, 2)
+            .build();
+        assert_eq!(DemoController::choose_direction(&game, 0), Some(Direction::MinusY));
+    }
+
+    // Test that demo mode plays the game to the end without human input.
+    // Snakes racing for the same pizza may hold forever, the stall rule ends such games
+    #[test]
+    fn test_demo_runs_to_game_over() {
+        let mut game = crate::game::GameBuilder::new()
+            .field(8, 8)
+            .seed(11)
+            .snake(Vector2i::new(2, 2), Direction::PlusX, 2)
+            .snake(Vector2i::new(5, 5), Direction::MinusX, 2)
+            .build();
+        let mut steps = 0;
+        while game.game_over_reason().is_none() && steps < 10_000 {
+            DemoController::steer(&mut game);
+            game.step();
+            steps += 1;
+        }
+        assert!(game.game_over_reason().is_some(), "Demo didn't end in {} steps", steps);
+        // Snakes ate something on the way
+        let mut summary = Vec::new();
+        game.players_summary_into(&mut summary);
+        assert!(summary.iter().any(|summary| summary.score > 0));
+    }
+}
@@ -1,4 +1,6 @@
 use crate::grid::Grid;
+use crate::base::{Vector2i, PlayerColor, PlayerIndex, Rgb};
+use std::time::Duration;
 
 /// The short summary information about player
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -6,6 +8,11 @@ pub struct PlayerSummary
 {
     pub score : u32,
     pub alive : bool,
+    /// Current score multiplier for eating pizzas in quick succession
+    pub multiplier : u32,
+    pub color : PlayerColor,
+    /// Exact color of the player. Matches `color` unless configured otherwise
+    pub rgb : Rgb,
 }
 
 /// The structure that represents an update event
@@ -14,13 +21,216 @@ pub struct Update
 {
     pub grid : Grid,
     pub players_summary : Vec<PlayerSummary>,
+    /// Number of steps done since the game start
+    pub step : u64,
+    /// Wall-clock time since the game loop start
+    pub elapsed : Duration,
+    /// Players whose snake wrapped across the field edge on the last step.
+    /// The body of such snake is not continuous at the edge
+    pub wrapped : Vec<PlayerIndex>,
+}
+
+/// The enum that describes why the game is over
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameOverReason
+{
+    /// All players are dead
+    AllDead,
+    /// Nobody moved or died for too long. The game ends as a draw
+    Stalled,
+    /// There are no empty cells and no pizzas left, so nobody can grow.
+    /// The game ends as a draw
+    BoardFull,
+    /// Only one player is alive, see `GameEndCondition::LastStanding`
+    LastStanding,
+    /// A player reached the target score, see `GameEndCondition::ScoreTarget`
+    ScoreTarget,
+    /// The step limit is reached, see `GameEndCondition::StepLimit`
+    StepLimit,
+    /// All the pizzas are eaten, see `GameEndCondition::BoardCleared`
+    BoardCleared,
 }
 
 /// The structure that represents the game over event
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct GameOver
 {
+    pub reason : GameOverReason,
     pub players_summary : Vec<PlayerSummary>,
+    /// Wall-clock time since the game loop start. Zero if loop was never started
+    pub duration : Duration,
+    /// Number of steps done during the game
+    pub total_steps : u64,
+    /// Player indices ordered from the best to the worst
+    pub ranking : Vec<PlayerIndex>,
+}
+
+/// The enum that describes why the player died
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeathCause
+{
+    /// Snake left the field or entered a wall
+    Wall,
+    /// Snake collided with its own body
+    OwnSnake,
+    /// Snake collided with the snake of other player
+    OtherSnake,
+    /// Snake entered active hazard
+    Hazard,
+}
+
+/// The structure that represents the player death event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlayerDied
+{
+    pub player_index : PlayerIndex,
+    pub cause : DeathCause,
+    /// The cell the snake tried to enter
+    pub position : Vector2i,
+    /// The player whose snake was hit. Only set when cause is OtherSnake
+    pub killer : Option<PlayerIndex>,
+}
+
+/// The structure that represents the reward for killing other snake.
+/// Only sent if kill reward is enabled in config
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KillCredited
+{
+    pub killer : PlayerIndex,
+    pub victim : PlayerIndex,
+    /// Score added to the killer
+    pub score : u32,
+    /// Number of cells the killer snake grows by
+    pub growth : u32,
+}
+
+/// The structure that represents the event of new player taking the lead
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeadChanged
+{
+    pub new_leader : PlayerIndex,
+    pub score : u32,
+}
+
+/// The structure that represents the time spent on single update step.
+/// Only sent if enabled in config
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TickTiming
+{
+    /// Number of steps done, including the measured one
+    pub step : u64,
+    /// Time spent on the step and grid generation
+    pub duration : Duration,
+}
+
+/// The structure that represents the update step that took longer than the tick budget
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TickOverrun
+{
+    /// Number of steps done, including the slow one
+    pub step : u64,
+    /// Time spent on the step and grid generation
+    pub duration : Duration,
+    pub budget : Duration,
+    /// Number of overruns since the game start, including this one
+    pub overruns : u64,
+}
+/// The structure that represents the snake warped to a new place instead of dying.
+/// Only sent if enabled in config
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlayerWarped
+{
+    pub player_index : PlayerIndex,
+    /// The death the warp saved from
+    pub cause : DeathCause,
+    /// The cell the snake tried to enter
+    pub from : Vector2i,
+    /// The new head position
+    pub to : Vector2i,
+    /// The snake length after the warp
+    pub length : usize,
+    /// Score taken from the player
+    pub score_lost : u32,
+}
+
+/// The structure that represents one update of the start countdown.
+/// Only sent if enabled in config
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CountdownTick
+{
+    /// Number of updates left before the snakes start moving.
+    /// 0 is sent with the first update the snakes move on
+    pub remaining : u32,
+}
+
+/// The structure that represents the player sending more directions than accepted.
+/// Sent when the player has inputs dropped on several consecutive steps
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputFlood
+{
+    pub player_index : PlayerIndex,
+    /// Number of consecutive steps with dropped inputs
+    pub steps : u32,
+}
+
+/// The structure that represents the last pizza eaten when no more pizzas will spawn.
+/// Only sent with the limited pizza supply, continuous spawning never clears the board
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoardCleared
+{
+    /// Number of steps done since the game start
+    pub step : u64,
+}
+
+/// The structure that represents the snake entering ice. The snake ignores
+/// turns until the `IceLeft` event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IceEntered
+{
+    pub player_index : PlayerIndex,
+    pub position : Vector2i,
+    /// Number of steps the snake slides straight
+    pub slide_steps : u64,
+}
+
+/// The structure that represents the end of the slide. The snake obeys turns again
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IceLeft
+{
+    pub player_index : PlayerIndex,
+}
+
+/// The structure that represents the downscaled grid for spectator overviews.
+/// Only sent if enabled in config
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Thumbnail
+{
+    /// Row-major cell categories, see `grid::cell_category`
+    pub cells : Vec<u8>,
+    pub width : usize,
+    pub height : usize,
+}
+
+/// The structure that represents the liveness signal of the game loop.
+/// Only sent if enabled in config
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heartbeat
+{
+    /// Number of steps done since the game start
+    pub step : u64,
+    pub alive_count : usize,
+}
+
+/// The structure that represents the shrink of the arena.
+/// Only sent if enabled in config
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArenaShrunk
+{
+    /// Number of steps done since the game start
+    pub step : u64,
+    /// Number of rings turned into walls so far. The playable area
+    /// starts this number of cells away from every edge
+    pub rings : i32,
 }
 
 /// The enum that represents a global game event
@@ -29,4 +239,18 @@ pub enum GlobalEvent
 {
     Update(Update),
     GameOver(GameOver),
+    PlayerDied(PlayerDied),
+    LeadChanged(LeadChanged),
+    TickTiming(TickTiming),
+    KillCredited(KillCredited),
+    Heartbeat(Heartbeat),
+    ArenaShrunk(ArenaShrunk),
+    TickOverrun(TickOverrun),
+    PlayerWarped(PlayerWarped),
+    CountdownTick(CountdownTick),
+    InputFlood(InputFlood),
+    Thumbnail(Thumbnail),
+    BoardCleared(BoardCleared),
+    IceEntered(IceEntered),
+    IceLeft(IceLeft),
 }
\ No newline at end of file
@@ -1,65 +1,339 @@
-use crate::snake::Snake;
+use crate::snake::{DirChange, Snake, build_initial_body};
 use crate::events;
-use crate::base::{Vector2i, PlayerIndex, Direction};
-use crate::grid::{Grid, GridCell, PizzaRec, SnakeRec, SnakeBodyPart};
-use crate::player::{Player, UserControlRx};
+use crate::config::{ContestRule, GameConfig, GameEndCondition, KillReward, ResolutionOrder, WarpPenalty};
+use crate::base::{Vector2i, PlayerColor, PlayerIndex, Direction, Rgb};
+use crate::grid::{self, Grid, GridAccess, GridCell, Hazard, PizzaKind, PizzaRec, Remains, SnakeRec, SnakeBodyPart, Trail};
+use crate::player::{Controller, Player, INPUT_FLOOD_STEPS};
+use crate::spawner::{PizzaSpawner, RandomSpawner};
+use crate::demo::DemoController;
+use std::collections::HashSet;
 use std::sync::mpsc;
 use std::time;
-use rand;
+use rand::{Rng, SeedableRng};
+use rand::seq::SliceRandom;
+use rand::rngs::StdRng;
 
-const INITIAL_LENGTH : u32 = 2;
-const UPDATE_INTERVAL : time::Duration = time::Duration::from_millis(250);
+/// Time between two update steps of the game loop
+pub const UPDATE_INTERVAL : time::Duration = time::Duration::from_millis(250);
+/// The longest the game loop sleeps between checking inputs and commands
+const MAX_SLEEP_INTERVAL : time::Duration = time::Duration::from_millis(10);
+/// The most update steps the game loop does at once to catch up after a delay.
+/// Time beyond that is dropped, so a long stall doesn't fast-forward the game
+const MAX_STEPS_PER_PASS : u32 = 4;
+/// The number of cells the snake loses by eating a poison pizza
+const POISON_SHRINK : usize = 2;
+/// There are spawn points for this number of players
+const MAX_PLAYERS : usize = 4;
 
 /// The type for the globabl update channel
 pub type GlobalUpdateTx = mpsc::Sender<events::GlobalEvent>;
 pub type GlobalUpdateRx = mpsc::Receiver<events::GlobalEvent>;
 
+/// Commands that control the flow of the game loop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameCommand
+{
+    /// Stops advancing the game on timer
+    Pause,
+    /// Continues advancing the game on timer
+    Resume,
+    /// Advances the game exactly one step. Only meaningful while paused
+    StepOnce,
+    /// Starts steering all players with `DemoController`
+    StartDemo,
+    /// Returns the control to the players
+    StopDemo,
+}
+
+/// The type for the game command channel
+pub type GameCommandTx = mpsc::Sender<GameCommand>;
+pub type GameCommandRx = mpsc::Receiver<GameCommand>;
+
+/// The type for the custom logic called at the end of every step, see `Game::set_tick_hook`
+pub type TickHook = Box<dyn FnMut(&mut Game) + Send>;
+
 /// Enum that describes one of the things that may happen with a snake during update step
 #[derive (Debug, Clone, Copy, PartialEq, Eq)]
-enum ActionStep
+pub enum ActionStep
 {
-    /// Snake can't move because other snake competes for the same positition
+    /// Snake can't move because other snake competes for the same positition.
+    /// Dead players always hold
     Hold,
     /// Snake moves in the direction it's looking at
     Move,
     /// Snake dies because it collides with other snake or with the wall
-    Die,
+    Die {
+        cause : events::DeathCause,
+        /// The cell the snake tried to enter
+        position : Vector2i,
+        /// The player whose snake was hit. Only set for OtherSnake cause
+        killer : Option<PlayerIndex>,
+    },
+}
+
+/// Record of the pizza eaten during update step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PizzaEaten
+{
+    pub player_index : PlayerIndex,
+    pub position : Vector2i,
+}
+
+/// Everything that happened during single update step
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct StepOutcome
+{
+    /// Action done by every player. Indexed by player index
+    pub actions : Vec<ActionStep>,
+    pub pizzas_eaten : Vec<PizzaEaten>,
+    /// Positions of the pizzas spawned at the end of the step
+    pub pizzas_spawned : Vec<Vector2i>,
+}
+
+/// Built-in wall layouts, see `Game::apply_preset`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MapPreset
+{
+    /// No walls
+    Empty,
+    /// Walls along the field edges
+    Border,
+    /// Walls along the central row and column. They cross the default spawn
+    /// points, so snakes should be placed by hand
+    CrossCenter,
+    /// Every cell becomes a wall with given probability, from 0 to 1. Cells of
+    /// the spawn points and the cells in front of them stay free. Same seed
+    /// gives same walls
+    RandomBlocks(f64, u64),
+}
+
+/// Pizza placed on the field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Pizza
+{
+    position : Vector2i,
+    kind : PizzaKind,
+}
+
+impl Pizza {
+    /// Makes normal pizza at given position
+    fn normal(position : Vector2i) -> Pizza {
+        Pizza::of_kind(position, PizzaKind::Normal)
+    }
+    /// Makes pizza of given kind at given position
+    fn of_kind(position : Vector2i, kind : PizzaKind) -> Pizza {
+        Pizza { position, kind }
+    }
 }
 
 /// Game object. Create and configure it to start a game.
 pub struct Game {
     players : Vec<Player>,
-    field_size : Vector2i,
-    pizzas : Vec<Vector2i>,
+    config : GameConfig,
+    pizzas : Vec<Pizza>,
     grid : Grid,
+    /// Cells recently vacated by snake tails. Only tracked if enabled in config
+    trails : Vec<Trail>,
+    hazards : Vec<Hazard>,
+    /// Bodies of the dead snakes that are not cleared yet
+    remains : Vec<Remains>,
+    walls : Vec<Vector2i>,
+    /// Slippery cells
+    ice : Vec<Vector2i>,
+    /// Cells of the play area, row-major over the field. Other cells act as walls.
+    /// None makes the whole field playable
+    play_mask : Option<Vec<bool>>,
+    /// Players whose snake wrapped across the edge on the last step
+    wrapped : Vec<PlayerIndex>,
+    /// Number of outer rings turned into walls by the arena shrink
+    shrunk_rings : i32,
+    /// Number of update steps that exceeded the tick budget
+    tick_overruns : u64,
 
     global_event_channels : Vec<GlobalUpdateTx>,
+    command_channel : Option<GameCommandRx>,
+    /// Game doesn't advance on timer while paused
+    paused : bool,
+    /// All players are steered by `DemoController`
+    demo : bool,
+    /// Number of steps done since the game start
+    step_count : u64,
+    /// Updates left in the start countdown. None if the countdown is over
+    countdown : Option<u32>,
+    /// The moment the game loop was started
+    start_time : Option<time::Instant>,
+    /// Picks the cells for new pizzas
+    pizza_spawner : Box<dyn PizzaSpawner + Send>,
+    /// Number of pizzas spawned by the game, for the limited pizza supply
+    pizzas_spawned : u32,
+    /// Custom logic called at the end of every step
+    tick_hook : Option<TickHook>,
+    /// Shuffles the resolution order and picks the warp places
+    rng : StdRng,
+    /// The top scoring living player
+    leader : Option<PlayerIndex>,
+    /// Number of consecutive steps nobody moved or died
+    stalled_steps : u32,
 }
 
 ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////
 
 impl Game {
-    /// Creates new unitialized game object
+    /// Creates new unitialized game object with default config
+    /// #panics
+    /// If any field dimension is not positive
     pub fn new(field_size : Vector2i) -> Game {
+        Game::with_config(GameConfig { field_size, ..GameConfig::default() })
+    }
+    /// Creates new unitialized game object with given config
+    /// #panics
+    /// If any field dimension is not positive
+    pub fn with_config(config : GameConfig) -> Game {
+        assert!(config.field_size.x > 0 && config.field_size.y > 0,
+            "Field size must be positive, got {}x{}", config.field_size.x, config.field_size.y);
+        let pizza_spawner = Box::new(RandomSpawner::new(config.pizza_spawn_dist, config.seed));
+        let countdown = config.start_countdown;
+        let rng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
         Game {
             players : Vec::new(),
-            field_size,
+            config,
             pizzas : Vec::new(),
-            grid : Grid::from_elem((0,0), GridCell::Empty),
+            grid : grid::new_grid(0, 0),
+            trails : Vec::new(),
+            hazards : Vec::new(),
+            remains : Vec::new(),
+            walls : Vec::new(),
+            ice : Vec::new(),
+            play_mask : None,
+            wrapped : Vec::new(),
+            shrunk_rings : 0,
+            tick_overruns : 0,
             global_event_channels : Vec::new(),
+            command_channel : None,
+            paused : false,
+            demo : false,
+            step_count : 0,
+            countdown,
+            start_time : None,
+            pizza_spawner,
+            pizzas_spawned : 0,
+            tick_hook : None,
+            rng,
+            leader : None,
+            stalled_steps : 0,
         }
     }
     /// Adds new player. Returns new player index that can
     /// be used for referencing this player
-    pub fn register_player(&mut self, control : Option<UserControlRx>) -> PlayerIndex {
+    /// #panics
+    /// When the player can't be spawned, see `try_register_player`
+    pub fn register_player(&mut self, control : Option<Box<dyn Controller + Send>>) -> PlayerIndex {
+        self.try_register_player(control).expect("No room to spawn the player")
+    }
+
+    /// Adds new player. Returns new player index that can be used for referencing
+    /// this player. Returns None if all spawn points are taken, or if the spawned snake
+    /// would be closer than the spawn policy allows to any living snake
+    pub fn try_register_player(&mut self, control : Option<Box<dyn Controller + Send>>) -> Option<PlayerIndex> {
+        let new_player_index = self.players.len();
+        if new_player_index >= MAX_PLAYERS {
+            return None;
+        }
+        let snake = self.make_spawn_snake(new_player_index);
+        self.try_register_snake(control, snake)
+    }
+
+    /// Adds new player the same way as `register_player`, but the snake faces
+    /// given direction instead of the center. The body trails behind the head
+    /// and is shortened if needed to fit into the field.
+    /// Respawned snakes face the center again
+    /// #panics
+    /// When the player can't be spawned, see `try_register_player_facing`
+    pub fn register_player_facing(&mut self, control : Option<Box<dyn Controller + Send>>, facing : Direction) -> PlayerIndex {
+        self.try_register_player_facing(control, facing).expect("No room to spawn the player")
+    }
+
+    /// Adds new player the same way as `try_register_player`, but the snake faces
+    /// given direction. Returns None also if the body doesn't fit into the field
+    pub fn try_register_player_facing(&mut self, control : Option<Box<dyn Controller + Send>>,
+                                      facing : Direction) -> Option<PlayerIndex> {
+        let new_player_index = self.players.len();
+        if new_player_index >= MAX_PLAYERS {
+            return None;
+        }
+        let length = self.config.initial_length;
+        let (spawn_pos, _) = Game::calc_spawn_pos(new_player_index, length, self.config.field_size);
+        let snake = Snake::new_clamped(spawn_pos, facing, length, self.config.field_size);
+        if !snake.body().all(|pos| self.in_bounds(*pos)) {
+            return None;
+        }
+        self.try_register_snake(control, snake)
+    }
+
+    /// Adds new player with given snake, if the snake is clear of walls and far enough
+    /// from other snakes
+    fn try_register_snake(&mut self, control : Option<Box<dyn Controller + Send>>, snake : Snake) -> Option<PlayerIndex> {
         let new_player_index = self.players.len();
-        // make spawn point
-        let (spaw_pos, spawn_dir) = Game::calc_spawn_pos(new_player_index, INITIAL_LENGTH, self.field_size);
+        if !self.is_spawn_clear(&snake) || !self.is_spawn_distance_kept(&snake) {
+            return None;
+        }
         let mut player = Player::new();
         player.control = control;
-        player.snake = Some(Snake::new(spaw_pos, spawn_dir, INITIAL_LENGTH));
+        player.snake = Some(snake);
+        player.color = PlayerColor::for_index(new_player_index);
         self.players.push(player);
-        new_player_index
+        Some(new_player_index)
+    }
+
+    /// Returns true if no part of the snake is in a wall, in remains or outside the play area
+    fn is_spawn_clear(&self, snake : &Snake) -> bool {
+        snake.body().all(|pos| !self.is_wall(*pos) && !self.is_remains(*pos))
+    }
+
+    /// Returns true if the snake is far enough from all living snakes
+    fn is_spawn_distance_kept(&self, snake : &Snake) -> bool {
+        let min_distance = self.config.spawn_policy.snake_distance as i32;
+        self.players.iter()
+            .filter_map(|player| player.snake.as_ref())
+            .flat_map(|other| other.body())
+            .all(|other_pos| snake.body().all(|pos| {
+                let offset = *pos - *other_pos;
+                offset.x.abs() + offset.y.abs() >= min_distance
+            }))
+    }
+
+    /// Respawns dead player at its spawn point. If the spawn point is too close
+    /// to other snakes, the first free spawn point of other players is used.
+    /// If none is free, the player respawns at its own spawn point anyway
+    /// #panics
+    /// When player is alive
+    pub fn respawn_player(&mut self, player_index : PlayerIndex) {
+        assert!(!self.players[player_index].alive(), "Player is alive");
+        let snake = std::iter::once(player_index)
+            .chain((0..MAX_PLAYERS).filter(|spawn_index| *spawn_index != player_index))
+            .map(|spawn_index| self.make_spawn_snake(spawn_index))
+            .find(|snake| self.is_spawn_clear(snake) && self.is_spawn_distance_kept(snake))
+            .unwrap_or_else(|| self.make_spawn_snake(player_index));
+        self.players[player_index].respawn(snake);
+    }
+
+    /// Makes the snake at the spawn point of the player with given index.
+    /// The snake length is clamped to fit into the field
+    fn make_spawn_snake(&self, player_index : PlayerIndex) -> Snake {
+        let length = self.config.initial_length;
+        let (spawn_pos, spawn_dir) = Game::calc_spawn_pos(player_index, length, self.config.field_size);
+        Snake::new_clamped(spawn_pos, spawn_dir, length, self.config.field_size)
+    }
+
+    /// Replaces the controller of the player. Directions queued from the old
+    /// controller are dropped. Without control the snake keeps going straight
+    pub fn set_player_control(&mut self, player_index : PlayerIndex, control : Option<Box<dyn Controller + Send>>) {
+        let player = &mut self.players[player_index];
+        player.control = control;
+        player.input_queue.clear();
     }
 
     /// Register global event channel
@@ -67,647 +341,4258 @@ impl Game {
         self.global_event_channels.push(channel);
     }
 
-    /// Helper function that moves player snake
-    /// #panics
-    /// When player dead
-    fn move_player(&mut self, player_index : PlayerIndex) {
-        let player = &mut self.players[player_index];
-        // Get snake. Snake is expected
-        let snake = player.snake.as_mut().unwrap();
-        // Move the snake
-        snake.move_forward();
-        // see if there is pizza
-        let head_pos = snake.body()[0];
-        if let Some(pizza_index) = self.pizzas.iter().position(|p| *p == head_pos) {
-            // Eat pizza
-            snake.eat(1);
-            player.score += 1;
-            // Remove pizza
-            self.pizzas.remove(pizza_index);
-        }
+    /// Register game command channel. Replaces previously registered one
+    pub fn register_command_channel(&mut self, channel : GameCommandRx) {
+        self.command_channel = Some(channel);
     }
 
-    /// Execute single update step
-    fn step(&mut self) {
-        // Predict the step action for every player
-        let mut actions = Vec::new();
-        // Predict action for each snake. Dead snakes just hold
-        for player_index in 0..self.players.len() {
-            let action= self.predict_next_action(player_index);
-            actions.push(action);
-        }
+    /// Sets the score of the player. Useful for giving a handicap
+    /// to some players before the game start
+    pub fn set_score(&mut self, player_index : PlayerIndex, score : u32) {
+        self.players[player_index].score = score;
+    }
 
-        // Apply the actions
-        for player_index in 0..self.players.len() {
-            // Match the action
-            match actions[player_index] {
-                ActionStep::Hold => {
-                    // Do nothing
-                },
-                ActionStep::Move => {
-                    // Move the snake
-                    self.move_player(player_index);
-                },
-                ActionStep::Die => {
-                    // Kill the snake
-                    self.players[player_index].kill();
-                },
-            }
-        }
-        // Spawn pizza if there is none
-        if self.pizzas.is_empty() {
-            // We need up to date grid
-            self.grid = self.generate_grid();
-            // Calculate spawn position
-            let spawn_pos = Self::calc_spawn_pos_for_pizza(
-                &self.grid, self.num_empty_cells());
-            self.pizzas.push(spawn_pos);
+    /// Returns player indices ordered by score from the highest to the lowest.
+    /// Players with equal score are ordered by the snake length, the current one
+    /// for living players and the one at death for dead players, longer first.
+    /// Then living players go first and dead players that died later go before
+    /// those that died earlier. Remaining ties are ordered by index
+    pub fn ranking(&self) -> Vec<PlayerIndex> {
+        let mut ranking : Vec<PlayerIndex> = (0..self.players.len()).collect();
+        ranking.sort_by_key(|&player_index| {
+            let player = &self.players[player_index];
+            let (length, survived) = match &player.snake {
+                Some(snake) => (snake.length(), u64::MAX),
+                None => (player.death_length, player.death_step.unwrap_or(0)),
+            };
+            std::cmp::Reverse((player.score, length, survived))
+        });
+        ranking
+    }
+
+    /// Returns indices of all alive players
+    pub fn alive_player_indices(&self) -> Vec<PlayerIndex> {
+        self.players.iter().enumerate()
+            .filter(|(_, player)| player.alive())
+            .map(|(player_index, _)| player_index)
+            .collect()
+    }
+
+    /// Returns number of alive players
+    pub fn alive_count(&self) -> usize {
+        self.players.iter().filter(|player| player.alive()).count()
+    }
+
+    /// Iterates living players with their snake bodies, from the head to the tail.
+    /// Reads the snakes directly, without cloning them or generating the grid.
+    /// The bodies are ring buffers, so they're iterated rather than sliced
+    pub fn living_snakes(&self) -> impl Iterator<Item = (PlayerIndex, impl ExactSizeIterator<Item = &Vector2i> + Clone)> {
+        self.players.iter().enumerate()
+            .filter_map(|(player_index, player)| player.snake.as_ref().map(|snake| (player_index, snake.body())))
+    }
+
+    /// Returns the head position of the player snake. None if player is dead
+    pub fn snake_head(&self, player_index : PlayerIndex) -> Option<Vector2i> {
+        self.players[player_index].snake.as_ref().map(|snake| snake.head())
+    }
+
+    /// Returns the direction the player snake looks to, which is the direction of
+    /// its next move. None if player is dead or there is no such player
+    pub fn player_look_direction(&self, player_index : PlayerIndex) -> Option<Direction> {
+        self.players.get(player_index)?.snake.as_ref().map(|snake| snake.look_direction())
+    }
+
+    /// Returns the length of the player snake. 0 if player is dead
+    pub fn snake_length(&self, player_index : PlayerIndex) -> usize {
+        self.players[player_index].snake.as_ref().map_or(0, |snake| snake.length())
+    }
+
+    /// Returns the body parts of the player snake where the body turns, from the head
+    /// to the tail. Meant for replays and heatmaps. Empty if player is dead
+    pub fn snake_turning_points(&self, player_index : PlayerIndex) -> Vec<Vector2i> {
+        self.players[player_index].snake.as_ref().map_or(Vec::new(), |snake| snake.turning_points())
+    }
+
+    /// Returns the number of steps along the player snake from the head to the tail.
+    /// 0 if player is dead
+    pub fn snake_path_length(&self, player_index : PlayerIndex) -> usize {
+        self.players[player_index].snake.as_ref().map_or(0, |snake| snake.path_length())
+    }
+
+    /// Returns positions of all pizzas on the field
+    pub fn pizza_positions(&self) -> Vec<Vector2i> {
+        self.pizzas.iter().map(|pizza| pizza.position).collect()
+    }
+
+    /// Turns the player snake to given direction, bypassing its control.
+    /// The turn is rejected if the player is dead, slides on ice or the direction
+    /// reverses the last move
+    pub fn steer_player(&mut self, player_index : PlayerIndex, direction : Direction) -> DirChange {
+        if self.players[player_index].slide_until.is_some() {
+            return DirChange::Rejected;
+        }
+        match self.players[player_index].snake.as_mut() {
+            Some(snake) => snake.set_look_direction(direction),
+            None => DirChange::Rejected,
         }
+    }
 
+    /// Returns the game config
+    pub fn config(&self) -> &GameConfig {
+        &self.config
+    }
 
+    /// Returns the cells recently vacated by snake tails.
+    /// Always empty unless trail tracking is enabled in config
+    pub fn trails(&self) -> &[Trail] {
+        &self.trails
     }
 
-    /// Starts the game loop. This function will return only when game is over.
-    /// Or shutdown command was received.
-    pub fn game_loop(&mut self, shutdown_rx : mpsc::Receiver<()>) {
+    /// Replaces the policy that picks the cells for new pizzas.
+    /// By default pizzas are random, see `RandomSpawner`
+    pub fn set_pizza_spawner(&mut self, spawner : Box<dyn PizzaSpawner + Send>) {
+        self.pizza_spawner = spawner;
+    }
 
-        // Generate initial grid
-        self.grid = self.generate_grid();
+    /// Sets the function called at the end of every step with the game itself.
+    /// Lets embedders run custom logic, like spawning things, without forking the game.
+    /// The hook is detached while it runs, so calling `step` from the hook steps
+    /// without the hook. Setting a new hook from the hook replaces the running one
+    /// after it returns
+    pub fn set_tick_hook(&mut self, hook : TickHook) {
+        self.tick_hook = Some(hook);
+    }
 
-        // Start the timer
-        let mut timer = time::Instant::now();
+    /// Returns the number of update steps that exceeded the tick budget
+    pub fn tick_overruns(&self) -> u64 {
+        self.tick_overruns
+    }
 
-        // Start actual loop
-        loop {
-            // The game is over when all players are dead
-            let game_over = !(self.players.iter().any(|p| p.alive()));
-            if game_over
-            {
-                self.send_game_over_event();
-                break;
-            }
-            //Check shutdown
-            if let Ok(_) = shutdown_rx.try_recv() {
-                break;
-            }
+    /// Sets the color of the player. Players get distinct colors on registration
+    pub fn set_player_color(&mut self, player_index : PlayerIndex, color : PlayerColor) {
+        self.players[player_index].color = color;
+    }
 
-            // Read all players inputs on every loop
-            for player in &mut self.players {
-                player.read_inputs();
-            }
+    /// Sets the exact color of the player reported in the summaries, for frontends
+    /// that don't use the `PlayerColor` palette. By default it's the RGB of the palette color
+    pub fn set_player_rgb(&mut self, player_index : PlayerIndex, rgb : Rgb) {
+        self.players[player_index].rgb = Some(rgb);
+    }
 
-            // Measure time elapsed
-            let elapsed = timer.elapsed();
-            if elapsed > UPDATE_INTERVAL {
-                // Substract updated interval from running timer. That way any leftover time
-                // will be counted towards the next update interval.
-                timer = time::Instant::now().checked_sub(elapsed - UPDATE_INTERVAL).unwrap();
+    /// Adds hazard to the field. Hazard schedule is driven by the step count
+    pub fn add_hazard(&mut self, hazard : Hazard) {
+        self.hazards.push(hazard);
+    }
 
-                // Do update step
-                self.step();
+    /// Returns the hazards that are not cleared yet
+    pub fn hazards(&self) -> &[Hazard] {
+        &self.hazards
+    }
 
-                // Update grid
-                self.grid = self.generate_grid();
-                
-                // Send update event
-                self.send_update_event();
+    /// Returns the cells of the dead snake bodies that are not cleared yet
+    pub fn remains(&self) -> &[Remains] {
+        &self.remains
+    }
 
-            }
+    /// Adds permanent wall to the field. Pizzas never spawn in walls
+    /// #panics
+    /// If position is outside of the field
+    pub fn add_wall(&mut self, pos : Vector2i) {
+        assert!(self.in_bounds(pos), "Wall is outside of the field");
+        if !self.walls.contains(&pos) {
+            self.walls.push(pos);
         }
     }
 
-    /// REturns number of empty cells in the field.
-    fn num_empty_cells(&self) -> i32 {
-        let mut num = (self.field_size.x * self.field_size.y) as i32;
-        // Substract pizas
-        num -= self.pizzas.len() as i32;
-        // Substract length of every snake that is alive
-        for player in &self.players {
-            if player.alive() {
-                num -= player.snake.as_ref().unwrap().body().len() as i32;
+    /// Adds ice to the field. Snake entering the ice ignores turns for the configured
+    /// number of steps, see `GameConfig::ice_slide_steps`. Pizzas never spawn on ice
+    /// #panics
+    /// If position is outside of the field
+    pub fn add_ice(&mut self, pos : Vector2i) {
+        assert!(self.in_bounds(pos), "Ice is outside of the field");
+        if !self.ice.contains(&pos) {
+            self.ice.push(pos);
+        }
+    }
+
+    /// Replaces all walls with the layout of given preset for the current field size
+    pub fn apply_preset(&mut self, preset : MapPreset) {
+        let size = self.config.field_size;
+        let cells = (0..size.x).flat_map(|x| (0..size.y).map(move |y| Vector2i::new(x, y)));
+        self.walls = match preset {
+            MapPreset::Empty => Vec::new(),
+            MapPreset::Border => cells
+                .filter(|pos| pos.x == 0 || pos.y == 0 || pos.x == size.x - 1 || pos.y == size.y - 1)
+                .collect(),
+            MapPreset::CrossCenter => cells
+                .filter(|pos| pos.x == size.x / 2 || pos.y == size.y / 2)
+                .collect(),
+            MapPreset::RandomBlocks(density, seed) => {
+                let mut rng = StdRng::seed_from_u64(seed);
+                let spawn_cells : Vec<Vector2i> = (0..MAX_PLAYERS)
+                    .map(|player_index| self.make_spawn_snake(player_index))
+                    .flat_map(|snake| {
+                        let ahead = snake.head() + Vector2i::from_direction(snake.look_direction());
+                        snake.body().copied().chain(std::iter::once(ahead)).collect::<Vec<_>>()
+                    })
+                    .collect();
+                cells
+                    .filter(|pos| !spawn_cells.contains(pos))
+                    .filter(|_| rng.gen_bool(density.clamp(0.0, 1.0)))
+                    .collect()
             }
+        };
+    }
+
+    /// Returns the walls inside the field
+    pub fn walls(&self) -> &[Vector2i] {
+        &self.walls
+    }
+
+    /// Restricts the play area to the cells set in the mask. The mask is row-major
+    /// over the field, so the cell (x, y) is `mask[y * width + x]`. Cells outside
+    /// the play area act as walls for snakes, pizzas and spawns. None makes the
+    /// whole field playable
+    /// #panics
+    /// If the mask size doesn't match the field
+    pub fn set_play_mask(&mut self, mask : Option<Vec<bool>>) {
+        if let Some(mask) = &mask {
+            let size = self.config.field_size;
+            assert_eq!(mask.len(), (size.x * size.y) as usize, "Mask doesn't match the field");
         }
-        num
+        self.play_mask = mask;
     }
 
-    /// Calculate the spaw position for the snake with given index.
-    /// All snakes start from the center outwards.
-    /// Maximum of 4 snakes can be spawned.
-    /// Returns position and direction
-    /// 
+    /// Returns the play area mask. None if the whole field is playable
+    pub fn play_mask(&self) -> Option<&[bool]> {
+        self.play_mask.as_deref()
+    }
+
+    /// Returns true if the position inside the field is in a wall or outside the play area
+    fn is_wall(&self, pos : Vector2i) -> bool {
+        let masked = self.play_mask.as_ref()
+            .is_some_and(|mask| !mask[(pos.y * self.config.field_size.x + pos.x) as usize]);
+        masked || self.walls.contains(&pos)
+    }
+
+    /// Returns true if the position is taken by the body of the dead snake
+    fn is_remains(&self, pos : Vector2i) -> bool {
+        self.remains.iter().any(|remains| remains.position == pos)
+    }
+
+    /// Starts the slide if the player snake has entered ice. Entering ice again
+    /// during the slide makes it longer
+    fn slip_on_ice(&mut self, player_index : PlayerIndex) {
+        let position = match self.snake_head(player_index) {
+            Some(head) if self.ice.contains(&head) => head,
+            _ => return,
+        };
+        let slide_steps = self.config.ice_slide_steps;
+        self.players[player_index].slide_until = Some(self.step_count + slide_steps);
+        self.send_event(events::GlobalEvent::IceEntered(events::IceEntered { player_index, position, slide_steps }));
+    }
+
+    /// Kills the player. The snake body stays as remains if enabled in config
+    fn kill_player(&mut self, player_index : PlayerIndex) {
+        if let (Some(lifetime), Some(snake)) = (self.config.remains_lifetime, &self.players[player_index].snake) {
+            let clear_step = self.step_count + 1 + lifetime;
+            self.remains.extend(snake.body().map(|position| Remains { position : *position, clear_step }));
+        }
+        self.players[player_index].kill();
+        self.players[player_index].death_step = Some(self.step_count);
+    }
+
+    /// Returns number of steps done since the game start
+    pub fn step_count(&self) -> u64 {
+        self.step_count
+    }
+
+    /// Helper function that moves player snake. Returns the eaten pizza if any
     /// #panics
-    /// Panics if the index > 4
-    fn calc_spawn_pos(index : PlayerIndex, length : u32, field_size : Vector2i) -> (Vector2i, Direction)
-    {
-        assert!(index < 4);
-        let center = Vector2i::new(field_size.x / 2, field_size.y / 2);
-        let mut pos = center;
-        let dir: Direction;
-        match index {
-            0 => {
-                pos.x -= length as i32;
-                dir = Direction::MinusX;
-            },
-            1 => {
-                pos.y -= length as i32;
-                dir = Direction::MinusY;
+    /// When player dead
+    fn move_player(&mut self, player_index : PlayerIndex) -> Option<PizzaEaten> {
+        let player = &mut self.players[player_index];
+        // Get snake. Snake is expected
+        let snake = player.snake.as_mut().unwrap();
+        // Move the snake
+        snake.allow_self_overlap(!self.config.self_collision);
+        let vacated = snake.move_forward();
+        if self.config.wrap_around && snake.wrap_head(self.config.field_size) {
+            self.wrapped.push(player_index);
+        }
+        // Remember vacated cell if tracking trails
+        if let (Some(cell), Some(_)) = (vacated, self.config.trail_lifetime) {
+            self.trails.retain(|trail| trail.position != cell);
+            self.trails.push(Trail { position : cell, age : 0 });
+        }
+        // see if there is pizza
+        let head_pos = snake.head();
+        if let Some(pizza_index) = self.pizzas.iter().position(|p| p.position == head_pos) {
+            // Eat pizza. Snake at the length cap or without growth doesn't grow, but still scores.
+            // Poison makes the snake shorter instead
+            let at_cap = self.config.max_snake_length
+                .is_some_and(|max_length| snake.pending_length() >= max_length);
+            let kind = self.pizzas[pizza_index].kind;
+            if kind == PizzaKind::Poison {
+                snake.shrink(POISON_SHRINK);
+            }
+            else if self.config.grow_on_eat && !at_cap {
+                snake.eat(1);
+            }
+            player.award_pizza(self.pizzas[pizza_index].kind.value(), self.step_count, self.config.combo_window);
+            // Remove pizza
+            self.pizzas.remove(pizza_index);
+            return Some(PizzaEaten { player_index, position : head_pos });
+        }
+        None
+    }
+
+    /// Applies the predicted action of the player
+    fn apply_action(&mut self, player_index : PlayerIndex, action : ActionStep, outcome : &mut StepOutcome) {
+        match action {
+            ActionStep::Hold => {
+                // Do nothing
             },
-            2 => {
-                pos.x += length as i32;
-                dir = Direction::PlusX;
+            ActionStep::Move => {
+                // Move the snake
+                if let Some(eaten) = self.move_player(player_index) {
+                    outcome.pizzas_eaten.push(eaten);
+                }
+                self.slip_on_ice(player_index);
             },
-            3 => {
-                pos.y += length as i32;
-                dir = Direction::PlusY;
+            ActionStep::Die { cause, position, killer } => {
+                // Warp the snake if possible, kill otherwise
+                let warped = match self.config.warp_on_death {
+                    Some(penalty) => self.warp_player(player_index, cause, position, penalty),
+                    None => false,
+                };
+                if warped {
+                    return;
+                }
+                self.kill_player(player_index);
+                self.send_event(events::GlobalEvent::PlayerDied(events::PlayerDied {
+                    player_index,
+                    cause,
+                    position,
+                    killer,
+                }));
+                // Credit the owner of the body that was hit. Warped snakes didn't die
+                if let (Some(killer), Some(reward)) = (killer, self.config.kill_reward) {
+                    self.credit_kill(killer, player_index, reward);
+                }
             },
-            _ => panic!("Too many snakes"),
         }
-        (pos, dir)
     }
 
-    /// Generate the grid that represents the current state of the game
-    pub fn generate_grid(&self) -> Grid {
-        let mut grid = 
-            Grid::from_elem(
-                (self.field_size.x as usize, self.field_size.y as usize),
-                 GridCell::Empty);
-        // Add pizzas
-        for pizza in &self.pizzas {
-            grid[[pizza.x as usize, pizza.y as usize]] = GridCell::Pizza(PizzaRec{});
+    /// Executes up to `n` update steps without waiting, for fast-forwarding.
+    /// Stops as soon as the game is over and returns the game over event,
+    /// which is also sent to all channels. None if the game goes on after `n` steps
+    /// or was already over before the call, so the game over is reported once
+    pub fn step_n(&mut self, n : u64) -> Option<events::GameOver> {
+        if self.game_over_reason().is_some() {
+            return None;
         }
+        for _ in 0..n {
+            self.step();
+            if let Some(reason) = self.game_over_reason() {
+                return Some(self.send_game_over_event(reason));
+            }
+        }
+        None
+    }
 
-        // Add snakes
-        for (player_i, player) in self.players.iter().enumerate()
-        {
-            // Skip dead
-            if !player.alive() { continue; }
-            // Get snake
-            let snake = player.snake.as_ref().unwrap();
-            let player_i = player_i as PlayerIndex;
-            let snake_len = snake.body().len();
-            for (part_i, body_part) in snake.body().iter().enumerate() {
-
-                let cell = match part_i {
-                    0 => 
-                        GridCell::Snake(SnakeRec{body_part : SnakeBodyPart::Head, player_index : player_i}),
-                    _ if part_i == snake_len - 1 => 
-                        GridCell::Snake(SnakeRec{body_part : SnakeBodyPart::Tail, player_index : player_i}),
-                    _ => 
-                        GridCell::Snake(SnakeRec{body_part : SnakeBodyPart::Body, player_index : player_i}),
-                };
-                grid[[body_part.x as usize, body_part.y as usize]] = cell;
+    /// Executes single update step and reports what happened.
+    /// Events are sent the same way as when driven by the game loop.
+    /// The actions are applied in the configured `ResolutionOrder`
+    pub fn step(&mut self) -> StepOutcome {
+        let mut outcome = StepOutcome::default();
+        let had_pizzas = !self.pizzas.is_empty();
+
+        // Apply queued inputs. Warn about the players flooding the inputs
+        for player_index in 0..self.players.len() {
+            let player = &mut self.players[player_index];
+            player.apply_queued_input();
+            if player.start_tick() == INPUT_FLOOD_STEPS {
+                self.send_event(events::GlobalEvent::InputFlood(events::InputFlood {
+                    player_index,
+                    steps : INPUT_FLOOD_STEPS,
+                }));
             }
         }
 
-        // Return
-        grid
-    }
+        // Predict the step action for every player
+        let mut actions = Vec::new();
+        // Predict action for each snake. Dead snakes just hold.
+        // Collisions are looked up in the grid, so it must be up to date
+        self.refresh_grid();
+        for player_index in 0..self.players.len() {
+            let action= self.predict_next_action_in(Some(&self.grid), player_index);
+            actions.push(action);
+        }
 
-    /// Calculate spawn position for the pizza
-    fn calc_spawn_pos_for_pizza(grid : &Grid, estimated_free_cells : i32) -> Vector2i {
-        // Panic if estimated free cells is < 0
-        assert!(estimated_free_cells >= 0);
-        // Randomly generate the free cell index
-        let mut free_cell_counter = rand::random::<usize>() % estimated_free_cells as usize;   
-        // Loop the grid and find empty cell with the given index
-        for ((x, y), cell) in grid.indexed_iter() {
-            if *cell == GridCell::Empty {
-                if free_cell_counter == 0 {
-                    return Vector2i::new(x as i32, y as i32);
-                }
-                else {
-                     free_cell_counter -= 1; 
-                };
+        // Age the trails left on previous steps
+        self.age_trails();
+
+        // Reset combos of the players that waited too long
+        if let Some(window) = self.config.combo_window {
+            for player in &mut self.players {
+                player.expire_combo(self.step_count, window);
             }
         }
-        // Should never happen
-        panic!("Could not find free cell");
-    }
 
-    /// Predicts the next action that particular player snake will do in next step.
-    /// #panics
-    /// If player is dead
-    fn predict_next_action( &self, player_index : PlayerIndex) -> ActionStep {
-        // Dead players alwats hold
-        if !self.players[player_index as usize].alive() {
-            return ActionStep::Hold;
+        // Apply the actions
+        self.wrapped.clear();
+        outcome.actions = actions.clone();
+        for player_index in self.resolution_order() {
+            self.apply_action(player_index, actions[player_index], &mut outcome);
         }
-        // First estimate the coordinate of potential new head
-        let player = &self.players[player_index as usize];
-        let player_snake = player.snake.as_ref().unwrap();
-        let mut new_head = player_snake.body()[0];
-        new_head += Vector2i::from_direction(player_snake.look_direction());
-        // Check if the new head is inside the field
-        if new_head.x < 0 || new_head.x >= self.field_size.x ||
-           new_head.y < 0 || new_head.y >= self.field_size.y {
-            return ActionStep::Die;
+        // Count steps without any progress
+        if outcome.actions.iter().all(|action| *action == ActionStep::Hold) {
+            self.stalled_steps += 1;
+        }
+        else {
+            self.stalled_steps = 0;
         }
 
-        // See if new head position is occupied by body OR head of any snake
-        for player in &self.players {
-            if !player.alive() { continue; }
-            // Get the snake ref
-            let any_snake = player.snake.as_ref().unwrap();
-            // Check all body parts except last (tail)
-            for body_part in &any_snake.body()[..any_snake.body().len() - 1] {
-                if *body_part == new_head {
-                    return ActionStep::Die;
-                }
+        // Drift pizzas towards snakes
+        if let Some(interval) = self.config.pizza_gravity_interval {
+            if (self.step_count + 1).is_multiple_of(interval) {
+                self.apply_pizza_gravity();
             }
         }
 
-        // If any other snake compete to the same head position, then hold
-        // Loop snake with index. Skip current.
-        for (other_player_index, other_player) in self.players.iter().enumerate() {
-            if other_player_index == player_index as usize || !other_player.alive() {
-                continue;
+        // Notify if someone took the lead
+        self.update_leader();
+
+        // Spawn pizza if there is none and the supply allows
+        if self.pizzas.is_empty() && self.has_pizza_supply() {
+            // We need up to date grid
+            self.refresh_grid();
+            // There may be no place for pizza
+            if let Some(spawn_pos) = self.pizza_spawn_pos() {
+                self.pizzas.push(Pizza::normal(spawn_pos));
+                self.pizzas_spawned += 1;
+                outcome.pizzas_spawned.push(spawn_pos);
             }
-            // Get other snake
-            let other_snake = other_player.snake.as_ref().unwrap();
-            // Estimate this snake expected head position
-            let mut other_new_head = other_snake.body()[0];
-            other_new_head += Vector2i::from_direction(other_snake.look_direction());
-            // If this position is the same - hold
-            if other_new_head == new_head {
-                return ActionStep::Hold;
+        }
+
+        self.step_count += 1;
+        // Notify when the last pizza is gone for good
+        if had_pizzas && self.is_board_cleared() {
+            self.send_event(events::GlobalEvent::BoardCleared(events::BoardCleared { step : self.step_count }));
+        }
+        // Forget cleared hazards
+        let step_count = self.step_count;
+        self.hazards.retain(|hazard| hazard.clear_step > step_count);
+        self.remains.retain(|remains| remains.clear_step > step_count);
+        // Stop the slides that are over
+        for player_index in 0..self.players.len() {
+            if self.players[player_index].slide_until.is_some_and(|until| until < step_count) {
+                self.players[player_index].slide_until = None;
+                self.send_event(events::GlobalEvent::IceLeft(events::IceLeft { player_index }));
             }
         }
-        // In all other cases snake can move
-        ActionStep::Move
+        // Shrink the arena on schedule
+        if let Some(interval) = self.config.shrink_interval {
+            if self.step_count.is_multiple_of(interval) {
+                self.shrink_arena();
+            }
+        }
+        // Run the custom logic. The hook set by the hook itself wins
+        if let Some(mut hook) = self.tick_hook.take() {
+            hook(self);
+            if self.tick_hook.is_none() {
+                self.tick_hook = Some(hook);
+            }
+        }
+        outcome
     }
 
-    /// Small utils function that returns summary for all players
-    fn get_players_summary(&self) -> Vec<events::PlayerSummary> {
-        self.players.iter().map(|player| player.summary()).collect()
-    }
-    /// Sends the game over event to all channels
-    fn send_game_over_event(&self) {
-        // Create game over event
-        let game_over_event = events::GameOver{
-            players_summary : self.get_players_summary()
+    /// Moves the dying snake to a random free place, shortened by the penalty.
+    /// The new body is a straight line of empty cells with room for the snake
+    /// ahead of it. Cells other snakes are about to enter are avoided.
+    /// Returns false if there is no such place
+    fn warp_player(&mut self, player_index : PlayerIndex, cause : events::DeathCause, from : Vector2i,
+                   penalty : WarpPenalty) -> bool {
+        let old_snake = match self.players[player_index].snake.take() {
+            Some(snake) => snake,
+            None => return false,
         };
-        // Send event to all channels.
-        for channel in &self.global_event_channels {
-            match channel.send(events::GlobalEvent::GameOver(game_over_event.clone())) {
-                Ok(_) => {},
-                Err(_) => {// Don't care about dead channels
-                }
-            };
+        let length = (old_snake.length() * penalty.length_kept_percent as usize / 100).max(2);
+        // Grid without the warped snake and with the cells taken on this step
+        let mut grid = self.generate_grid();
+        for other in self.players.iter().filter_map(|player| player.snake.as_ref()) {
+            let next_head = self.wrap_pos(other.head() + Vector2i::from_direction(other.look_direction()));
+            if self.in_bounds(next_head) {
+                grid.set_at(next_head.x as usize, next_head.y as usize, GridCell::Wall);
+            }
         }
-    }
-    /// Sends update event to all channels
-    fn send_update_event(&self) {
-        // Create update event
-        let update_event = events::Update{
-            grid : self.grid.clone(),
-            players_summary : self.get_players_summary(),
+        // Find all places that fit
+        let mut places = Vec::new();
+        for ((x, y), _) in grid.indexed_iter() {
+            let head = Vector2i::new(x as i32, y as i32);
+            for direction in Direction::all() {
+                let body = build_initial_body(head, direction, length as u32);
+                if !body.iter().all(|pos| self.in_bounds(*pos) && grid.at(pos.x as usize, pos.y as usize) == GridCell::Empty) {
+                    continue;
+                }
+                let mut placed_grid = grid.clone();
+                for pos in &body {
+                    placed_grid.set_at(pos.x as usize, pos.y as usize, GridCell::Wall);
+                }
+                if grid::reachable_area(&placed_grid, head + Vector2i::from_direction(direction)) >= length {
+                    places.push((head, direction));
+                }
+            }
+        }
+        if places.is_empty() {
+            self.players[player_index].snake = Some(old_snake);
+            return false;
+        }
+        let (head, direction) = places[self.rng.gen_range(0..places.len())];
+        let player = &mut self.players[player_index];
+        player.respawn(Snake::new(head, direction, length as u32));
+        let score_lost = penalty.score_loss.min(player.score);
+        player.score -= score_lost;
+        self.send_event(events::GlobalEvent::PlayerWarped(events::PlayerWarped {
+            player_index,
+            cause,
+            from,
+            to : head,
+            length,
+            score_lost,
+        }));
+        true
+    }
+
+    /// Picks the cell for a new pizza from the up to date grid. Cells too close to
+    /// snake heads are avoided, unless there are no other free cells
+    fn pizza_spawn_pos(&mut self) -> Option<Vector2i> {
+        let min_distance = self.config.spawn_policy.pizza_distance as i32;
+        if min_distance > 0 {
+            let heads : Vec<Vector2i> = self.living_snakes()
+                .filter_map(|(_, mut body)| body.next().copied())
+                .collect();
+            // Cells near the heads are hidden from the spawner
+            let mut grid = self.grid.clone();
+            let mut free_cells = 0;
+            for ((x, y), cell) in grid.indexed_iter_mut() {
+                if *cell != GridCell::Empty {
+                    continue;
+                }
+                let pos = Vector2i::new(x as i32, y as i32);
+                if heads.iter().any(|head| (head.x - pos.x).abs() + (head.y - pos.y).abs() < min_distance) {
+                    *cell = GridCell::Wall;
+                }
+                else {
+                    free_cells += 1;
+                }
+            }
+            if free_cells > 0 {
+                return self.pizza_spawner.spawn_pos(&grid, free_cells);
+            }
+        }
+        let free_cells = self.num_empty_cells();
+        self.pizza_spawner.spawn_pos(&self.grid, free_cells)
+    }
+
+    /// Returns the player indices in the order their actions are applied on this step
+    fn resolution_order(&mut self) -> Vec<PlayerIndex> {
+        let mut order : Vec<PlayerIndex> = (0..self.players.len()).collect();
+        match self.config.resolution_order {
+            ResolutionOrder::PlayerIndex => {},
+            ResolutionOrder::HighestScore => {
+                // Stable sort keeps the index order for equal scores
+                order.sort_by_key(|&player_index| std::cmp::Reverse(self.players[player_index].score));
+            },
+            ResolutionOrder::Shuffled => order.shuffle(&mut self.rng),
+        }
+        order
+    }
+
+    /// Turns the outermost ring of the playable area into walls. Kills the snakes
+    /// caught in the ring and removes the pizzas there. At least the central
+    /// cell or line of the field stays playable
+    fn shrink_arena(&mut self) {
+        let size = self.config.field_size;
+        let ring = self.shrunk_rings;
+        if 2 * ring + 1 >= size.x.min(size.y) {
+            return;
+        }
+        self.shrunk_rings += 1;
+        let in_ring = |pos : &Vector2i| {
+            pos.x.min(pos.y).min(size.x - 1 - pos.x).min(size.y - 1 - pos.y) == ring
         };
-        // Send event to all channels.
-        for channel in &self.global_event_channels {
-            match channel.send(events::GlobalEvent::Update(update_event.clone())) {
-                Ok(_) => {},
-                Err(_) => {// Don't care about dead channels
+        for x in 0..size.x {
+            for y in 0..size.y {
+                let pos = Vector2i::new(x, y);
+                if in_ring(&pos) {
+                    self.add_wall(pos);
                 }
+            }
+        }
+        self.pizzas.retain(|pizza| !in_ring(&pizza.position));
+        for player_index in 0..self.players.len() {
+            let position = match self.players[player_index].snake.as_ref()
+                .and_then(|snake| snake.body().find(|part| in_ring(part)).copied()) {
+                Some(position) => position,
+                None => continue,
             };
+            self.kill_player(player_index);
+            self.send_event(events::GlobalEvent::PlayerDied(events::PlayerDied {
+                player_index,
+                cause : events::DeathCause::Wall,
+                position,
+                killer : None,
+            }));
         }
+        self.send_event(events::GlobalEvent::ArenaShrunk(events::ArenaShrunk {
+            step : self.step_count,
+            rings : self.shrunk_rings,
+        }));
     }
-}
 
+    /// Rewards the killer if it's still alive. Growth respects the length cap
+    fn credit_kill(&mut self, killer : PlayerIndex, victim : PlayerIndex, reward : KillReward) {
+        let player = &mut self.players[killer];
+        let snake = match player.snake.as_mut() {
+            Some(snake) => snake,
+            None => return,
+        };
+        let growth = match self.config.max_snake_length {
+            Some(max_length) => reward.growth.min(max_length.saturating_sub(snake.pending_length()) as u32),
+            None => reward.growth,
+        };
+        snake.eat(growth as i32);
+        player.score += reward.score;
+        self.send_event(events::GlobalEvent::KillCredited(events::KillCredited {
+            killer,
+            victim,
+            score : reward.score,
+            growth,
+        }));
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Finds the top scoring living player and sends the event if it has changed.
+    /// Nobody leads until somebody scores. On a tie the current leader keeps
+    /// the lead, otherwise the player with lower index wins the tie
+    fn update_leader(&mut self) {
+        let mut new_leader = self.leader.filter(|&leader| self.players[leader].alive());
+        for (player_index, player) in self.players.iter().enumerate() {
+            if !player.alive() || player.score == 0 {
+                continue;
+            }
+            match new_leader {
+                Some(leader) if self.players[leader].score >= player.score => {},
+                _ => new_leader = Some(player_index),
+            }
+        }
+        if new_leader != self.leader {
+            self.leader = new_leader;
+            if let Some(leader) = new_leader {
+                self.send_event(events::GlobalEvent::LeadChanged(events::LeadChanged {
+                    new_leader : leader,
+                    score : self.players[leader].score,
+                }));
+            }
+        }
+    }
 
-    // Test each new player gets new index
-    #[test]
-    fn test_register_player() {
-        let mut game = Game::new( Vector2i::new(10, 10));
-        let player1 = game.register_player(None);
-        let player2 = game.register_player(None);
-        let player3 = game.register_player(None);
-        assert_eq!(player1, 0);
-        assert_eq!(player2, 1);
-        assert_eq!(player3, 2);
-        // Test that snake is created
-        assert!(game.players[player1].snake.is_some());
-        assert!(game.players[player2].snake.is_some());
-        assert!(game.players[player3].snake.is_some());
+    /// Moves every pizza one cell towards the closest living snake head.
+    /// The pizza tries the axis with the longer distance first. It stays
+    /// in place if neither cell towards the head is empty
+    fn apply_pizza_gravity(&mut self) {
+        let heads : Vec<Vector2i> = self.players.iter()
+            .filter_map(|player| player.snake.as_ref())
+            .map(|snake| snake.head())
+            .collect();
+        for pizza_index in 0..self.pizzas.len() {
+            let position = self.pizzas[pizza_index].position;
+            let distance = |head : &&Vector2i| (head.x - position.x).abs() + (head.y - position.y).abs();
+            let head = match heads.iter().min_by_key(distance) {
+                Some(head) => *head,
+                None => return,
+            };
+            let offset = head - position;
+            let step_x = Vector2i::new(offset.x.signum(), 0);
+            let step_y = Vector2i::new(0, offset.y.signum());
+            let candidates = if offset.x.abs() >= offset.y.abs() { [step_x, step_y] } else { [step_y, step_x] };
+            // Moves inside the field only, so the target is never a wall
+            let target = candidates.iter()
+                .filter(|step| **step != Vector2i::zero())
+                .map(|step| position + *step)
+                .find(|target| self.cell_at(*target) == GridCell::Empty);
+            if let Some(target) = target {
+                self.pizzas[pizza_index].position = target;
+            }
+        }
     }
 
-    // Test calc_spawn_pos
-    #[test]
-    fn test_calc_spawn_pos() {
-        let field_size = Vector2i::new(10, 10);
-        let (pos, dir) = Game::calc_spawn_pos(0, 3, field_size);
-        assert_eq!(pos, Vector2i::new(2, 5));
-        assert_eq!(dir, Direction::MinusX);
+    /// Ages all trails by 1 step and forgets the expired ones
+    fn age_trails(&mut self) {
+        let lifetime = match self.config.trail_lifetime {
+            Some(lifetime) => lifetime,
+            None => return,
+        };
+        for trail in &mut self.trails {
+            trail.age += 1;
+        }
+        self.trails.retain(|trail| trail.age < lifetime);
+    }
 
-        let (pos, dir) = Game::calc_spawn_pos(1, 3, field_size);
-        assert_eq!(pos, Vector2i::new(5, 2));
-        assert_eq!(dir, Direction::MinusY);
+    /// Starts the game loop. This function will return only when game is over.
+    /// Or shutdown command was received.
+    /// Returns the game over event, which is also sent to all channels.
+    /// None if the loop was shut down before the game was over
+    pub fn game_loop(&mut self, shutdown_rx : mpsc::Receiver<()>) -> Option<events::GameOver> {
 
-        let (pos, dir) = Game::calc_spawn_pos(2, 3, field_size);
-        assert_eq!(pos, Vector2i::new(8, 5));
-        assert_eq!(dir, Direction::PlusX);
+        // Generate initial grid
+        self.refresh_grid();
 
-        let (pos, dir) = Game::calc_spawn_pos(3, 3, field_size);
-        assert_eq!(pos, Vector2i::new(5, 8));
-        assert_eq!(dir, Direction::PlusY);
-    }
-    // Calling test_cal_spawn_pos with index > 4 should panic
-    #[test]
-    #[should_panic]
-    fn test_calc_spawn_pos_panic() {
-        Game::calc_spawn_pos(4, 3, Vector2i::new(10, 10));
-    }
+        // Remember when the game has started
+        self.start_time = Some(time::Instant::now());
 
-    // Test num_empty_cells
-    #[test]
-    fn test_num_empty_cells() {
-        let mut game = Game::new( Vector2i::new(10, 10));
-        assert_eq!(game.num_empty_cells(), 100);
-        game.register_player(None);
-        assert_eq!(game.num_empty_cells(), 100 - INITIAL_LENGTH as i32);
-        game.register_player(None);
-        assert_eq!(game.num_empty_cells(), 100 - 2 * INITIAL_LENGTH as i32);
-        // Register dead player
-        game.register_player(None);
-        game.players[2].snake = None;
-        assert_eq!(game.num_empty_cells(), 100 - 2 * INITIAL_LENGTH as i32);
-        
-        // Add some food
-        game.pizzas.push(Vector2i::new(0, 0));
-        game.pizzas.push(Vector2i::new(0, 1));
-        
-        assert_eq!(game.num_empty_cells(), 100 - 2 * INITIAL_LENGTH as i32 - 2);
+        // Start the timers. The accumulator holds the time not yet spent on updates
+        let mut timer = time::Instant::now();
+        let mut accumulator = time::Duration::ZERO;
+        let mut heartbeat_timer = time::Instant::now();
+
+        // Start actual loop
+        loop {
+            // The game is over when all players are dead or nobody can progress
+            if let Some(reason) = self.game_over_reason()
+            {
+                return Some(self.send_game_over_event(reason));
+            }
+            //Check shutdown
+            if shutdown_rx.try_recv().is_ok() {
+                return None;
+            }
+
+            // Read all players inputs on every loop
+            for player in &mut self.players {
+                player.read_inputs(self.config.input_buffer, self.config.max_inputs_per_tick);
+            }
+
+            // Read game commands
+            self.read_commands();
+
+            // Accumulate the time elapsed. Paused game doesn't advance on timer
+            let now = time::Instant::now();
+            if self.paused {
+                accumulator = time::Duration::ZERO;
+            }
+            else {
+                accumulator += now - timer;
+                // Do all the updates that are due. Leftover time counts towards the next one
+                for _ in 0..Self::take_due_steps(&mut accumulator, UPDATE_INTERVAL, MAX_STEPS_PER_PASS) {
+                    if self.game_over_reason().is_some() {
+                        break;
+                    }
+                    self.update();
+                }
+            }
+            timer = now;
+
+            // Signal liveness. Paused game sends heartbeats too
+            if let Some(interval) = self.config.heartbeat_interval {
+                if heartbeat_timer.elapsed() >= interval {
+                    heartbeat_timer = time::Instant::now();
+                    self.send_event(events::GlobalEvent::Heartbeat(events::Heartbeat {
+                        step : self.step_count,
+                        alive_count : self.alive_count(),
+                    }));
+                }
+            }
+
+            // Don't burn CPU while waiting for the next update
+            std::thread::sleep(Self::calc_sleep_duration(accumulator + timer.elapsed(), UPDATE_INTERVAL));
+        }
     }
 
-    // Test generate gird
-    #[test]
-    fn test_generate_grid() {
-        let mut game = Game::new( Vector2i::new(3, 3));
-        let player1 = game.register_player(None);
-        // Manually set the snake points to make it easier to test
-        game.players[player1].snake.as_mut().unwrap().set_body(vec![
-            Vector2i::new(0, 0),
-            Vector2i::new(0, 1),
-            Vector2i::new(0, 2),
-        ]);
-        // Create another dead player, which should have 0 effect on grid
-        {
-            let player2 = game.register_player(None);
-            game.players[player2].snake = None;
+    /// Returns the number of update steps due for the accumulated time and removes
+    /// their time from the accumulator. At most `max_steps` are returned, the time of
+    /// the steps beyond that is dropped and only the leftover below the interval is kept
+    fn take_due_steps(accumulator : &mut time::Duration, interval : time::Duration, max_steps : u32) -> u32 {
+        let mut steps = 0;
+        while *accumulator >= interval && steps < max_steps {
+            *accumulator -= interval;
+            steps += 1;
+        }
+        if *accumulator >= interval {
+            *accumulator = time::Duration::from_nanos((accumulator.as_nanos() % interval.as_nanos()) as u64);
         }
+        steps
+    }
 
-        // Add one pizza
-        game.pizzas.push(Vector2i::new(2, 2));
-        // Generate grid
-        let grid = game.generate_grid();
-        // Check grid
-        assert_eq!(grid[[0, 0]], GridCell::Snake(SnakeRec{body_part : SnakeBodyPart::Head, player_index : player1}));
-        assert_eq!(grid[[0, 1]], GridCell::Snake(SnakeRec{body_part : SnakeBodyPart::Body, player_index : player1}));
-        assert_eq!(grid[[0, 2]], GridCell::Snake(SnakeRec{body_part : SnakeBodyPart::Tail, player_index : player1}));
-        assert_eq!(grid[[1, 0]], GridCell::Empty);
-        assert_eq!(grid[[1, 1]], GridCell::Empty);
-        assert_eq!(grid[[1, 2]], GridCell::Empty);
-        assert_eq!(grid[[2, 0]], GridCell::Empty);
-        assert_eq!(grid[[2, 1]], GridCell::Empty);
-        assert_eq!(grid[[2, 2]], GridCell::Pizza(PizzaRec{}));
+    /// Calculates how long the game loop can sleep. It sleeps until the next update,
+    /// but wakes up regularly to stay responsive to inputs, commands and shutdown
+    fn calc_sleep_duration(elapsed : time::Duration, interval : time::Duration) -> time::Duration {
+        interval.saturating_sub(elapsed).min(MAX_SLEEP_INTERVAL)
     }
 
-    // Test game_loop shutdown
-    #[test]
-    fn test_game_loop_shutdown() {
-        // Create mpsc channel
-        let (tx, rx) = mpsc::channel();
+    /// Does update step, updates the grid and notifies about it
+    fn update(&mut self) {
+        let start = time::Instant::now();
 
-        let mut game = Box::new(Game::new( Vector2i::new(10, 10)));
+        // Hold the snakes during the start countdown, but show the board
+        match self.countdown {
+            Some(remaining) if remaining > 0 => {
+                self.send_event(events::GlobalEvent::CountdownTick(events::CountdownTick { remaining }));
+                self.countdown = Some(remaining - 1);
+                self.refresh_grid();
+                self.send_update_event();
+                return;
+            },
+            Some(_) => {
+                self.send_event(events::GlobalEvent::CountdownTick(events::CountdownTick { remaining : 0 }));
+                self.countdown = None;
+            },
+            None => {},
+        }
 
-        let handle = std::thread::spawn(move || {
-            // Start game loop
-            game.game_loop(rx);
-        });
-        // Send shutdown immediately;
-        tx.send(()).unwrap();
+        // Let the AI play in demo mode
+        if self.demo {
+            DemoController::steer(self);
+        }
 
-        // Wait 20 times for 50 ms. Every time checking that thread finished
-        for _ in 0..20 {
-            std::thread::sleep(std::time::Duration::from_millis(50));
-            if handle.is_finished() {
-                break;
+        // Do update step
+        self.step();
+
+        // Update grid
+        self.refresh_grid();
+
+        // Report the time spent if enabled
+        let duration = start.elapsed();
+        if self.config.report_tick_timing {
+            self.send_event(events::GlobalEvent::TickTiming(events::TickTiming {
+                step : self.step_count,
+                duration,
+            }));
+        }
+        // Warn if the game falls behind
+        if let Some(budget) = self.config.tick_budget {
+            if duration > budget {
+                self.tick_overruns += 1;
+                self.send_event(events::GlobalEvent::TickOverrun(events::TickOverrun {
+                    step : self.step_count,
+                    duration,
+                    budget,
+                    overruns : self.tick_overruns,
+                }));
             }
         }
 
-        // Check the thread is done
-        assert!(handle.is_finished());
+        // Send update event
+        self.send_update_event();
 
-        // Wait for thread to finish
-        handle.join().unwrap();
+        // Send the thumbnail on schedule
+        if let Some(thumbnail) = self.config.thumbnail {
+            if self.step_count.is_multiple_of(thumbnail.interval.max(1)) {
+                let (cells, width, height) = grid::thumbnail(&self.grid, thumbnail.max_width, thumbnail.max_height);
+                self.send_event(events::GlobalEvent::Thumbnail(events::Thumbnail { cells, width, height }));
+            }
+        }
     }
 
-    // Test predict_next_action
-    #[test]
-    fn test_predict_next_action() {
-        // Create small 4x4 game
-        let mut game = Game::new( Vector2i::new(4, 4));
-        let player_index0 = game.register_player(None);
-        
-        // Single snake going out of bounds dies
-        {
-            let snake0 = game.players[player_index0].snake.as_mut().unwrap();
-            snake0.set_body(vec![
-                Vector2i::new(0, 3),
-                Vector2i::new(0, 2),
-            ]);
-            assert!( snake0.try_set_look_direction( Direction::PlusY ));
-            assert_eq!(game.predict_next_action(player_index0), ActionStep::Die);
-        }
-        // Single snake going to current tail pos: moves. This is because during the move
-        // this cell will be freed
-        {
-            let snake0 = game.players[player_index0].snake.as_mut().unwrap();
-            snake0.set_body(vec![
-                Vector2i::new(1, 2),
-                Vector2i::new(2, 2),
-                Vector2i::new(2, 1), 
-                Vector2i::new(1, 1),   
-            ]);     
-            assert!( snake0.try_set_look_direction( Direction::MinusY ));
-            assert_eq!(game.predict_next_action(player_index0), ActionStep::Move);
-        }
-        // The snake that attempts to move to it's own body pos - dies
-        {
-            let snake0 = game.players[player_index0].snake.as_mut().unwrap();
-            snake0.set_body(vec![
-                Vector2i::new(1, 2),
-                Vector2i::new(2, 2),
-                Vector2i::new(2, 1), 
-                Vector2i::new(1, 1),   
-                Vector2i::new(0, 1), 
-            ]);     
-            assert!( snake0.try_set_look_direction( Direction::MinusY ));
-            assert_eq!(game.predict_next_action(player_index0), ActionStep::Die);
+    /// Reads and executes all pending game commands
+    fn read_commands(&mut self) {
+        while let Some(Ok(command)) = self.command_channel.as_ref().map(|c| c.try_recv()) {
+            match command {
+                GameCommand::Pause => self.paused = true,
+                GameCommand::Resume => self.paused = false,
+                GameCommand::StepOnce => {
+                    // Single steps are only done while paused
+                    if self.paused {
+                        self.update();
+                    }
+                }
+                GameCommand::StartDemo => self.demo = true,
+                GameCommand::StopDemo => self.demo = false,
+            }
         }
-        // Add one more small snake for further tests
-        let player_index1 = game.register_player(None);
-        {
-            let snake1 = game.players[player_index1].snake.as_mut().unwrap();
-                snake1.set_body(vec![
-                    Vector2i::new(3, 2),
-                    Vector2i::new(3, 1),
-                    Vector2i::new(3, 0),
-                ]);
-            assert!( snake1.try_set_look_direction( Direction::PlusY ));
+    }
+
+    /// REturns number of empty cells in the field.
+    fn num_empty_cells(&self) -> i32 {
+        // Collect every taken cell once, the same cell may be taken by several things
+        let mut taken : HashSet<Vector2i> = self.walls.iter().copied().collect();
+        // Add the cells outside the play area
+        if let Some(mask) = &self.play_mask {
+            let width = self.config.field_size.x;
+            taken.extend(mask.iter().enumerate()
+                .filter(|(_, playable)| !**playable)
+                .map(|(i, _)| Vector2i::new(i as i32 % width, i as i32 / width)));
         }
-        // Add dead player. Mainly to make sure it doesn't crash. It should cause no real affects.
-        {
-            let player_index2 = game.register_player(None);
-            game.players[player_index2].snake = None;
-            // Dead player always hold
-            assert_eq!(game.predict_next_action(player_index2), ActionStep::Hold);
+        // Add pizzas, shown hazards, ice and remains
+        taken.extend(self.pizzas.iter().map(|pizza| pizza.position));
+        taken.extend(self.hazards.iter()
+            .filter(|hazard| hazard.cell_at_step(self.step_count).is_some())
+            .map(|hazard| hazard.position));
+        taken.extend(self.ice.iter().copied());
+        taken.extend(self.remains.iter().map(|remains| remains.position));
+        // Add the bodies of the snakes that are alive
+        for player in self.players.iter().filter(|player| player.alive()) {
+            taken.extend(player.snake.as_ref().unwrap().body().copied());
         }
+        self.config.field_size.x * self.config.field_size.y - taken.len() as i32
+    }
 
-        // When snake tries to move to the head position of other snake - it dies
-        {
-            let snake0 = game.players[player_index0].snake.as_mut().unwrap();
-            snake0.set_body(vec![
-                Vector2i::new(2, 2),
-                Vector2i::new(1, 2),
+    /// Calculate the spaw position for the snake with given index.
+    /// Every snake is placed on its own side of the center and faces the center.
+    /// The body trails behind the head towards the wall, see `build_initial_body`.
+    /// Maximum of 4 snakes can be spawned.
+    /// Offset from the center is clamped to leave room for the tail between the head and the wall.
+    /// Returns head position and direction
+    /// 
+    /// #panics
+    /// Panics if the index >= MAX_PLAYERS
+    fn calc_spawn_pos(index : PlayerIndex, length : u32, field_size : Vector2i) -> (Vector2i, Direction)
+    {
+        assert!(index < MAX_PLAYERS);
+        let center = Vector2i::new(field_size.x / 2, field_size.y / 2);
+        let mut pos = center;
+        let length = length as i32;
+        let dir = match index {
+            0 => {
+                pos.x -= length.min(center.x - 1).max(0);
+                Direction::PlusX
+            },
+            1 => {
+                pos.y -= length.min(center.y - 1).max(0);
+                Direction::PlusY
+            },
+            2 => {
+                pos.x += length.min(field_size.x - 2 - center.x).max(0);
+                Direction::MinusX
+            },
+            3 => {
+                pos.y += length.min(field_size.y - 2 - center.y).max(0);
+                Direction::MinusY
+            },
+            _ => panic!("Too many snakes"),
+        };
+        (pos, dir)
+    }
+
+    /// Generate the grid that represents the current state of the game
+    pub fn generate_grid(&self) -> Grid {
+        let mut grid = grid::new_grid(self.config.field_size.x as usize, self.config.field_size.y as usize);
+        self.regenerate_grid_into(&mut grid);
+        grid
+    }
+
+    /// Same as `generate_grid`, but refills the given grid. The memory is reused
+    /// if the grid has the field size, so regenerating every step doesn't allocate
+    pub fn regenerate_grid_into(&self, grid : &mut Grid) {
+        let (width, height) = (self.config.field_size.x as usize, self.config.field_size.y as usize);
+        if grid.width() == width && grid.height() == height {
+            grid.fill(GridCell::Empty);
+        }
+        else {
+            *grid = grid::new_grid(width, height);
+        }
+        // Add hazards. Everything else is drawn over them
+        for hazard in &self.hazards {
+            if let Some(cell) = hazard.cell_at_step(self.step_count) {
+                grid.set_at(hazard.position.x as usize, hazard.position.y as usize, cell);
+            }
+        }
+        // Add ice
+        for ice in &self.ice {
+            grid.set_at(ice.x as usize, ice.y as usize, GridCell::Ice);
+        }
+        // Add walls and the cells outside the play area
+        for wall in &self.walls {
+            grid.set_at(wall.x as usize, wall.y as usize, GridCell::Wall);
+        }
+        if let Some(mask) = &self.play_mask {
+            for (i, _) in mask.iter().enumerate().filter(|(_, playable)| !**playable) {
+                grid.set_at(i % width, i / width, GridCell::Wall);
+            }
+        }
+        // Add the bodies of the dead snakes
+        for remains in &self.remains {
+            grid.set_at(remains.position.x as usize, remains.position.y as usize, GridCell::Remains);
+        }
+        // Add pizzas
+        for pizza in &self.pizzas {
+            grid.set_at(pizza.position.x as usize, pizza.position.y as usize,
+                GridCell::Pizza(PizzaRec{ kind : pizza.kind }));
+        }
+
+        // Add snakes
+        for (player_i, player) in self.players.iter().enumerate()
+        {
+            // Skip dead
+            if !player.alive() { continue; }
+            // Get snake
+            let snake = player.snake.as_ref().unwrap();
+            let player_i = player_i as PlayerIndex;
+            let snake_len = snake.length();
+            // Parts closer to the head are drawn over the body passing under them
+            for (part_i, body_part) in snake.body().enumerate().rev() {
+                let cell = Self::snake_cell(player_i, part_i, snake_len);
+                grid.set_at(body_part.x as usize, body_part.y as usize, cell);
+            }
+        }
+    }
+
+    /// Regenerates the game grid in place
+    fn refresh_grid(&mut self) {
+        let mut grid = std::mem::replace(&mut self.grid, grid::new_grid(0, 0));
+        self.regenerate_grid_into(&mut grid);
+        self.grid = grid;
+    }
+
+    /// Returns the grid cell for the given part of the snake.
+    /// Tail takes precedence over neck, so snake of length 2 has no neck
+    fn snake_cell(player_index : PlayerIndex, part_i : usize, snake_len : usize) -> GridCell {
+        let body_part = match part_i {
+            0 => SnakeBodyPart::Head,
+            _ if part_i == snake_len - 1 => SnakeBodyPart::Tail,
+            1 => SnakeBodyPart::Neck,
+            _ => SnakeBodyPart::Body,
+        };
+        GridCell::Snake(SnakeRec{ body_part, player_index })
+    }
+
+    /// Returns the contents of single cell without generating the whole grid.
+    /// The result is the same as the cell of `generate_grid`
+    /// #panics
+    /// If position is outside of the field
+    pub fn cell_at(&self, pos : Vector2i) -> GridCell {
+        assert!(self.in_bounds(pos), "Position is outside of the field");
+        // Snakes are drawn over pizzas, and later snakes over earlier ones
+        for (player_index, player) in self.players.iter().enumerate().rev() {
+            if let Some(snake) = &player.snake {
+                if let Some(part_i) = snake.body().position(|p| *p == pos) {
+                    return Self::snake_cell(player_index, part_i, snake.length());
+                }
+            }
+        }
+        if let Some(pizza) = self.pizzas.iter().rev().find(|p| p.position == pos) {
+            return GridCell::Pizza(PizzaRec{ kind : pizza.kind });
+        }
+        // Remains are drawn over walls, then walls over ice and hazards
+        if self.is_remains(pos) {
+            return GridCell::Remains;
+        }
+        if self.is_wall(pos) {
+            return GridCell::Wall;
+        }
+        if self.ice.contains(&pos) {
+            return GridCell::Ice;
+        }
+        self.hazards.iter().rev()
+            .filter(|hazard| hazard.position == pos)
+            .find_map(|hazard| hazard.cell_at_step(self.step_count))
+            .unwrap_or(GridCell::Empty)
+    }
+
+    /// Returns true if the player can turn to given direction and survive the next step.
+    /// Reversing the last move is not possible, so it is never safe.
+    /// Dead players have no safe moves. Doesn't change the game state
+    pub fn is_move_safe(&self, player_index : PlayerIndex, direction : Direction) -> bool {
+        self.check_move_safe(None, player_index, direction)
+    }
+
+    /// Same as `is_move_safe`, with the snakes looked up in the given grid of the current
+    /// state. Checking several moves against one grid is faster than looking up the cells
+    pub fn is_move_safe_in(&self, grid : &Grid, player_index : PlayerIndex, direction : Direction) -> bool {
+        self.check_move_safe(Some(grid), player_index, direction)
+    }
+
+    /// Implements `is_move_safe` and `is_move_safe_in`
+    fn check_move_safe(&self, grid : Option<&Grid>, player_index : PlayerIndex, direction : Direction) -> bool {
+        let snake = match self.players[player_index].snake.as_ref() {
+            Some(snake) => snake,
+            None => return false,
+        };
+        if !snake.can_look(direction) {
+            return false;
+        }
+        !matches!(self.predict_action_in(grid, player_index, direction), ActionStep::Die { .. })
+    }
+
+    /// Returns all directions the player can turn to and survive the next step.
+    /// Never contains the reverse of the last move. Empty for dead players
+    pub fn safe_moves(&self, player_index : PlayerIndex) -> Vec<Direction> {
+        Direction::all().into_iter()
+            .filter(|direction| self.is_move_safe(player_index, *direction))
+            .collect()
+    }
+
+    /// Predicts what the player snake will do on the next step if nothing changes
+    /// before it, the same way the step decides it. None if player is dead or there
+    /// is no such player. Doesn't change the game state
+    pub fn predicted_action(&self, player_index : PlayerIndex) -> Option<ActionStep> {
+        match self.players.get(player_index) {
+            Some(player) if player.alive() => Some(self.predict_next_action(player_index)),
+            _ => None,
+        }
+    }
+
+    /// Predicts the next action that particular player snake will do in next step.
+    fn predict_next_action( &self, player_index : PlayerIndex) -> ActionStep {
+        self.predict_next_action_in(None, player_index)
+    }
+
+    /// Same as `predict_next_action`, with snake collisions looked up in the given
+    /// grid of the current state. Without the grid the cells are looked up one by one
+    fn predict_next_action_in( &self, grid : Option<&Grid>, player_index : PlayerIndex) -> ActionStep {
+        // Dead players alwats hold
+        if !self.players[player_index].alive() {
+            return ActionStep::Hold;
+        }
+        let direction = self.players[player_index].snake.as_ref().unwrap().look_direction();
+        self.predict_action_in(grid, player_index, direction)
+    }
+
+    /// Returns the player whose snake blocks the head of given player moving to the position.
+    /// The tail vacates its cell unless the snake grows, so it doesn't block.
+    /// Own body doesn't block without self collisions.
+    /// The grid shows one snake part per cell. If that part doesn't block, the bodies
+    /// are scanned for another snake under it, so the lowest blocking player index is found.
+    /// Without the grid the cell is looked up with `cell_at`
+    fn snake_blocking_at(&self, grid : Option<&Grid>, pos : Vector2i, player_index : PlayerIndex) -> Option<PlayerIndex> {
+        let blocks = |owner : PlayerIndex, body_part : SnakeBodyPart| {
+            let snake = self.players[owner].snake.as_ref().unwrap();
+            (owner != player_index || self.config.self_collision) &&
+                (body_part != SnakeBodyPart::Tail || snake.will_grow())
+        };
+        let cell = match grid {
+            Some(grid) => grid.at(pos.x as usize, pos.y as usize),
+            None => self.cell_at(pos),
+        };
+        let rec = match cell {
+            GridCell::Snake(rec) => rec,
+            _ => return None,
+        };
+        if blocks(rec.player_index, rec.body_part) {
+            return Some(rec.player_index);
+        }
+        self.players.iter().enumerate()
+            .filter(|(_, player)| player.alive())
+            .find(|(owner, player)| {
+                let snake = player.snake.as_ref().unwrap();
+                let len = snake.length();
+                snake.body().enumerate()
+                    .any(|(part_i, body_part)| *body_part == pos &&
+                        blocks(*owner, if part_i + 1 == len { SnakeBodyPart::Tail } else { SnakeBodyPart::Body }))
+            })
+            .map(|(owner, _)| owner)
+    }
+
+    /// Returns the death of the player snake if it runs into something moving in given
+    /// direction. Other snakes competing for the same cell are not checked.
+    /// #panics
+    /// If player is dead
+    fn predict_collision( &self, grid : Option<&Grid>, player_index : PlayerIndex, direction : Direction) -> Option<ActionStep> {
+        // First estimate the coordinate of potential new head
+        let player_snake = self.players[player_index].snake.as_ref().unwrap();
+        let new_head = self.wrap_pos(player_snake.head() + Vector2i::from_direction(direction));
+        // Check if the new head is inside the field and not in a wall
+        if !self.in_bounds(new_head) || self.is_wall(new_head) || self.is_remains(new_head) {
+            return Some(ActionStep::Die { cause : events::DeathCause::Wall, position : new_head, killer : None });
+        }
+        // Check if the new head enters active hazard
+        if self.hazards.iter().any(|hazard| hazard.position == new_head &&
+            hazard.cell_at_step(self.step_count) == Some(GridCell::Hazard { active : true })) {
+            return Some(ActionStep::Die { cause : events::DeathCause::Hazard, position : new_head, killer : None });
+        }
+        // See if new head position is occupied by body OR head of any snake
+        match self.snake_blocking_at(grid, new_head, player_index)? {
+            owner if owner == player_index =>
+                Some(ActionStep::Die { cause : events::DeathCause::OwnSnake, position : new_head, killer : None }),
+            owner =>
+                Some(ActionStep::Die { cause : events::DeathCause::OtherSnake, position : new_head, killer : Some(owner) }),
+        }
+    }
+
+    /// Predicts the action of the player snake if it moves in given direction.
+    /// Snake collisions are looked up in the given grid of the current state.
+    /// Without the grid the cells are looked up one by one
+    /// #panics
+    /// If player is dead
+    fn predict_action_in( &self, grid : Option<&Grid>, player_index : PlayerIndex, direction : Direction) -> ActionStep {
+        if let Some(die) = self.predict_collision(grid, player_index, direction) {
+            return die;
+        }
+        let player_snake = self.players[player_index].snake.as_ref().unwrap();
+        let new_head = self.wrap_pos(player_snake.head() + Vector2i::from_direction(direction));
+
+        // Find the longest of other snakes competing for the same head position
+        let mut rival : Option<(PlayerIndex, usize)> = None;
+        for (other_player_index, other_player) in self.players.iter().enumerate() {
+            if other_player_index == player_index || !other_player.alive() {
+                continue;
+            }
+            // Get other snake
+            let other_snake = other_player.snake.as_ref().unwrap();
+            // Estimate this snake expected head position
+            let other_new_head = self.wrap_pos(
+                other_snake.head() + Vector2i::from_direction(other_snake.look_direction()));
+            if other_new_head == new_head && rival.is_none_or(|(_, length)| other_snake.length() > length) {
+                rival = Some((other_player_index, other_snake.length()));
+            }
+        }
+        let (rival_index, rival_length) = match rival {
+            Some(rival) => rival,
+            // In all other cases snake can move
+            None => return ActionStep::Move,
+        };
+        match self.config.contest_rule {
+            ContestRule::Hold => ActionStep::Hold,
+            ContestRule::LongerWins { tie_dies } => {
+                let length = player_snake.length();
+                let die = ActionStep::Die { cause : events::DeathCause::OtherSnake, position : new_head, killer : Some(rival_index) };
+                if length > rival_length {
+                    ActionStep::Move
+                }
+                else if length < rival_length || tie_dies {
+                    die
+                }
+                else {
+                    ActionStep::Hold
+                }
+            },
+        }
+    }
+
+    /// Returns true if the position is inside the field
+    pub fn in_bounds(&self, pos : Vector2i) -> bool {
+        let size = self.config.field_size;
+        pos.x >= 0 && pos.y >= 0 && pos.x < size.x && pos.y < size.y
+    }
+
+    /// Moves the position outside of the field to the opposite edge if wrapping is enabled
+    pub(crate) fn wrap_pos(&self, pos : Vector2i) -> Vector2i {
+        if !self.config.wrap_around {
+            return pos;
+        }
+        let size = self.config.field_size;
+        Vector2i::new(pos.x.rem_euclid(size.x), pos.y.rem_euclid(size.y))
+    }
+
+    /// Returns true if the game can spawn more pizzas
+    fn has_pizza_supply(&self) -> bool {
+        self.config.pizza_supply.is_none_or(|supply| self.pizzas_spawned < supply)
+    }
+
+    /// Returns true if all pizzas are eaten and no more will spawn
+    fn is_board_cleared(&self) -> bool {
+        self.pizzas.is_empty() && !self.has_pizza_supply()
+    }
+
+    /// Returns true if the end condition is met
+    fn is_end_condition_met(&self, condition : GameEndCondition) -> bool {
+        match condition {
+            GameEndCondition::LastStanding => self.players.len() > 1 && self.alive_count() <= 1,
+            GameEndCondition::ScoreTarget(target) => self.players.iter().any(|player| player.score >= target),
+            GameEndCondition::StepLimit(limit) => self.step_count >= limit,
+            GameEndCondition::BoardCleared => self.is_board_cleared(),
+        }
+    }
+
+    /// Returns the reason of the game over or None if the game goes on.
+    /// All players being dead is checked first, then the configured end conditions
+    /// in their order, then the full board and the stall
+    pub fn game_over_reason(&self) -> Option<events::GameOverReason> {
+        if self.alive_count() == 0 {
+            return Some(events::GameOverReason::AllDead);
+        }
+        if let Some(condition) = self.config.end_conditions.iter().find(|condition| self.is_end_condition_met(**condition)) {
+            return Some(match condition {
+                GameEndCondition::LastStanding => events::GameOverReason::LastStanding,
+                GameEndCondition::ScoreTarget(_) => events::GameOverReason::ScoreTarget,
+                GameEndCondition::StepLimit(_) => events::GameOverReason::StepLimit,
+                GameEndCondition::BoardCleared => events::GameOverReason::BoardCleared,
+            });
+        }
+        if self.pizzas.is_empty() && self.num_empty_cells() <= 0 {
+            return Some(events::GameOverReason::BoardFull);
+        }
+        match self.config.stall_threshold {
+            Some(threshold) if self.stalled_steps >= threshold => Some(events::GameOverReason::Stalled),
+            _ => None,
+        }
+    }
+
+    /// Small utils function that returns summary for all players
+    fn get_players_summary(&self) -> Vec<events::PlayerSummary> {
+        let mut summary = Vec::with_capacity(self.players.len());
+        self.players_summary_into(&mut summary);
+        summary
+    }
+    /// Writes summary for all players into given buffer, replacing its contents.
+    /// Reuses the buffer memory, so polling every step doesn't allocate
+    pub fn players_summary_into(&self, buf : &mut Vec<events::PlayerSummary>) {
+        buf.clear();
+        buf.extend(self.players.iter().map(|player| player.summary()));
+    }
+    /// Sends the game over event to all channels. Returns the sent event
+    fn send_game_over_event(&self, reason : events::GameOverReason) -> events::GameOver {
+        // Create game over event
+        let game_over_event = events::GameOver{
+            reason,
+            players_summary : self.get_players_summary(),
+            duration : self.start_time.map(|t| t.elapsed()).unwrap_or_default(),
+            total_steps : self.step_count,
+            ranking : self.ranking(),
+        };
+        self.send_event(events::GlobalEvent::GameOver(game_over_event.clone()));
+        game_over_event
+    }
+    /// Sends update event to all channels
+    fn send_update_event(&self) {
+        // Create update event
+        let update_event = events::Update{
+            grid : self.grid.clone(),
+            players_summary : self.get_players_summary(),
+            step : self.step_count,
+            elapsed : self.start_time.map(|t| t.elapsed()).unwrap_or_default(),
+            wrapped : self.wrapped.clone(),
+        };
+        self.send_event(events::GlobalEvent::Update(update_event));
+    }
+    /// Sends the event to all channels
+    fn send_event(&self, event : events::GlobalEvent) {
+        for channel in &self.global_event_channels {
+            match channel.send(event.clone()) {
+                Ok(_) => {},
+                Err(_) => {// Don't care about dead channels
+                }
+            };
+        }
+    }
+}
+
+/// Builder of the game with hand placed walls, pizzas and snakes.
+/// Meant for scripted scenarios, e.g. tests and tutorials
+pub struct GameBuilder
+{
+    config : GameConfig,
+    walls : Vec<Vector2i>,
+    pizzas : Vec<Pizza>,
+    snakes : Vec<Snake>,
+}
+
+impl GameBuilder {
+    /// Creates builder with default config
+    pub fn new() -> GameBuilder {
+        GameBuilder::with_config(GameConfig::default())
+    }
+    /// Creates builder with given config
+    pub fn with_config(config : GameConfig) -> GameBuilder {
+        GameBuilder { config, walls : Vec::new(), pizzas : Vec::new(), snakes : Vec::new() }
+    }
+    /// Sets the field size
+    pub fn field(mut self, width : i32, height : i32) -> GameBuilder {
+        self.config.field_size = Vector2i::new(width, height);
+        self
+    }
+    /// Sets the random seed
+    pub fn seed(mut self, seed : u64) -> GameBuilder {
+        self.config.seed = Some(seed);
+        self
+    }
+    /// Adds wall
+    pub fn wall(mut self, pos : Vector2i) -> GameBuilder {
+        self.walls.push(pos);
+        self
+    }
+    /// Adds normal pizza
+    pub fn pizza(self, pos : Vector2i) -> GameBuilder {
+        self.pizza_of(pos, PizzaKind::Normal)
+    }
+    /// Adds pizza of given kind
+    pub fn pizza_of(mut self, pos : Vector2i, kind : PizzaKind) -> GameBuilder {
+        self.pizzas.push(Pizza::of_kind(pos, kind));
+        self
+    }
+    /// Adds player without control. The straight snake has the head at given
+    /// position and looks in given direction. Players are indexed in the order of adding
+    pub fn snake(mut self, head : Vector2i, direction : Direction, length : u32) -> GameBuilder {
+        self.snakes.push(Snake::new(head, direction, length));
+        self
+    }
+    /// Builds the game. Spawn points and distances are not applied to the added snakes
+    /// #panics
+    /// If the field size is not positive or any wall is outside of the field
+    pub fn build(self) -> Game {
+        let mut game = Game::with_config(self.config);
+        for wall in self.walls {
+            game.add_wall(wall);
+        }
+        game.pizzas = self.pizzas;
+        for (player_index, snake) in self.snakes.into_iter().enumerate() {
+            let mut player = Player::new();
+            player.snake = Some(snake);
+            player.color = PlayerColor::for_index(player_index);
+            game.players.push(player);
+        }
+        game.grid = game.generate_grid();
+        game
+    }
+}
+
+impl Default for GameBuilder {
+    fn default() -> GameBuilder {
+        GameBuilder::new()
+    }
+}
+
+/// Direction change recorded for replays
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScriptedInput
+{
+    /// The step before which the direction is applied
+    pub step : u64,
+    pub player_index : PlayerIndex,
+    pub direction : Direction,
+}
+
+/// Plays the game without the game loop. Registers given number of players
+/// and applies the inputs before their steps. Returns None if the game
+/// is not over after max_steps
+fn run_replay(config : &GameConfig, seed : u64, num_players : usize, inputs : &[ScriptedInput],
+    max_steps : u64) -> Option<events::GameOver> {
+    let mut game = Game::with_config(GameConfig { seed : Some(seed), ..config.clone() });
+    for _ in 0..num_players {
+        game.try_register_player(None)?;
+    }
+    loop {
+        if let Some(reason) = game.game_over_reason() {
+            return Some(game.send_game_over_event(reason));
+        }
+        if game.step_count >= max_steps {
+            return None;
+        }
+        for input in inputs.iter().filter(|input| input.step == game.step_count) {
+            if let Some(snake) = game.players.get_mut(input.player_index).and_then(|p| p.snake.as_mut()) {
+                snake.try_set_look_direction(input.direction);
+            }
+        }
+        game.step();
+    }
+}
+
+/// Replays the game from the seed and recorded inputs and checks that
+/// it ends the same way. Wall-clock duration is not compared
+pub fn verify_replay(config : &GameConfig, seed : u64, inputs : &[ScriptedInput], expected : &events::GameOver) -> bool {
+    match run_replay(config, seed, expected.players_summary.len(), inputs, expected.total_steps) {
+        Some(game_over) => {
+            game_over.reason == expected.reason &&
+            game_over.players_summary == expected.players_summary &&
+            game_over.total_steps == expected.total_steps &&
+            game_over.ranking == expected.ranking
+        }
+        None => false,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{PizzaSpawnDist, SpawnPolicy, ThumbnailConfig};
+    use crate::spawner::FirstFreeSpawner;
+
+    // Test each new player gets new index
+    #[test]
+    fn test_register_player() {
+        let mut game = Game::new( Vector2i::new(10, 10));
+        let player1 = game.register_player(None);
+        let player2 = game.register_player(None);
+        let player3 = game.register_player(None);
+        assert_eq!(player1, 0);
+        assert_eq!(player2, 1);
+        assert_eq!(player3, 2);
+        // Test that snake is created
+        assert!(game.players[player1].snake.is_some());
+        assert!(game.players[player2].snake.is_some());
+        assert!(game.players[player3].snake.is_some());
+    }
+
+    // Test calc_spawn_pos
+    #[test]
+    fn test_calc_spawn_pos() {
+        let field_size = Vector2i::new(10, 10);
+        let (pos, dir) = Game::calc_spawn_pos(0, 3, field_size);
+        assert_eq!(pos, Vector2i::new(2, 5));
+        assert_eq!(dir, Direction::PlusX);
+
+        let (pos, dir) = Game::calc_spawn_pos(1, 3, field_size);
+        assert_eq!(pos, Vector2i::new(5, 2));
+        assert_eq!(dir, Direction::PlusY);
+
+        let (pos, dir) = Game::calc_spawn_pos(2, 3, field_size);
+        assert_eq!(pos, Vector2i::new(8, 5));
+        assert_eq!(dir, Direction::MinusX);
+
+        let (pos, dir) = Game::calc_spawn_pos(3, 3, field_size);
+        assert_eq!(pos, Vector2i::new(5, 8));
+        assert_eq!(dir, Direction::MinusY);
+    }
+    // Calling test_cal_spawn_pos with index > 4 should panic
+    #[test]
+    #[should_panic]
+    fn test_calc_spawn_pos_panic() {
+        Game::calc_spawn_pos(4, 3, Vector2i::new(10, 10));
+    }
+
+    // Zero area field should be rejected
+    #[test]
+    #[should_panic(expected = "Field size must be positive")]
+    fn test_zero_field_panic() {
+        Game::new(Vector2i::new(0, 0));
+    }
+
+    // Negative field dimension should be rejected
+    #[test]
+    #[should_panic(expected = "Field size must be positive")]
+    fn test_negative_field_panic() {
+        Game::new(Vector2i::new(10, -1));
+    }
+
+    // Test num_empty_cells
+    #[test]
+    fn test_num_empty_cells() {
+        let mut game = Game::new( Vector2i::new(10, 10));
+        assert_eq!(game.num_empty_cells(), 100);
+        game.register_player(None);
+        assert_eq!(game.num_empty_cells(), 100 - game.config.initial_length as i32);
+        game.register_player(None);
+        assert_eq!(game.num_empty_cells(), 100 - 2 * game.config.initial_length as i32);
+        // Register dead player
+        game.register_player(None);
+        game.players[2].snake = None;
+        assert_eq!(game.num_empty_cells(), 100 - 2 * game.config.initial_length as i32);
+        
+        // Add some food
+        game.pizzas.push(Pizza::normal(Vector2i::new(0, 0)));
+        game.pizzas.push(Pizza::normal(Vector2i::new(0, 1)));
+        
+        assert_eq!(game.num_empty_cells(), 100 - 2 * game.config.initial_length as i32 - 2);
+    }
+
+    // Test generate gird
+    #[test]
+    fn test_generate_grid() {
+        // Spawned snakes overlap on such a small field
+        let mut game = Game::with_config(GameConfig {
+            field_size : Vector2i::new(3, 3),
+            spawn_policy : SpawnPolicy { snake_distance : 0, pizza_distance : 0 },
+            ..GameConfig::default()
+        });
+        let player1 = game.register_player(None);
+        // Manually set the snake points to make it easier to test
+        game.players[player1].snake.as_mut().unwrap().set_body(vec![
+            Vector2i::new(0, 0),
+            Vector2i::new(0, 1),
+            Vector2i::new(0, 2),
+            Vector2i::new(1, 2),
+        ]);
+        // Create another dead player, which should have 0 effect on grid
+        {
+            let player2 = game.register_player(None);
+            game.players[player2].snake = None;
+        }
+
+        // Add one pizza
+        game.pizzas.push(Pizza::normal(Vector2i::new(2, 2)));
+        // Generate grid
+        let grid = game.generate_grid();
+        // Check grid
+        assert_eq!(grid[[0, 0]], GridCell::Snake(SnakeRec{body_part : SnakeBodyPart::Head, player_index : player1}));
+        assert_eq!(grid[[0, 1]], GridCell::Snake(SnakeRec{body_part : SnakeBodyPart::Neck, player_index : player1}));
+        assert_eq!(grid[[0, 2]], GridCell::Snake(SnakeRec{body_part : SnakeBodyPart::Body, player_index : player1}));
+        assert_eq!(grid[[1, 2]], GridCell::Snake(SnakeRec{body_part : SnakeBodyPart::Tail, player_index : player1}));
+        assert_eq!(grid[[1, 0]], GridCell::Empty);
+        assert_eq!(grid[[1, 1]], GridCell::Empty);
+        assert_eq!(grid[[2, 0]], GridCell::Empty);
+        assert_eq!(grid[[2, 1]], GridCell::Empty);
+        assert_eq!(grid[[2, 2]], GridCell::Pizza(PizzaRec{ kind : PizzaKind::Normal }));
+    }
+
+    // Test game_loop shutdown
+    #[test]
+    fn test_game_loop_shutdown() {
+        // Create mpsc channel
+        let (tx, rx) = mpsc::channel();
+
+        let mut game = Box::new(Game::new( Vector2i::new(10, 10)));
+
+        let handle = std::thread::spawn(move || {
+            // Start game loop
+            game.game_loop(rx);
+        });
+        // Send shutdown immediately;
+        tx.send(()).unwrap();
+
+        // Wait 20 times for 50 ms. Every time checking that thread finished
+        for _ in 0..20 {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            if handle.is_finished() {
+                break;
+            }
+        }
+
+        // Check the thread is done
+        assert!(handle.is_finished());
+
+        // Wait for thread to finish
+        handle.join().unwrap();
+    }
+
+    // Test predict_next_action
+    #[test]
+    fn test_predict_next_action() {
+        // Create small 4x4 game. Players are registered next to hand placed snakes
+        let mut game = Game::with_config(GameConfig {
+            field_size : Vector2i::new(4, 4),
+            spawn_policy : SpawnPolicy { snake_distance : 0, pizza_distance : 0 },
+            ..GameConfig::default()
+        });
+        let player_index0 = game.register_player(None);
+        
+        // Single snake going out of bounds dies
+        {
+            let snake0 = game.players[player_index0].snake.as_mut().unwrap();
+            snake0.set_body(vec![
+                Vector2i::new(0, 3),
+                Vector2i::new(0, 2),
+            ]);
+            assert!( snake0.try_set_look_direction( Direction::PlusY ));
+            assert_eq!(game.predict_next_action(player_index0), ActionStep::Die {
+                cause : events::DeathCause::Wall, position : Vector2i::new(0, 4), killer : None });
+        }
+        // Single snake going to current tail pos: moves. This is because during the move
+        // this cell will be freed
+        {
+            let snake0 = game.players[player_index0].snake.as_mut().unwrap();
+            snake0.set_body(vec![
+                Vector2i::new(1, 2),
+                Vector2i::new(2, 2),
+                Vector2i::new(2, 1), 
+                Vector2i::new(1, 1),   
+            ]);     
+            assert!( snake0.try_set_look_direction( Direction::MinusY ));
+            assert_eq!(game.predict_next_action(player_index0), ActionStep::Move);
+        }
+        // The snake that attempts to move to it's own body pos - dies
+        {
+            let snake0 = game.players[player_index0].snake.as_mut().unwrap();
+            snake0.set_body(vec![
+                Vector2i::new(1, 2),
+                Vector2i::new(2, 2),
+                Vector2i::new(2, 1), 
+                Vector2i::new(1, 1),   
+                Vector2i::new(0, 1), 
+            ]);     
+            assert!( snake0.try_set_look_direction( Direction::MinusY ));
+            assert_eq!(game.predict_next_action(player_index0), ActionStep::Die {
+                cause : events::DeathCause::OwnSnake, position : Vector2i::new(1, 1), killer : None });
+        }
+        // Add one more small snake for further tests
+        let player_index1 = game.register_player(None);
+        {
+            let snake1 = game.players[player_index1].snake.as_mut().unwrap();
+                snake1.set_body(vec![
+                    Vector2i::new(3, 2),
+                    Vector2i::new(3, 1),
+                    Vector2i::new(3, 0),
+                ]);
+            assert!( snake1.try_set_look_direction( Direction::PlusY ));
+        }
+        // Add dead player. Mainly to make sure it doesn't crash. It should cause no real affects.
+        {
+            let player_index2 = game.register_player(None);
+            game.players[player_index2].snake = None;
+            // Dead player always hold
+            assert_eq!(game.predict_next_action(player_index2), ActionStep::Hold);
+        }
+
+        // When snake tries to move to the head position of other snake - it dies
+        {
+            let snake0 = game.players[player_index0].snake.as_mut().unwrap();
+            snake0.set_body(vec![
+                Vector2i::new(2, 2),
+                Vector2i::new(1, 2),
+            ]);     
+            assert!( snake0.try_set_look_direction( Direction::PlusX ));
+            assert_eq!(game.predict_next_action(player_index0), ActionStep::Die {
+                cause : events::DeathCause::OtherSnake, position : Vector2i::new(3, 2), killer : Some(player_index1) });
+        }
+        // When snake tries to move to the body position of other snake - it dies
+        {
+            let snake0 = game.players[player_index0].snake.as_mut().unwrap();
+            snake0.set_body(vec![
+                Vector2i::new(2, 3),
+                Vector2i::new(1, 3),
+            ]);     
+            assert!( snake0.try_set_look_direction( Direction::PlusX ));
+            assert_eq!(game.predict_next_action(player_index0), ActionStep::Hold);
+        }
+        // When snake tries to move ot the tail position of other snake - it moves. This is because during the move
+        // this position will be freed
+        {
+            let snake0 = game.players[player_index0].snake.as_mut().unwrap();
+            snake0.set_body(vec![
+                Vector2i::new(2, 0),
+                Vector2i::new(1, 0),
             ]);     
             assert!( snake0.try_set_look_direction( Direction::PlusX ));
-            assert_eq!(game.predict_next_action(player_index0), ActionStep::Die);
+            assert_eq!(game.predict_next_action(player_index0), ActionStep::Move);
+        }
+        // When snake competes with other snake for same position - it holds
+        {
+            let snake0 = game.players[player_index0].snake.as_mut().unwrap();
+            snake0.set_body(vec![
+                Vector2i::new(2, 0),
+                Vector2i::new(1, 0),
+            ]);     
+            assert!( snake0.try_set_look_direction( Direction::PlusX ));
+            assert_eq!(game.predict_next_action(player_index0), ActionStep::Move);
+        }
+
+    }
+
+    // Test move_player
+    #[test]
+    fn test_move_player() {
+        // Create small 4x4 game
+        let mut game = Game::new( Vector2i::new(4, 4));
+        let player_index0 = game.register_player(None);
+        {
+            let snake = game.players[player_index0].snake.as_mut().unwrap();
+            // Setup snake such that it can move forward 2 times
+            snake.set_body(vec![
+                Vector2i::new(0, 1), 
+                Vector2i::new(0, 0),   
+            ]);
+            assert!( snake.try_set_look_direction( Direction::PlusY ));
+        }
+ 
+        // Also add one pizza
+        game.pizzas.push(Pizza::normal(Vector2i::new(0, 3)));
+        // First move
+        game.move_player(player_index0);
+        // Doesn't eat pizza. Doesn't increase score
+        assert_eq!(game.pizzas.len(), 1);
+        assert_eq!(game.players[player_index0].score, 0);
+        // Second move
+        // Eats pizza and increase score
+        game.move_player(player_index0);
+        assert_eq!(game.pizzas.len(), 0);
+        assert_eq!(game.players[player_index0].score, 1);
+        // Also check final snake position
+        assert_eq!(game.players[player_index0].snake.as_ref().unwrap().body().copied().collect::<Vec<_>>(), vec![
+            Vector2i::new(0, 3), 
+            Vector2i::new(0, 2)
+        ]);
+
+    }
+
+    // Test sending game over event
+    #[test]
+    fn test_game_over_event() {
+        // Create small 4x4 game
+        let mut game = Game::new( Vector2i::new(4, 4));
+        let player_index0 = game.register_player(None);
+        game.players[player_index0].score = 10;
+        
+        // Create another dead player
+        let player_index1 = game.register_player(None);
+        game.players[player_index1].kill();
+ 
+        // Create a channel for global event
+        let channel = mpsc::channel::<events::GlobalEvent>();
+        game.register_global_event_channel(channel.0);
+        // Trigger event
+        game.send_game_over_event(events::GameOverReason::AllDead);
+        // Receive event
+        let event = channel.1.recv().unwrap();
+        // Make sure it's a GameOver event
+        match event {
+            events::GlobalEvent::GameOver(data) => {
+                // Assert there are 2 players
+                assert_eq!(data.players_summary.len(), 2);
+                // First is alive and has score
+                assert!(data.players_summary[0].alive);
+                assert_eq!(data.players_summary[0].score, 10);
+                // Second is dead
+                assert!(!data.players_summary[1].alive);
+                assert_eq!(data.players_summary[1].score, 0);
+                assert_eq!(data.reason, events::GameOverReason::AllDead);
+                // Game loop was never started
+                assert_eq!(data.duration, time::Duration::ZERO);
+                assert_eq!(data.total_steps, 0);
+                
+            }
+            _ => panic!("Unexpected event")
+        };
+    }
+
+    // Test the update event
+    #[test]
+    fn test_update_event() {
+        // Create small 4x4 game
+        let mut game = Game::new( Vector2i::new(4, 4));
+        let _player_index0 = game.register_player(None);
+
+        // Create a channel for global event
+        let channel = mpsc::channel::<events::GlobalEvent>();
+        game.register_global_event_channel(channel.0);
+        // Make sure grid is up to date
+        game.grid = game.generate_grid();
+        // Trigger event
+        game.send_update_event();
+        // Receive event
+        let event = channel.1.recv().unwrap();
+        // Make sure it's a GameOver event
+        match event {
+            events::GlobalEvent::Update(data) => {
+                // Assert there is 1 player
+                assert_eq!(data.players_summary.len(), 1);
+                // Assert there is grid of correct size
+                assert_eq!(data.grid.dim(), (4, 4));
+                // Game loop was never started
+                assert_eq!(data.step, 0);
+                assert_eq!(data.elapsed, time::Duration::ZERO);
+            }
+            _ => panic!("Unexpected event")
+        };
+    }
+
+    // Test the player died event reports position and killer
+    #[test]
+    fn test_player_died_event() {
+        // Create small 5x5 game
+        let mut game = Game::new( Vector2i::new(5, 5));
+        let player_index0 = game.register_player(None);
+        let player_index1 = game.register_player(None);
+        // First snake runs into the body of the second one
+        {
+            let snake0 = game.players[player_index0].snake.as_mut().unwrap();
+            snake0.set_body(vec![
+                Vector2i::new(1, 2),
+                Vector2i::new(0, 2),
+            ]);
+            assert!( snake0.try_set_look_direction( Direction::PlusX ));
+        }
+        {
+            let snake1 = game.players[player_index1].snake.as_mut().unwrap();
+            snake1.set_body(vec![
+                Vector2i::new(2, 1),
+                Vector2i::new(2, 2),
+                Vector2i::new(2, 3),
+            ]);
+            assert!( snake1.try_set_look_direction( Direction::MinusY ));
+        }
+        assert_eq!(game.predict_next_action(player_index0), ActionStep::Die {
+            cause : events::DeathCause::OtherSnake, position : Vector2i::new(2, 2), killer : Some(player_index1) });
+
+        // Create a channel for global event
+        let channel = mpsc::channel::<events::GlobalEvent>();
+        game.register_global_event_channel(channel.0);
+        // Do the step
+        game.step();
+        assert!(!game.players[player_index0].alive());
+        assert!(game.players[player_index1].alive());
+        // Receive event
+        let event = channel.1.recv().unwrap();
+        assert_eq!(event, events::GlobalEvent::PlayerDied(events::PlayerDied {
+            player_index : player_index0,
+            cause : events::DeathCause::OtherSnake,
+            position : Vector2i::new(2, 2),
+            killer : Some(player_index1),
+        }));
+    }
+
+    // Test that inputs queued while player is dead are not applied after respawn
+    #[test]
+    fn test_respawn_discards_inputs() {
+        let mut game = Game::new( Vector2i::new(10, 10));
+        let (control_tx, control_rx) = mpsc::channel();
+        let player_index = game.register_player(Some(Box::new(control_rx)));
+        let spawn_dir = game.players[player_index].snake.as_ref().unwrap().look_direction();
+        game.players[player_index].kill();
+        // Queue inputs while dead
+        control_tx.send(Direction::PlusY).unwrap();
+        control_tx.send(Direction::MinusY).unwrap();
+        // Respawn and read inputs
+        game.respawn_player(player_index);
+        game.players[player_index].read_inputs(0, None);
+        // Snake keeps its spawn direction
+        assert!(game.players[player_index].alive());
+        assert_eq!(game.players[player_index].snake.as_ref().unwrap().look_direction(), spawn_dir);
+        // Inputs sent after respawn are applied
+        control_tx.send(Direction::PlusY).unwrap();
+        game.players[player_index].read_inputs(0, None);
+        assert_eq!(game.players[player_index].snake.as_ref().unwrap().look_direction(), Direction::PlusY);
+    }
+
+    // Test that StepOnce advances paused game exactly one step
+    #[test]
+    fn test_step_once() {
+        let mut game = Game::new( Vector2i::new(10, 10));
+        game.register_player(None);
+        let (command_tx, command_rx) = mpsc::channel();
+        game.register_command_channel(command_rx);
+        let (event_tx, event_rx) = mpsc::channel();
+        game.register_global_event_channel(event_tx);
+        let (shutdown_tx, shutdown_rx) = mpsc::channel();
+
+        // StepOnce is ignored while not paused
+        command_tx.send(GameCommand::StepOnce).unwrap();
+        game.read_commands();
+        assert_eq!(game.step_count(), 0);
+
+        // Pause and request single step before the loop starts
+        command_tx.send(GameCommand::Pause).unwrap();
+        command_tx.send(GameCommand::StepOnce).unwrap();
+        let handle = std::thread::spawn(move || {
+            game.game_loop(shutdown_rx);
+            game
+        });
+        // Wait for a few update intervals
+        std::thread::sleep(UPDATE_INTERVAL * 3);
+        shutdown_tx.send(()).unwrap();
+        let game = handle.join().unwrap();
+
+        // Exactly one step was done and one update was sent
+        assert_eq!(game.step_count(), 1);
+        let updates = event_rx.try_iter()
+            .filter(|e| matches!(e, events::GlobalEvent::Update(_)))
+            .count();
+        assert_eq!(updates, 1);
+    }
+
+    // Test that trails record vacated cells and age them
+    #[test]
+    fn test_trails() {
+        let mut game = Game::with_config(GameConfig {
+            trail_lifetime : Some(2),
+            ..GameConfig::default()
+        });
+        let player_index = game.register_player(None);
+        {
+            let snake = game.players[player_index].snake.as_mut().unwrap();
+            snake.set_body(vec![
+                Vector2i::new(1, 1),
+                Vector2i::new(0, 1),
+            ]);
+            assert!( snake.try_set_look_direction( Direction::PlusX ));
+        }
+        // Keep the pizza out of the way
+        game.pizzas.push(Pizza::normal(Vector2i::new(9, 9)));
+
+        // First step vacates the old tail
+        game.step();
+        assert_eq!(game.trails(), &[Trail { position : Vector2i::new(0, 1), age : 0 }]);
+        // Second step ages it and adds a new one
+        game.step();
+        assert_eq!(game.trails(), &[
+            Trail { position : Vector2i::new(0, 1), age : 1 },
+            Trail { position : Vector2i::new(1, 1), age : 0 },
+        ]);
+        // Third step forgets the expired one
+        game.step();
+        assert_eq!(game.trails(), &[
+            Trail { position : Vector2i::new(1, 1), age : 1 },
+            Trail { position : Vector2i::new(2, 1), age : 0 },
+        ]);
+    }
+
+    // Test that trails are not tracked by default
+    #[test]
+    fn test_trails_disabled() {
+        let mut game = Game::new( Vector2i::new(10, 10));
+        game.register_player(None);
+        game.pizzas.push(Pizza::normal(Vector2i::new(9, 9)));
+        game.step();
+        assert!(game.trails().is_empty());
+    }
+
+    // Test that grid width and height match the field size
+    #[test]
+    fn test_grid_orientation() {
+        let mut game = Game::new( Vector2i::new(5, 3));
+        let player_index = game.register_player(None);
+        game.players[player_index].snake.as_mut().unwrap().set_body(vec![
+            Vector2i::new(4, 0),
+            Vector2i::new(4, 1),
+        ]);
+        let grid = game.generate_grid();
+        assert_eq!(grid.width(), 5);
+        assert_eq!(grid.height(), 3);
+        assert_eq!(grid.at(4, 0), GridCell::Snake(SnakeRec{body_part : SnakeBodyPart::Head, player_index}));
+        assert_eq!(grid.at(4, 1), GridCell::Snake(SnakeRec{body_part : SnakeBodyPart::Tail, player_index}));
+        assert_eq!(grid.at(0, 2), GridCell::Empty);
+    }
+
+    // Test that buffered inputs are applied one per step
+    #[test]
+    fn test_input_buffer() {
+        let mut game = Game::with_config(GameConfig {
+            input_buffer : 2,
+            ..GameConfig::default()
+        });
+        let (control_tx, control_rx) = mpsc::channel();
+        let player_index = game.register_player(Some(Box::new(control_rx)));
+        {
+            let snake = game.players[player_index].snake.as_mut().unwrap();
+            snake.set_body(vec![
+                Vector2i::new(5, 5),
+                Vector2i::new(6, 5),
+            ]);
+            assert!( snake.try_set_look_direction( Direction::MinusX ));
+        }
+        // Keep the pizza out of the way
+        game.pizzas.push(Pizza::normal(Vector2i::new(0, 0)));
+
+        // Up then right within single step. Right would be a reverse if applied immediately
+        control_tx.send(Direction::MinusY).unwrap();
+        control_tx.send(Direction::PlusX).unwrap();
+        game.players[player_index].read_inputs(game.config.input_buffer, game.config.max_inputs_per_tick);
+
+        // Turns up on the first step
+        game.step();
+        let snake = game.players[player_index].snake.as_ref().unwrap();
+        assert_eq!(snake.look_direction(), Direction::MinusY);
+        assert_eq!(snake.head(), Vector2i::new(5, 4));
+        // Turns right on the second step
+        game.step();
+        let snake = game.players[player_index].snake.as_ref().unwrap();
+        assert_eq!(snake.look_direction(), Direction::PlusX);
+        assert_eq!(snake.head(), Vector2i::new(6, 4));
+    }
+
+    // Test that the last valid input read before the step wins
+    #[test]
+    fn test_last_valid_input_wins() {
+        let mut game = Game::new(Vector2i::new(10, 10));
+        let (control_tx, control_rx) = mpsc::channel();
+        let player_index = game.register_player(Some(Box::new(control_rx)));
+        let head = game.snake_head(player_index).unwrap();
+        assert_eq!(game.players[player_index].snake.as_ref().unwrap().look_direction(), Direction::PlusX);
+
+        // Two valid inputs, the last one wins
+        control_tx.send(Direction::PlusY).unwrap();
+        control_tx.send(Direction::MinusY).unwrap();
+        game.players[player_index].read_inputs(game.config.input_buffer, game.config.max_inputs_per_tick);
+        game.step();
+        assert_eq!(game.snake_head(player_index), Some(head + Vector2i::new(0, -1)));
+
+        // Valid input followed by a reverse keeps the valid one
+        control_tx.send(Direction::PlusX).unwrap();
+        control_tx.send(Direction::PlusY).unwrap();
+        game.players[player_index].read_inputs(game.config.input_buffer, game.config.max_inputs_per_tick);
+        game.step();
+        assert_eq!(game.snake_head(player_index), Some(head + Vector2i::new(1, -1)));
+    }
+
+    // Test that buffered reverse of the queued direction is skipped instead of wasting a step
+    #[test]
+    fn test_input_buffer_skips_reverse() {
+        let mut game = Game::with_config(GameConfig {
+            input_buffer : 2,
+            ..GameConfig::default()
+        });
+        let (control_tx, control_rx) = mpsc::channel();
+        let player_index = game.register_player(Some(Box::new(control_rx)));
+        control_tx.send(Direction::PlusY).unwrap();
+        control_tx.send(Direction::MinusY).unwrap();
+        control_tx.send(Direction::PlusX).unwrap();
+        game.players[player_index].read_inputs(game.config.input_buffer, game.config.max_inputs_per_tick);
+        assert_eq!(game.players[player_index].input_queue, vec![Direction::PlusY, Direction::PlusX]);
+    }
+
+    // Test alive players queries
+    #[test]
+    fn test_alive_players() {
+        let mut game = Game::new( Vector2i::new(10, 10));
+        assert_eq!(game.alive_count(), 0);
+        assert!(game.alive_player_indices().is_empty());
+        for _ in 0..4 {
+            game.register_player(None);
+        }
+        assert_eq!(game.alive_count(), 4);
+        game.players[0].kill();
+        game.players[2].kill();
+        assert_eq!(game.alive_player_indices(), vec![1, 3]);
+        assert_eq!(game.alive_count(), 2);
+    }
+
+    // Test that game over reports the number of steps
+    #[test]
+    fn test_game_over_total_steps() {
+        let mut game = Game::new( Vector2i::new(10, 10));
+        game.register_player(None);
+        let (event_tx, event_rx) = mpsc::channel();
+        game.register_global_event_channel(event_tx);
+        for _ in 0..3 {
+            game.step();
+        }
+        game.send_game_over_event(events::GameOverReason::AllDead);
+        let game_over = event_rx.try_iter().find_map(|event| match event {
+            events::GlobalEvent::GameOver(data) => Some(data),
+            _ => None,
+        }).unwrap();
+        assert_eq!(game_over.total_steps, 3);
+    }
+
+    // Test that every free cell can get a pizza
+    #[test]
+    fn test_calc_spawn_pos_for_pizza() {
+        let mut game = Game::with_config(GameConfig {
+            field_size : Vector2i::new(3, 3),
+            seed : Some(42),
+            ..GameConfig::default()
+        });
+        let player_index = game.register_player(None);
+        game.players[player_index].snake.as_mut().unwrap().set_body(vec![
+            Vector2i::new(0, 0),
+            Vector2i::new(1, 0),
+        ]);
+        let grid = game.generate_grid();
+        let free_cells = game.num_empty_cells();
+        assert_eq!(free_cells, 7);
+
+        let mut spawner = RandomSpawner::new(PizzaSpawnDist::Uniform, Some(42));
+        let mut hits = ndarray::Array2::<u32>::zeros((3, 3));
+        for _ in 0..1000 {
+            let pos = spawner.spawn_pos(&grid, free_cells).unwrap();
+            assert_eq!(grid[[pos.x as usize, pos.y as usize]], GridCell::Empty);
+            hits[[pos.x as usize, pos.y as usize]] += 1;
+        }
+        // Every free cell is reachable
+        for ((x, y), cell) in grid.indexed_iter() {
+            if *cell == GridCell::Empty {
+                assert!(hits[[x, y]] > 0, "Cell ({}, {}) never got a pizza", x, y);
+            }
+        }
+        // No free cells - no pizza
+        assert_eq!(spawner.spawn_pos(&grid, 0), None);
+    }
+
+    // Test that same seed gives same pizzas
+    #[test]
+    fn test_seed_reproducible() {
+        let make_pizzas = || {
+            let mut game = Game::with_config(GameConfig { seed : Some(7), ..GameConfig::default() });
+            game.register_player(None);
+            game.step();
+            game.pizzas.clone()
+        };
+        assert_eq!(make_pizzas(), make_pizzas());
+    }
+
+    // Test that over-long initial snakes are clamped into the field
+    #[test]
+    fn test_initial_length_clamped() {
+        let mut game = Game::with_config(GameConfig {
+            field_size : Vector2i::new(10, 10),
+            initial_length : 20,
+            ..GameConfig::default()
+        });
+        for _ in 0..4 {
+            game.register_player(None);
+        }
+        for player in &game.players {
+            let body = player.snake.as_ref().unwrap().body();
+            assert!(body.len() >= 2);
+            for pos in body {
+                assert!(pos.x >= 0 && pos.x < 10 && pos.y >= 0 && pos.y < 10, "{:?} is out of field", pos);
+            }
+        }
+    }
+
+    // Test that lead change event fires when the lead flips
+    #[test]
+    fn test_lead_changed_event() {
+        let mut game = Game::new( Vector2i::new(10, 10));
+        let player_index0 = game.register_player(None);
+        let player_index1 = game.register_player(None);
+        let (event_tx, event_rx) = mpsc::channel();
+        game.register_global_event_channel(event_tx);
+        let lead_events = |rx : &GlobalUpdateRx| -> Vec<events::LeadChanged> {
+            rx.try_iter().filter_map(|event| match event {
+                events::GlobalEvent::LeadChanged(data) => Some(data),
+                _ => None,
+            }).collect()
+        };
+
+        // Nobody leads without score
+        game.update_leader();
+        assert!(lead_events(&event_rx).is_empty());
+        // Second player scores
+        game.players[player_index1].score = 1;
+        game.update_leader();
+        assert_eq!(lead_events(&event_rx), vec![events::LeadChanged { new_leader : player_index1, score : 1 }]);
+        // Tie keeps the current leader
+        game.players[player_index0].score = 1;
+        game.update_leader();
+        assert!(lead_events(&event_rx).is_empty());
+        // First player takes the lead
+        game.players[player_index0].score = 2;
+        game.update_leader();
+        assert_eq!(lead_events(&event_rx), vec![events::LeadChanged { new_leader : player_index0, score : 2 }]);
+        // Nothing changes - no event
+        game.update_leader();
+        assert!(lead_events(&event_rx).is_empty());
+        // Leader dies and the lead goes to the other player
+        game.players[player_index0].kill();
+        game.update_leader();
+        assert_eq!(lead_events(&event_rx), vec![events::LeadChanged { new_leader : player_index1, score : 1 }]);
+    }
+
+    // Test sleep duration of the game loop
+    #[test]
+    fn test_calc_sleep_duration() {
+        let ms = time::Duration::from_millis;
+        // Far from the update - sleep is capped
+        assert_eq!(Game::calc_sleep_duration(ms(0), ms(250)), MAX_SLEEP_INTERVAL);
+        // Close to the update - sleep until the update
+        assert_eq!(Game::calc_sleep_duration(ms(245), ms(250)), ms(5).min(MAX_SLEEP_INTERVAL));
+        // Update is overdue - don't sleep
+        assert_eq!(Game::calc_sleep_duration(ms(300), ms(250)), ms(0));
+    }
+
+    // Test that sleeping game loop still updates on schedule
+    #[test]
+    fn test_game_loop_update_schedule() {
+        let mut game = Game::new( Vector2i::new(40, 40));
+        game.register_player(None);
+        let (event_tx, event_rx) = mpsc::channel();
+        game.register_global_event_channel(event_tx);
+        let (shutdown_tx, shutdown_rx) = mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            game.game_loop(shutdown_rx);
+        });
+        // Wait for 4 update intervals and a bit
+        std::thread::sleep(UPDATE_INTERVAL * 4 + UPDATE_INTERVAL / 2);
+        shutdown_tx.send(()).unwrap();
+        handle.join().unwrap();
+
+        let updates = event_rx.try_iter()
+            .filter(|e| matches!(e, events::GlobalEvent::Update(_)))
+            .count();
+        assert!((3..=5).contains(&updates), "Got {} updates", updates);
+    }
+
+    // Test that due steps are taken from the accumulator and capped
+    #[test]
+    fn test_take_due_steps() {
+        let ms = time::Duration::from_millis;
+        let mut accumulator = ms(240);
+        assert_eq!(Game::take_due_steps(&mut accumulator, ms(250), 4), 0);
+        assert_eq!(accumulator, ms(240));
+        accumulator += ms(20);
+        assert_eq!(Game::take_due_steps(&mut accumulator, ms(250), 4), 1);
+        assert_eq!(accumulator, ms(10));
+        // Late pass catches up with several steps
+        accumulator += ms(740);
+        assert_eq!(Game::take_due_steps(&mut accumulator, ms(250), 4), 3);
+        assert_eq!(accumulator, ms(0));
+        // Long stall is capped and the extra time is dropped
+        accumulator += ms(2030);
+        assert_eq!(Game::take_due_steps(&mut accumulator, ms(250), 4), 4);
+        assert_eq!(accumulator, ms(30));
+    }
+
+    // Test that the game loop doesn't drift over many updates
+    #[test]
+    fn test_game_loop_no_drift() {
+        let mut game = Game::with_config(GameConfig {
+            field_size : Vector2i::new(40, 40),
+            wrap_around : true,
+            max_snake_length : Some(3),
+            ..GameConfig::default()
+        });
+        game.register_player(None);
+        let (event_tx, event_rx) = mpsc::channel();
+        game.register_global_event_channel(event_tx);
+        let (shutdown_tx, shutdown_rx) = mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            game.game_loop(shutdown_rx);
+        });
+        std::thread::sleep(UPDATE_INTERVAL * 12 + UPDATE_INTERVAL / 2);
+        shutdown_tx.send(()).unwrap();
+        handle.join().unwrap();
+
+        let updates = event_rx.try_iter()
+            .filter(|e| matches!(e, events::GlobalEvent::Update(_)))
+            .count();
+        assert!((11..=13).contains(&updates), "Got {} updates", updates);
+    }
+
+    // Test that starting score is reported and affects ranking
+    #[test]
+    fn test_starting_score() {
+        let mut game = Game::new( Vector2i::new(10, 10));
+        let player_index0 = game.register_player(None);
+        let player_index1 = game.register_player(None);
+        assert_eq!(game.ranking(), vec![player_index0, player_index1]);
+        game.set_score(player_index1, 5);
+        assert_eq!(game.players[player_index1].summary().score, 5);
+        assert_eq!(game.ranking(), vec![player_index1, player_index0]);
+        // Earned score adds to the starting one
+        game.players[player_index0].score += 6;
+        assert_eq!(game.ranking(), vec![player_index0, player_index1]);
+    }
+
+    // Test that cell_at agrees with the generated grid
+    #[test]
+    fn test_cell_at() {
+        let mut game = Game::new( Vector2i::new(4, 4));
+        let player_index0 = game.register_player(None);
+        let player_index1 = game.register_player(None);
+        game.players[player_index0].snake.as_mut().unwrap().set_body(vec![
+            Vector2i::new(0, 0),
+            Vector2i::new(0, 1),
+            Vector2i::new(0, 2),
+        ]);
+        game.players[player_index1].snake.as_mut().unwrap().set_body(vec![
+            Vector2i::new(3, 3),
+            Vector2i::new(2, 3),
+        ]);
+        game.pizzas.push(Pizza::normal(Vector2i::new(2, 1)));
+        let grid = game.generate_grid();
+        // Head, neck, tail, pizza, empty. Short snake has no neck
+        assert_eq!(game.cell_at(Vector2i::new(0, 0)), GridCell::Snake(SnakeRec{body_part : SnakeBodyPart::Head, player_index : player_index0}));
+        assert_eq!(game.cell_at(Vector2i::new(0, 1)), GridCell::Snake(SnakeRec{body_part : SnakeBodyPart::Neck, player_index : player_index0}));
+        assert_eq!(game.cell_at(Vector2i::new(0, 2)), GridCell::Snake(SnakeRec{body_part : SnakeBodyPart::Tail, player_index : player_index0}));
+        assert_eq!(game.cell_at(Vector2i::new(2, 3)), GridCell::Snake(SnakeRec{body_part : SnakeBodyPart::Tail, player_index : player_index1}));
+        assert_eq!(game.cell_at(Vector2i::new(2, 1)), GridCell::Pizza(PizzaRec{ kind : PizzaKind::Normal }));
+        assert_eq!(game.cell_at(Vector2i::new(1, 1)), GridCell::Empty);
+        // Every cell matches the grid
+        for ((x, y), cell) in grid.indexed_iter() {
+            assert_eq!(game.cell_at(Vector2i::new(x as i32, y as i32)), *cell);
+        }
+    }
+
+    // Test the step outcome records what happened during the step
+    #[test]
+    fn test_step_outcome() {
+        // Create small 5x5 game
+        let mut game = Game::new( Vector2i::new(5, 5));
+        let player_index0 = game.register_player(None);
+        let player_index1 = game.register_player(None);
+        // First snake eats the pizza
+        {
+            let snake0 = game.players[player_index0].snake.as_mut().unwrap();
+            snake0.set_body(vec![
+                Vector2i::new(1, 1),
+                Vector2i::new(0, 1),
+            ]);
+            assert!( snake0.try_set_look_direction( Direction::PlusX ));
         }
-        // When snake tries to move to the body position of other snake - it dies
+        game.pizzas.push(Pizza::normal(Vector2i::new(2, 1)));
+        // Second snake runs into the wall
+        {
+            let snake1 = game.players[player_index1].snake.as_mut().unwrap();
+            snake1.set_body(vec![
+                Vector2i::new(4, 3),
+                Vector2i::new(3, 3),
+            ]);
+            assert!( snake1.try_set_look_direction( Direction::PlusX ));
+        }
+
+        let outcome = game.step();
+        assert_eq!(outcome.actions, vec![
+            ActionStep::Move,
+            ActionStep::Die { cause : events::DeathCause::Wall, position : Vector2i::new(5, 3), killer : None },
+        ]);
+        assert_eq!(outcome.pizzas_eaten, vec![
+            PizzaEaten { player_index : player_index0, position : Vector2i::new(2, 1) },
+        ]);
+        // The eaten pizza is replaced with new one
+        assert_eq!(outcome.pizzas_spawned.len(), 1);
+        assert_eq!(outcome.pizzas_spawned[0], game.pizzas[0].position);
+
+        // Nothing is eaten or spawned on the next step. Dead player holds
+        game.pizzas = vec![Pizza::normal(Vector2i::new(0, 4))];
+        let outcome = game.step();
+        assert_eq!(outcome.actions, vec![ActionStep::Move, ActionStep::Hold]);
+        assert!(outcome.pizzas_eaten.is_empty());
+        assert!(outcome.pizzas_spawned.is_empty());
+    }
+
+    // Test that snakes blocking each other forever end the game as a draw
+    #[test]
+    fn test_stall_game_over() {
+        let mut game = Game::with_config(GameConfig {
+            field_size : Vector2i::new(5, 5),
+            stall_threshold : Some(3),
+            ..GameConfig::default()
+        });
+        let player_index0 = game.register_player(None);
+        let player_index1 = game.register_player(None);
+        // Snakes compete for the same cell between them
         {
             let snake0 = game.players[player_index0].snake.as_mut().unwrap();
             snake0.set_body(vec![
-                Vector2i::new(2, 3),
-                Vector2i::new(1, 3),
-            ]);     
+                Vector2i::new(1, 2),
+                Vector2i::new(0, 2),
+            ]);
             assert!( snake0.try_set_look_direction( Direction::PlusX ));
-            assert_eq!(game.predict_next_action(player_index0), ActionStep::Hold);
         }
-        // When snake tries to move ot the tail position of other snake - it moves. This is because during the move
-        // this position will be freed
-        {
-            let snake0 = game.players[player_index0].snake.as_mut().unwrap();
-            snake0.set_body(vec![
-                Vector2i::new(2, 0),
-                Vector2i::new(1, 0),
-            ]);     
-            assert!( snake0.try_set_look_direction( Direction::PlusX ));
-            assert_eq!(game.predict_next_action(player_index0), ActionStep::Move);
+        {
+            let snake1 = game.players[player_index1].snake.as_mut().unwrap();
+            snake1.set_body(vec![
+                Vector2i::new(3, 2),
+                Vector2i::new(4, 2),
+            ]);
+            assert!( snake1.try_set_look_direction( Direction::MinusX ));
+        }
+        let (event_tx, event_rx) = mpsc::channel();
+        game.register_global_event_channel(event_tx);
+        let (shutdown_tx, shutdown_rx) = mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            game.game_loop(shutdown_rx);
+            game
+        });
+        // Wait for the game over. Shut the loop down anyway so the test can't hang
+        let game_over = loop {
+            match event_rx.recv_timeout(time::Duration::from_secs(5)) {
+                Ok(events::GlobalEvent::GameOver(data)) => break Some(data),
+                Ok(_) => continue,
+                Err(_) => break None,
+            }
+        };
+        shutdown_tx.send(()).ok();
+        let game = handle.join().unwrap();
+
+        let game_over = game_over.expect("Game over event expected");
+        assert_eq!(game_over.reason, events::GameOverReason::Stalled);
+        assert_eq!(game_over.total_steps, 3);
+        // Nobody died
+        assert_eq!(game.alive_count(), 2);
+    }
+
+    // Test that spawned snake faces the center with its tail towards the wall
+    #[test]
+    fn test_spawn_faces_center() {
+        let mut game = Game::with_config(GameConfig {
+            field_size : Vector2i::new(10, 10),
+            initial_length : 3,
+            ..GameConfig::default()
+        });
+        let player_index0 = game.register_player(None);
+        let snake = game.players[player_index0].snake.as_ref().unwrap();
+        assert_eq!(snake.look_direction(), Direction::PlusX);
+        assert_eq!(snake.body().copied().collect::<Vec<_>>(), build_initial_body(Vector2i::new(2, 5), Direction::PlusX, 3));
+        // Head is the closest to the center, tail is the closest to the wall
+        assert_eq!(snake.head(), Vector2i::new(2, 5));
+        assert_eq!(snake.body().last(), Some(&Vector2i::new(0, 5)));
+    }
+
+    // Test that snake at the length cap scores but doesn't grow
+    #[test]
+    fn test_max_snake_length() {
+        let mut game = Game::with_config(GameConfig {
+            field_size : Vector2i::new(6, 6),
+            max_snake_length : Some(3),
+            ..GameConfig::default()
+        });
+        let player_index0 = game.register_player(None);
+        {
+            let snake = game.players[player_index0].snake.as_mut().unwrap();
+            snake.set_body(vec![
+                Vector2i::new(0, 1),
+                Vector2i::new(0, 0),
+            ]);
+            assert!( snake.try_set_look_direction( Direction::PlusY ));
+        }
+        // Below the cap the snake grows
+        game.pizzas.push(Pizza::normal(Vector2i::new(0, 2)));
+        game.move_player(player_index0);
+        game.move_player(player_index0);
+        assert_eq!(game.players[player_index0].snake.as_ref().unwrap().length(), 3);
+        assert_eq!(game.players[player_index0].score, 1);
+        // At the cap the snake only scores
+        game.pizzas.push(Pizza::normal(Vector2i::new(0, 4)));
+        game.move_player(player_index0);
+        game.move_player(player_index0);
+        assert_eq!(game.players[player_index0].snake.as_ref().unwrap().length(), 3);
+        assert_eq!(game.players[player_index0].score, 2);
+    }
+
+    // Test that center weighted pizzas spawn closer to the center than uniform ones
+    #[test]
+    fn test_center_weighted_pizza() {
+        let grid = grid::new_grid(21, 21);
+        let free_cells = 21 * 21;
+        let center = Vector2i::new(10, 10);
+        let mean_distance = |dist : PizzaSpawnDist| {
+            let mut spawner = RandomSpawner::new(dist, Some(7));
+            let mut total = 0;
+            for _ in 0..2000 {
+                let pos = spawner.spawn_pos(&grid, free_cells).unwrap();
+                let offset = pos - center;
+                total += offset.x.abs() + offset.y.abs();
+            }
+            total as f64 / 2000.0
+        };
+        let uniform = mean_distance(PizzaSpawnDist::Uniform);
+        let center_weighted = mean_distance(PizzaSpawnDist::CenterWeighted);
+        assert!(center_weighted < uniform * 0.9, "{} vs {}", center_weighted, uniform);
+
+        // Occupied cells are never picked and full grid gives no pizza
+        let mut grid = grid::new_grid(2, 1);
+        grid.set_at(0, 0, GridCell::Pizza(PizzaRec{ kind : PizzaKind::Normal }));
+        let mut spawner = RandomSpawner::new(PizzaSpawnDist::CenterWeighted, Some(7));
+        for _ in 0..10 {
+            assert_eq!(spawner.spawn_pos(&grid, 1),
+                Some(Vector2i::new(1, 0)));
+        }
+        grid.set_at(1, 0, GridCell::Pizza(PizzaRec{ kind : PizzaKind::Normal }));
+        assert_eq!(spawner.spawn_pos(&grid, 0), None);
+    }
+
+    // Test move safety checks
+    #[test]
+    fn test_is_move_safe() {
+        // Create small 5x5 game
+        let mut game = Game::new( Vector2i::new(5, 5));
+        let player_index0 = game.register_player(None);
+        let player_index1 = game.register_player(None);
+        {
+            let snake0 = game.players[player_index0].snake.as_mut().unwrap();
+            snake0.set_body(vec![
+                Vector2i::new(0, 1),
+                Vector2i::new(1, 1),
+            ]);
+            assert!( snake0.try_set_look_direction( Direction::MinusX ));
+        }
+        {
+            let snake1 = game.players[player_index1].snake.as_mut().unwrap();
+            snake1.set_body(vec![
+                Vector2i::new(2, 2),
+                Vector2i::new(1, 2),
+                Vector2i::new(0, 2),
+            ]);
+            assert!( snake1.try_set_look_direction( Direction::PlusX ));
+        }
+        // Wall is unsafe
+        assert!(!game.is_move_safe(player_index0, Direction::MinusX));
+        // Open space is safe
+        assert!(game.is_move_safe(player_index0, Direction::MinusY));
+        // Tail of the other snake moves away, so it's safe
+        assert!(game.is_move_safe(player_index0, Direction::PlusY));
+        // Reversing is never safe
+        assert!(!game.is_move_safe(player_index0, Direction::PlusX));
+        // State is untouched
+        assert_eq!(game.players[player_index0].snake.as_ref().unwrap().look_direction(), Direction::MinusX);
+
+        // Dead players have no safe moves
+        game.players[player_index1].kill();
+        assert!(!game.is_move_safe(player_index1, Direction::PlusX));
+    }
+
+    // Test that snake survives warning hazard and dies in active one
+    #[test]
+    fn test_hazard() {
+        let mut game = Game::new( Vector2i::new(6, 6));
+        let player_index0 = game.register_player(None);
+        let player_index1 = game.register_player(None);
+        {
+            let snake0 = game.players[player_index0].snake.as_mut().unwrap();
+            snake0.set_body(vec![
+                Vector2i::new(1, 1),
+                Vector2i::new(0, 1),
+            ]);
+            assert!( snake0.try_set_look_direction( Direction::PlusX ));
+        }
+        {
+            let snake1 = game.players[player_index1].snake.as_mut().unwrap();
+            snake1.set_body(vec![
+                Vector2i::new(1, 4),
+                Vector2i::new(0, 4),
+            ]);
+            assert!( snake1.try_set_look_direction( Direction::PlusX ));
+        }
+        // Both hazards warn right away and turn active on step 1
+        game.add_hazard(Hazard { position : Vector2i::new(2, 1), warn_step : 0, active_step : 1, clear_step : 3 });
+        game.add_hazard(Hazard { position : Vector2i::new(3, 4), warn_step : 0, active_step : 1, clear_step : 3 });
+        assert_eq!(game.cell_at(Vector2i::new(3, 4)), GridCell::Hazard { active : false });
+
+        // First snake enters the warning hazard and survives
+        game.step();
+        assert!(game.players[player_index0].alive());
+        assert!(game.players[player_index1].alive());
+        assert_eq!(game.cell_at(Vector2i::new(3, 4)), GridCell::Hazard { active : true });
+        assert_eq!(game.generate_grid().at(3, 4), GridCell::Hazard { active : true });
+
+        // Second snake enters the active hazard and dies
+        assert_eq!(game.predict_next_action(player_index1), ActionStep::Die {
+            cause : events::DeathCause::Hazard, position : Vector2i::new(3, 4), killer : None });
+        game.step();
+        assert!(game.players[player_index0].alive());
+        assert!(!game.players[player_index1].alive());
+
+        // Hazards clear on schedule
+        game.step();
+        assert!(game.hazards().is_empty());
+        assert_eq!(game.cell_at(Vector2i::new(3, 4)), GridCell::Empty);
+    }
+
+    // Test that pizzas eaten in quick succession give multiplied score
+    #[test]
+    fn test_combo_multiplier() {
+        let mut game = Game::with_config(GameConfig {
+            field_size : Vector2i::new(3, 10),
+            combo_window : Some(3),
+            ..GameConfig::default()
+        });
+        let player_index0 = game.register_player(None);
+        {
+            let snake = game.players[player_index0].snake.as_mut().unwrap();
+            snake.set_body(vec![
+                Vector2i::new(0, 1),
+                Vector2i::new(0, 0),
+            ]);
+            assert!( snake.try_set_look_direction( Direction::PlusY ));
+        }
+        // First pizza gives normal score
+        game.pizzas.push(Pizza::normal(Vector2i::new(0, 2)));
+        game.step();
+        assert_eq!(game.players[player_index0].score, 1);
+        // Second one within the window is doubled
+        game.pizzas = vec![Pizza::normal(Vector2i::new(0, 4))];
+        game.step();
+        game.step();
+        assert_eq!(game.players[player_index0].score, 3);
+        assert_eq!(game.players[player_index0].summary().multiplier, 2);
+        // Waiting too long resets the multiplier
+        game.pizzas = vec![Pizza::normal(Vector2i::new(2, 9))];
+        for _ in 0..4 {
+            game.step();
+        }
+        assert_eq!(game.players[player_index0].summary().multiplier, 1);
+        assert_eq!(game.players[player_index0].score, 3);
+    }
+
+    // Test that game loop returns the game over to the joining thread
+    #[test]
+    fn test_game_loop_returns_game_over() {
+        let mut game = Game::new( Vector2i::new(10, 10));
+        let player_index0 = game.register_player(None);
+        game.set_score(player_index0, 4);
+        let (_shutdown_tx, shutdown_rx) = mpsc::channel();
+        // Lone snake runs into the wall
+        let handle = std::thread::spawn(move || {
+            let game_over = game.game_loop(shutdown_rx);
+            (game, game_over)
+        });
+        let (game, game_over) = handle.join().unwrap();
+        let game_over = game_over.expect("Game over expected");
+        assert_eq!(game_over.reason, events::GameOverReason::AllDead);
+        assert_eq!(game_over.players_summary, game.get_players_summary());
+        // The snake may eat pizzas on its way
+        assert!(!game_over.players_summary[0].alive);
+        assert!(game_over.players_summary[0].score >= 4);
+
+        // Shut down game returns nothing
+        let mut game = Game::new( Vector2i::new(10, 10));
+        game.register_player(None);
+        let (shutdown_tx, shutdown_rx) = mpsc::channel();
+        shutdown_tx.send(()).unwrap();
+        assert_eq!(game.game_loop(shutdown_rx), None);
+    }
+
+    // Test that snakes spawning too close are rejected
+    #[test]
+    fn test_min_spawn_distance() {
+        // On 3x3 field the first two snakes would share the center cell
+        let mut game = Game::new( Vector2i::new(3, 3));
+        assert_eq!(game.try_register_player(None), Some(0));
+        assert_eq!(game.try_register_player(None), None);
+        assert_eq!(game.players.len(), 1);
+
+        // Spawned snakes keep the distance on a bigger field
+        let mut game = Game::with_config(GameConfig {
+            field_size : Vector2i::new(10, 10),
+            spawn_policy : SpawnPolicy { snake_distance : 3, pizza_distance : 0 },
+            ..GameConfig::default()
+        });
+        for player_index in 0..4 {
+            assert_eq!(game.try_register_player(None), Some(player_index));
+        }
+        // No more spawn points
+        assert_eq!(game.try_register_player(None), None);
+        for (player_index0, player0) in game.players.iter().enumerate() {
+            for player1 in &game.players[player_index0 + 1..] {
+                for pos0 in player0.snake.as_ref().unwrap().body() {
+                    for pos1 in player1.snake.as_ref().unwrap().body() {
+                        let offset = *pos0 - *pos1;
+                        assert!(offset.x.abs() + offset.y.abs() >= 3);
+                    }
+                }
+            }
+        }
+    }
+
+    // Test that swapped in control channel steers the snake and the old one is ignored
+    #[test]
+    fn test_set_player_control() {
+        let mut game = Game::new( Vector2i::new(10, 10));
+        let (old_tx, old_rx) = mpsc::channel();
+        let player_index0 = game.register_player(Some(Box::new(old_rx)));
+        assert_eq!(game.players[player_index0].snake.as_ref().unwrap().look_direction(), Direction::PlusX);
+
+        let (new_tx, new_rx) = mpsc::channel();
+        game.set_player_control(player_index0, Some(Box::new(new_rx)));
+        // Old channel is dropped by the game
+        assert!(old_tx.send(Direction::MinusY).is_err());
+        new_tx.send(Direction::PlusY).unwrap();
+        game.players[player_index0].read_inputs(game.config.input_buffer, game.config.max_inputs_per_tick);
+        assert_eq!(game.players[player_index0].snake.as_ref().unwrap().look_direction(), Direction::PlusY);
+
+        // Without control the snake goes straight
+        game.set_player_control(player_index0, None);
+        assert!(new_tx.send(Direction::PlusX).is_err());
+        game.players[player_index0].read_inputs(game.config.input_buffer, game.config.max_inputs_per_tick);
+        assert_eq!(game.players[player_index0].snake.as_ref().unwrap().look_direction(), Direction::PlusY);
+    }
+
+    // Test that pizzas drift towards the closest snake head
+    #[test]
+    fn test_pizza_gravity() {
+        let mut game = Game::with_config(GameConfig {
+            field_size : Vector2i::new(10, 10),
+            pizza_gravity_interval : Some(2),
+            ..GameConfig::default()
+        });
+        let player_index0 = game.register_player(None);
+        {
+            let snake = game.players[player_index0].snake.as_mut().unwrap();
+            snake.set_body(vec![
+                Vector2i::new(0, 1),
+                Vector2i::new(0, 0),
+            ]);
+            assert!( snake.try_set_look_direction( Direction::PlusY ));
+        }
+        game.pizzas.push(Pizza::normal(Vector2i::new(9, 9)));
+        // Pizza moves every second step along the longer axis first
+        game.step();
+        assert_eq!(game.pizzas[0].position, Vector2i::new(9, 9));
+        game.step();
+        assert_eq!(game.pizzas[0].position, Vector2i::new(8, 9));
+        game.step();
+        game.step();
+        assert_eq!(game.pizzas[0].position, Vector2i::new(7, 9));
+
+        // Pizza never moves onto a snake
+        game.pizzas = vec![Pizza::normal(Vector2i::new(1, 5))];
+        assert_eq!(game.players[player_index0].snake.as_ref().unwrap().head(), Vector2i::new(0, 5));
+        game.apply_pizza_gravity();
+        assert_eq!(game.pizzas[0].position, Vector2i::new(1, 5));
+    }
+
+    // Test that tick timing is reported only when enabled
+    #[test]
+    fn test_tick_timing() {
+        let timings = |report_tick_timing : bool| {
+            let mut game = Game::with_config(GameConfig {
+                field_size : Vector2i::new(10, 10),
+                report_tick_timing,
+                ..GameConfig::default()
+            });
+            game.register_player(None);
+            let (event_tx, event_rx) = mpsc::channel();
+            game.register_global_event_channel(event_tx);
+            game.update();
+            game.update();
+            event_rx.try_iter()
+                .filter_map(|event| match event {
+                    events::GlobalEvent::TickTiming(timing) => Some(timing),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        assert!(timings(false).is_empty());
+        let timings = timings(true);
+        assert_eq!(timings.iter().map(|timing| timing.step).collect::<Vec<_>>(), vec![1, 2]);
+        assert!(timings.iter().all(|timing| timing.duration > time::Duration::ZERO));
+    }
+
+    // Test that recorded replay verifies and tampered one doesn't
+    #[test]
+    fn test_verify_replay() {
+        let config = GameConfig { field_size : Vector2i::new(10, 10), ..GameConfig::default() };
+        let inputs = [
+            ScriptedInput { step : 0, player_index : 0, direction : Direction::MinusY },
+            ScriptedInput { step : 2, player_index : 0, direction : Direction::PlusX },
+            ScriptedInput { step : 3, player_index : 0, direction : Direction::MinusY },
+        ];
+        let recorded = run_replay(&config, 5, 1, &inputs, 1000).unwrap();
+        assert!(verify_replay(&config, 5, &inputs, &recorded));
+
+        // Changed input gives different outcome
+        let mut tampered = inputs;
+        tampered[2].direction = Direction::PlusY;
+        assert!(!verify_replay(&config, 5, &tampered, &recorded));
+        // Changed result doesn't verify either
+        let mut forged = recorded.clone();
+        forged.players_summary[0].score += 1;
+        assert!(!verify_replay(&config, 5, &inputs, &forged));
+    }
+
+    // Test that the tail of a growing snake doesn't vacate
+    #[test]
+    fn test_predict_growing_tail() {
+        let mut game = Game::new( Vector2i::new(5, 5));
+        let player_index0 = game.register_player(None);
+        {
+            // Snake is curled so its head is next to its tail
+            let snake = game.players[player_index0].snake.as_mut().unwrap();
+            snake.set_body(vec![
+                Vector2i::new(1, 1),
+                Vector2i::new(2, 1),
+                Vector2i::new(2, 2),
+                Vector2i::new(1, 2),
+            ]);
+            assert!( snake.try_set_look_direction( Direction::PlusY ));
+        }
+        // Tail moves away
+        assert_eq!(game.predict_next_action(player_index0), ActionStep::Move);
+        assert!(game.is_move_safe(player_index0, Direction::PlusY));
+        // Tail stays in place while growing
+        game.players[player_index0].snake.as_mut().unwrap().eat(1);
+        assert_eq!(game.predict_next_action(player_index0), ActionStep::Die {
+            cause : events::DeathCause::OwnSnake, position : Vector2i::new(1, 2), killer : None });
+        assert!(!game.is_move_safe(player_index0, Direction::PlusY));
+    }
+
+    // Test the player died scenario set up with the builder
+    #[test]
+    fn test_game_builder() {
+        let mut game = GameBuilder::new()
+            .field(5, 5)
+            .snake(Vector2i::new(1, 2), Direction::PlusX, 2)
+            .snake(Vector2i::new(2, 1), Direction::MinusY, 3)
+            .build();
+        assert_eq!(game.predict_next_action(0), ActionStep::Die {
+            cause : events::DeathCause::OtherSnake, position : Vector2i::new(2, 2), killer : Some(1) });
+        game.step();
+        assert!(!game.players[0].alive());
+        assert!(game.players[1].alive());
+
+        // Walls and pizzas are placed as given
+        let game = GameBuilder::new()
+            .field(4, 4)
+            .seed(3)
+            .wall(Vector2i::new(3, 0))
+            .pizza(Vector2i::new(1, 3))
+            .snake(Vector2i::new(2, 0), Direction::PlusX, 2)
+            .build();
+        assert_eq!(game.cell_at(Vector2i::new(3, 0)), GridCell::Wall);
+        assert_eq!(game.cell_at(Vector2i::new(1, 3)), GridCell::Pizza(PizzaRec{ kind : PizzaKind::Normal }));
+        assert_eq!(game.num_empty_cells(), 16 - 1 - 1 - 2);
+        assert_eq!(game.predict_next_action(0), ActionStep::Die {
+            cause : events::DeathCause::Wall, position : Vector2i::new(3, 0), killer : None });
+        assert!(game.is_move_safe(0, Direction::PlusY));
+    }
+
+    // Test that snake crossing the edge in wrap mode is reported in the update
+    #[test]
+    fn test_wrap_around() {
+        let config = GameConfig { wrap_around : true, ..GameConfig::default() };
+        let mut game = GameBuilder::with_config(config)
+            .field(5, 5)
+            .pizza(Vector2i::new(0, 0))
+            .snake(Vector2i::new(3, 2), Direction::PlusX, 2)
+            .build();
+        let (event_tx, event_rx) = mpsc::channel();
+        game.register_global_event_channel(event_tx);
+        let wrapped = |event_rx : &mpsc::Receiver<events::GlobalEvent>| event_rx.try_iter()
+            .find_map(|e| match e {
+                events::GlobalEvent::Update(update) => Some(update.wrapped),
+                _ => None,
+            })
+            .unwrap();
+
+        // Reaching the edge is a normal move
+        game.update();
+        assert_eq!(game.players[0].snake.as_ref().unwrap().head(), Vector2i::new(4, 2));
+        assert!(wrapped(&event_rx).is_empty());
+        // Crossing the edge enters the field from the opposite side
+        assert!(game.is_move_safe(0, Direction::PlusX));
+        game.update();
+        assert!(game.players[0].alive());
+        assert_eq!(game.players[0].snake.as_ref().unwrap().head(), Vector2i::new(0, 2));
+        assert_eq!(wrapped(&event_rx), vec![0]);
+        // Indicator only lasts for the step of wrapping
+        game.update();
+        assert!(wrapped(&event_rx).is_empty());
+
+        // Without wrapping the edge is deadly
+        let mut game = GameBuilder::new()
+            .field(5, 5)
+            .snake(Vector2i::new(4, 2), Direction::PlusX, 2)
+            .build();
+        game.step();
+        assert!(!game.players[0].alive());
+    }
+
+    // Test that the survivor gains the reward for the killed snake
+    #[test]
+    fn test_kill_reward() {
+        let config = GameConfig {
+            kill_reward : Some(KillReward { score : 3, growth : 2 }),
+            ..GameConfig::default()
+        };
+        let mut game = GameBuilder::with_config(config)
+            .field(5, 5)
+            .snake(Vector2i::new(1, 2), Direction::PlusX, 2)
+            .snake(Vector2i::new(2, 1), Direction::MinusY, 3)
+            .build();
+        let (event_tx, event_rx) = mpsc::channel();
+        game.register_global_event_channel(event_tx);
+        game.step();
+        assert!(!game.players[0].alive());
+        assert_eq!(game.players[1].score, 3);
+        assert_eq!(game.players[1].snake.as_ref().unwrap().pending_length(), 5);
+        let credits : Vec<_> = event_rx.try_iter()
+            .filter_map(|e| match e {
+                events::GlobalEvent::KillCredited(credit) => Some(credit),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(credits, vec![events::KillCredited { killer : 1, victim : 0, score : 3, growth : 2 }]);
+
+        // No reward for the snake that was warped instead of killed
+        let config = GameConfig {
+            kill_reward : Some(KillReward { score : 3, growth : 2 }),
+            warp_on_death : Some(WarpPenalty { length_kept_percent : 100, score_loss : 0 }),
+            ..GameConfig::default()
+        };
+        let mut game = GameBuilder::with_config(config)
+            .field(5, 5)
+            .seed(1)
+            .snake(Vector2i::new(1, 2), Direction::PlusX, 2)
+            .snake(Vector2i::new(2, 1), Direction::MinusY, 3)
+            .build();
+        let (event_tx, event_rx) = mpsc::channel();
+        game.register_global_event_channel(event_tx);
+        game.step();
+        assert!(game.players[0].alive());
+        assert_eq!(game.players[1].score, 0);
+        assert_eq!(game.players[1].snake.as_ref().unwrap().pending_length(), 3);
+        assert!(!event_rx.try_iter().any(|e| matches!(e,
+            events::GlobalEvent::KillCredited(_) | events::GlobalEvent::PlayerDied(_))));
+
+        // No reward by default
+        let mut game = GameBuilder::new()
+            .field(5, 5)
+            .snake(Vector2i::new(1, 2), Direction::PlusX, 2)
+            .snake(Vector2i::new(2, 1), Direction::MinusY, 3)
+            .build();
+        game.step();
+        assert!(!game.players[0].alive());
+        assert_eq!(game.players[1].score, 0);
+        assert_eq!(game.players[1].snake.as_ref().unwrap().pending_length(), 3);
+    }
+
+    // Test that pizzas spawn at exact positions with first free cell spawner
+    #[test]
+    fn test_first_free_pizza_spawner() {
+        let mut game = GameBuilder::new()
+            .field(4, 4)
+            .snake(Vector2i::new(1, 2), Direction::MinusY, 2)
+            .build();
+        game.set_pizza_spawner(Box::new(FirstFreeSpawner));
+        let outcome = game.step();
+        assert_eq!(outcome.pizzas_spawned, vec![Vector2i::new(0, 0)]);
+        game.players[0].snake.as_mut().unwrap().try_set_look_direction(Direction::MinusX);
+        game.step();
+        game.players[0].snake.as_mut().unwrap().try_set_look_direction(Direction::MinusY);
+        let outcome = game.step();
+        // Snake eats the pizza and covers (0, 0) and (0, 1)
+        assert_eq!(outcome.pizzas_eaten, vec![PizzaEaten { player_index : 0, position : Vector2i::new(0, 0) }]);
+        assert_eq!(outcome.pizzas_spawned, vec![Vector2i::new(0, 2)]);
+    }
+
+    // Test that heartbeats arrive between the update steps
+    #[test]
+    fn test_heartbeat() {
+        let mut game = Game::with_config(GameConfig {
+            field_size : Vector2i::new(40, 40),
+            heartbeat_interval : Some(UPDATE_INTERVAL / 5),
+            ..GameConfig::default()
+        });
+        game.register_player(None);
+        let (event_tx, event_rx) = mpsc::channel();
+        game.register_global_event_channel(event_tx);
+        let (shutdown_tx, shutdown_rx) = mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            game.game_loop(shutdown_rx);
+        });
+        std::thread::sleep(UPDATE_INTERVAL * 2 + UPDATE_INTERVAL / 2);
+        shutdown_tx.send(()).unwrap();
+        handle.join().unwrap();
+
+        // Count heartbeats between the first and the second update
+        let heartbeats : Vec<events::Heartbeat> = event_rx.try_iter()
+            .skip_while(|e| !matches!(e, events::GlobalEvent::Update(_)))
+            .skip(1)
+            .take_while(|e| !matches!(e, events::GlobalEvent::Update(_)))
+            .filter_map(|e| match e {
+                events::GlobalEvent::Heartbeat(heartbeat) => Some(heartbeat),
+                _ => None,
+            })
+            .collect();
+        assert!(heartbeats.len() >= 2, "Got {} heartbeats", heartbeats.len());
+        assert!(heartbeats.iter().all(|h| *h == events::Heartbeat { step : 1, alive_count : 1 }));
+    }
+
+    // Test that the arena shrinks on schedule and kills the snakes in the ring
+    #[test]
+    fn test_shrink_arena() {
+        let config = GameConfig { shrink_interval : Some(2), ..GameConfig::default() };
+        let mut game = GameBuilder::with_config(config)
+            .field(7, 7)
+            .snake(Vector2i::new(3, 3), Direction::PlusY, 2)
+            .snake(Vector2i::new(4, 1), Direction::PlusX, 2)
+            .build();
+        let (event_tx, event_rx) = mpsc::channel();
+        game.register_global_event_channel(event_tx);
+        game.step();
+        assert!(game.walls().is_empty());
+        // Second snake enters the outer ring
+        game.step();
+        assert_eq!(game.walls().len(), 24);
+        assert_eq!(game.cell_at(Vector2i::new(0, 0)), GridCell::Wall);
+        assert_ne!(game.cell_at(Vector2i::new(1, 1)), GridCell::Wall);
+        assert!(game.players[0].alive());
+        assert!(!game.players[1].alive());
+        let events : Vec<_> = event_rx.try_iter().collect();
+        assert!(events.contains(&events::GlobalEvent::PlayerDied(events::PlayerDied {
+            player_index : 1, cause : events::DeathCause::Wall, position : Vector2i::new(6, 1), killer : None })));
+        assert!(events.contains(&events::GlobalEvent::ArenaShrunk(events::ArenaShrunk { step : 2, rings : 1 })));
+
+        // Shrinks until the center is left. Keep the snake near the center so the game goes on
+        for _ in 0..4 {
+            game.players[0].snake = Some(Snake::new(Vector2i::new(3, 3), Direction::PlusY, 2));
+            game.step();
+        }
+        assert_eq!(game.walls().len(), 48);
+        assert_eq!(game.generate_grid().iter().filter(|cell| **cell != GridCell::Wall).count(), 1);
+    }
+
+    // Test that buffered summary is the same as the allocated one
+    #[test]
+    fn test_players_summary_into() {
+        let mut game = Game::new(Vector2i::new(10, 10));
+        game.register_player(None);
+        game.register_player(None);
+        game.set_score(1, 7);
+        // Stale contents are replaced
+        let mut buf = vec![events::PlayerSummary { score : 99, alive : false, multiplier : 1, color : PlayerColor::Red, rgb : (0, 0, 0) }; 5];
+        game.players_summary_into(&mut buf);
+        assert_eq!(buf, game.get_players_summary());
+        game.players[0].kill();
+        let capacity = buf.capacity();
+        game.players_summary_into(&mut buf);
+        assert_eq!(buf, game.get_players_summary());
+        assert_eq!(buf.capacity(), capacity);
+    }
+
+    // Spawner that is slow and never finds the place for pizza
+    struct SlowSpawner;
+
+    impl PizzaSpawner for SlowSpawner {
+        fn spawn_pos(&mut self, _grid : &Grid, _estimated_free_cells : i32) -> Option<Vector2i> {
+            std::thread::sleep(time::Duration::from_millis(5));
+            None
+        }
+    }
+
+    // Test that steps exceeding the tick budget are reported
+    #[test]
+    fn test_tick_overrun() {
+        let mut game = Game::with_config(GameConfig {
+            field_size : Vector2i::new(20, 20),
+            tick_budget : Some(time::Duration::from_millis(1)),
+            ..GameConfig::default()
+        });
+        game.register_player(None);
+        let (event_tx, event_rx) = mpsc::channel();
+        game.register_global_event_channel(event_tx);
+        assert_eq!(game.tick_overruns(), 0);
+
+        game.set_pizza_spawner(Box::new(SlowSpawner));
+        game.update();
+        game.update();
+        assert_eq!(game.tick_overruns(), 2);
+        let overruns : Vec<events::TickOverrun> = event_rx.try_iter()
+            .filter_map(|e| match e {
+                events::GlobalEvent::TickOverrun(overrun) => Some(overrun),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(overruns.len(), 2);
+        assert_eq!((overruns[1].step, overruns[1].overruns), (2, 2));
+        assert!(overruns[1].duration > overruns[1].budget);
+    }
+
+    // Test that border preset walls the perimeter
+    #[test]
+    fn test_preset_border() {
+        let mut game = Game::new(Vector2i::new(5, 4));
+        game.add_wall(Vector2i::new(2, 2));
+        game.apply_preset(MapPreset::Border);
+        assert_eq!(game.walls().len(), 14);
+        assert_eq!(grid::to_ascii(&game.generate_grid()), "%%%%%\n%...%\n%...%\n%%%%%\n");
+        game.apply_preset(MapPreset::Empty);
+        assert!(game.walls().is_empty());
+    }
+
+    // Test that cross preset walls the two center lines
+    #[test]
+    fn test_preset_cross_center() {
+        let mut game = Game::new(Vector2i::new(5, 4));
+        game.apply_preset(MapPreset::CrossCenter);
+        assert_eq!(game.walls().len(), 8);
+        assert_eq!(grid::to_ascii(&game.generate_grid()), "..%..\n..%..\n%%%%%\n..%..\n");
+    }
+
+    // Test that random blocks are reproducible and keep the spawn points free
+    #[test]
+    fn test_preset_random_blocks() {
+        let mut game = Game::new(Vector2i::new(20, 20));
+        game.apply_preset(MapPreset::RandomBlocks(0.3, 5));
+        let walls = game.walls().to_vec();
+        assert!((60..180).contains(&walls.len()), "Got {} walls", walls.len());
+        game.apply_preset(MapPreset::RandomBlocks(0.3, 5));
+        assert_eq!(game.walls(), &walls[..]);
+        // Full density walls everything but the spawn points
+        game.apply_preset(MapPreset::RandomBlocks(1.0, 5));
+        for _ in 0..MAX_PLAYERS {
+            game.register_player(None);
+        }
+        for player_index in 0..MAX_PLAYERS {
+            assert!(game.is_move_safe(player_index, game.players[player_index].snake.as_ref().unwrap().look_direction()));
+        }
+        game.step();
+        assert_eq!(game.alive_count(), MAX_PLAYERS);
+    }
+
+    // Test that players get distinct colors and the set color is reported
+    #[test]
+    fn test_player_color() {
+        let mut game = Game::new(Vector2i::new(20, 20));
+        let player_index0 = game.register_player(None);
+        let player_index1 = game.register_player(None);
+        let summary = game.get_players_summary();
+        assert_eq!(summary[player_index0].color, PlayerColor::White);
+        assert_eq!(summary[player_index1].color, PlayerColor::Green);
+        game.set_player_color(player_index1, PlayerColor::Red);
+        assert_eq!(game.get_players_summary()[player_index1].color, PlayerColor::Red);
+        assert_eq!(game.get_players_summary()[player_index0].color, PlayerColor::White);
+    }
+
+    // Test that a step changes only a few cells of the grid
+    #[test]
+    fn test_changed_cells_after_step() {
+        let mut game = GameBuilder::new()
+            .field(20, 20)
+            .pizza(Vector2i::new(0, 0))
+            .snake(Vector2i::new(5, 5), Direction::PlusX, 4)
+            .build();
+        let prev = game.generate_grid();
+        game.step();
+        let changed = game.generate_grid().changed_cells_since(&prev);
+        // New head, old head becomes neck, old neck becomes body, old tail cell is vacated
+        // and the new tail
+        assert_eq!(changed.len(), 5);
+        assert!(changed.contains(&(Vector2i::new(6, 5), GridCell::Snake(SnakeRec{ body_part : SnakeBodyPart::Head, player_index : 0 }))));
+        assert!(changed.contains(&(Vector2i::new(2, 5), GridCell::Empty)));
+    }
+
+    // Test that the resolution order decides who is credited when two snakes kill each other
+    #[test]
+    fn test_resolution_order() {
+        let make_game = |resolution_order| {
+            let config = GameConfig {
+                kill_reward : Some(KillReward { score : 3, growth : 0 }),
+                resolution_order,
+                ..GameConfig::default()
+            };
+            // Each snake turns into the neck of the other one
+            let mut game = GameBuilder::with_config(config)
+                .field(6, 6)
+                .pizza(Vector2i::new(5, 5))
+                .snake(Vector2i::new(2, 2), Direction::MinusY, 3)
+                .snake(Vector2i::new(3, 3), Direction::PlusY, 3)
+                .build();
+            assert_eq!(game.steer_player(0, Direction::PlusX), DirChange::Applied);
+            assert_eq!(game.steer_player(1, Direction::MinusX), DirChange::Applied);
+            game
+        };
+        let died_order = |event_rx : &mpsc::Receiver<events::GlobalEvent>| -> Vec<PlayerIndex> {
+            event_rx.try_iter()
+                .filter_map(|e| match e {
+                    events::GlobalEvent::PlayerDied(died) => Some(died.player_index),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // Player 0 dies first, so only player 1 is credited
+        let mut game = make_game(ResolutionOrder::PlayerIndex);
+        let (event_tx, event_rx) = mpsc::channel();
+        game.register_global_event_channel(event_tx);
+        let outcome = game.step();
+        assert_eq!(outcome.actions[0], ActionStep::Die {
+            cause : events::DeathCause::OtherSnake, position : Vector2i::new(3, 2), killer : Some(1) });
+        assert_eq!(outcome.actions[1], ActionStep::Die {
+            cause : events::DeathCause::OtherSnake, position : Vector2i::new(2, 3), killer : Some(0) });
+        assert_eq!(died_order(&event_rx), vec![0, 1]);
+        assert_eq!((game.players[0].score, game.players[1].score), (0, 3));
+
+        // Player 1 leads, so it dies first and player 0 is credited
+        let mut game = make_game(ResolutionOrder::HighestScore);
+        game.players[1].score = 1;
+        let (event_tx, event_rx) = mpsc::channel();
+        game.register_global_event_channel(event_tx);
+        game.step();
+        assert_eq!(died_order(&event_rx), vec![1, 0]);
+        assert_eq!((game.players[0].score, game.players[1].score), (3, 1));
+
+        // Equal scores fall back to the index order
+        let mut game = make_game(ResolutionOrder::HighestScore);
+        game.step();
+        assert_eq!((game.players[0].score, game.players[1].score), (0, 3));
+
+        // Shuffled order is the same for the same seed, and both orders happen
+        let shuffled_credits = |seed| {
+            let mut game = make_game(ResolutionOrder::Shuffled);
+            game.rng = StdRng::seed_from_u64(seed);
+            game.step();
+            (game.players[0].score, game.players[1].score)
+        };
+        let credits : Vec<_> = (0..32).map(shuffled_credits).collect();
+        assert_eq!(credits, (0..32).map(shuffled_credits).collect::<Vec<_>>());
+        assert!(credits.contains(&(0, 3)) && credits.contains(&(3, 0)));
+    }
+
+    // Test that the snake about to die warps to a free place with reduced length and score
+    #[test]
+    fn test_warp_on_death() {
+        let config = GameConfig {
+            warp_on_death : Some(WarpPenalty { length_kept_percent : 50, score_loss : 2 }),
+            ..GameConfig::default()
+        };
+        let mut game = GameBuilder::with_config(config.clone())
+            .field(8, 8)
+            .seed(3)
+            .pizza(Vector2i::new(0, 0))
+            .snake(Vector2i::new(7, 3), Direction::PlusX, 6)
+            .build();
+        game.set_score(0, 5);
+        let (event_tx, event_rx) = mpsc::channel();
+        game.register_global_event_channel(event_tx);
+        let outcome = game.step();
+        assert!(matches!(outcome.actions[0], ActionStep::Die { cause : events::DeathCause::Wall, .. }));
+        // Snake is alive, shorter and off the pizza. The old body cells are free again
+        assert!(game.players[0].alive());
+        assert_eq!(game.snake_length(0), 3);
+        assert_eq!(game.players[0].score, 3);
+        let snake = game.players[0].snake.as_ref().unwrap();
+        assert_eq!(game.pizza_positions(), vec![Vector2i::new(0, 0)]);
+        for pos in snake.body() {
+            assert!(pos.x >= 0 && pos.x < 8 && pos.y >= 0 && pos.y < 8);
+            assert_ne!(*pos, Vector2i::new(0, 0));
+        }
+        let events : Vec<_> = event_rx.try_iter().collect();
+        assert!(!events.iter().any(|e| matches!(e, events::GlobalEvent::PlayerDied(_))));
+        assert!(events.contains(&events::GlobalEvent::PlayerWarped(events::PlayerWarped {
+            player_index : 0,
+            cause : events::DeathCause::Wall,
+            from : Vector2i::new(8, 3),
+            to : snake.head(),
+            length : 3,
+            score_lost : 2,
+        })));
+        // The warped snake can move on
+        let outcome = game.step();
+        assert_eq!(outcome.actions[0], ActionStep::Move);
+
+        // Snake dies if there is no room for it
+        let mut game = GameBuilder::with_config(config)
+            .field(3, 1)
+            .snake(Vector2i::new(2, 0), Direction::PlusX, 3)
+            .build();
+        game.step();
+        assert!(!game.players[0].alive());
+    }
+
+    // Test that the player is steered by a custom controller
+    #[test]
+    fn test_custom_controller() {
+        // Plays the script, None ends the directions of one read
+        struct ScriptedController {
+            script : std::collections::VecDeque<Option<Direction>>,
         }
-        // When snake competes with other snake for same position - it holds
-        {
-            let snake0 = game.players[player_index0].snake.as_mut().unwrap();
-            snake0.set_body(vec![
-                Vector2i::new(2, 0),
-                Vector2i::new(1, 0),
-            ]);     
-            assert!( snake0.try_set_look_direction( Direction::PlusX ));
-            assert_eq!(game.predict_next_action(player_index0), ActionStep::Move);
+        impl Controller for ScriptedController {
+            fn poll_direction(&mut self) -> Option<Direction> {
+                self.script.pop_front().flatten()
+            }
         }
-
+        let mut game = Game::new(Vector2i::new(10, 10));
+        let script = vec![Some(Direction::PlusY), None, None, Some(Direction::MinusY), Some(Direction::PlusX), None];
+        let player_index = game.register_player(Some(Box::new(ScriptedController { script : script.into() })));
+        let head = game.snake_head(player_index).unwrap();
+        let mut heads = Vec::new();
+        for _ in 0..3 {
+            game.players[player_index].read_inputs(game.config.input_buffer, game.config.max_inputs_per_tick);
+            game.step();
+            heads.push(game.snake_head(player_index).unwrap() - head);
+        }
+        assert_eq!(heads, vec![Vector2i::new(0, 1), Vector2i::new(0, 2), Vector2i::new(1, 2)]);
     }
 
-    // Test move_player
+    // Test that snakes hold still during the start countdown and move after it
     #[test]
-    fn test_move_player() {
-        // Create small 4x4 game
-        let mut game = Game::new( Vector2i::new(4, 4));
-        let player_index0 = game.register_player(None);
-        {
-            let snake = game.players[player_index0].snake.as_mut().unwrap();
-            // Setup snake such that it can move forward 2 times
-            snake.set_body(vec![
-                Vector2i::new(0, 1), 
-                Vector2i::new(0, 0),   
-            ]);
-            assert!( snake.try_set_look_direction( Direction::PlusY ));
+    fn test_start_countdown() {
+        let mut game = Game::with_config(GameConfig { start_countdown : Some(3), ..GameConfig::default() });
+        let (control_tx, control_rx) = mpsc::channel();
+        let player_index = game.register_player(Some(Box::new(control_rx)));
+        let (event_tx, event_rx) = mpsc::channel();
+        game.register_global_event_channel(event_tx);
+        let head = game.snake_head(player_index).unwrap();
+        // Inputs are accepted during the countdown
+        control_tx.send(Direction::PlusY).unwrap();
+        game.players[player_index].read_inputs(game.config.input_buffer, game.config.max_inputs_per_tick);
+        for _ in 0..3 {
+            game.update();
+            assert_eq!(game.snake_head(player_index), Some(head));
+            assert_eq!(game.step_count(), 0);
         }
- 
-        // Also add one pizza
-        game.pizzas.push(Vector2i::new(0, 3));
-        // First move
-        game.move_player(player_index0);
-        // Doesn't eat pizza. Doesn't increase score
-        assert_eq!(game.pizzas.len(), 1);
-        assert_eq!(game.players[player_index0].score, 0);
-        // Second move
-        // Eats pizza and increase score
-        game.move_player(player_index0);
-        assert_eq!(game.pizzas.len(), 0);
-        assert_eq!(game.players[player_index0].score, 1);
-        // Also check final snake position
-        assert_eq!(*game.players[player_index0].snake.as_ref().unwrap().body(), vec![
-            Vector2i::new(0, 3), 
-            Vector2i::new(0, 2)
+        game.update();
+        assert_eq!(game.snake_head(player_index), Some(head + Vector2i::new(0, 1)));
+        assert_eq!(game.step_count(), 1);
+        let events : Vec<_> = event_rx.try_iter().collect();
+        let ticks : Vec<u32> = events.iter()
+            .filter_map(|e| match e {
+                events::GlobalEvent::CountdownTick(tick) => Some(tick.remaining),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ticks, vec![3, 2, 1, 0]);
+        // The board is shown on every countdown update
+        let updates = events.iter().filter(|e| matches!(e, events::GlobalEvent::Update(_))).count();
+        assert_eq!(updates, 4);
+        game.update();
+        assert_eq!(game.snake_head(player_index), Some(head + Vector2i::new(0, 2)));
+    }
+
+    // Test that the game ends as a draw when the snake fills the board
+    #[test]
+    fn test_board_full() {
+        let mut game = GameBuilder::new()
+            .field(3, 2)
+            .pizza(Vector2i::new(2, 1))
+            .snake(Vector2i::new(1, 1), Direction::PlusX, 2)
+            .build();
+        game.set_pizza_spawner(Box::new(FirstFreeSpawner));
+        game.players[0].snake = Some(Snake::with_body(vec![
+            Vector2i::new(1, 1),
+            Vector2i::new(0, 1),
+            Vector2i::new(0, 0),
+            Vector2i::new(1, 0),
+            Vector2i::new(2, 0),
+        ], Direction::PlusX));
+        // The last pizza spawns in the cell vacated by the tail
+        let outcome = game.step();
+        assert_eq!(outcome.pizzas_spawned, vec![Vector2i::new(2, 0)]);
+        assert_eq!(game.game_over_reason(), None);
+        // Eating it fills the board
+        assert_eq!(game.steer_player(0, Direction::MinusY), DirChange::Applied);
+        let outcome = game.step();
+        assert!(outcome.pizzas_spawned.is_empty());
+        assert_eq!(game.snake_length(0), 6);
+        assert_eq!(game.game_over_reason(), Some(events::GameOverReason::BoardFull));
+        // The game loop reports it
+        let (_shutdown_tx, shutdown_rx) = mpsc::channel();
+        let game_over = game.game_loop(shutdown_rx).unwrap();
+        assert_eq!(game_over.reason, events::GameOverReason::BoardFull);
+    }
+
+    // Test that regenerating into a buffer gives the same grid as generating a new one
+    #[test]
+    fn test_regenerate_grid_into() {
+        let mut game = GameBuilder::new()
+            .field(6, 5)
+            .wall(Vector2i::new(0, 4))
+            .pizza(Vector2i::new(5, 0))
+            .snake(Vector2i::new(2, 2), Direction::PlusX, 3)
+            .snake(Vector2i::new(4, 3), Direction::MinusY, 2)
+            .build();
+        game.add_hazard(Hazard { position : Vector2i::new(3, 0), warn_step : 0, active_step : 5, clear_step : 10 });
+        // Buffer of the field size with stale contents
+        let mut buffer = grid::new_grid(6, 5);
+        buffer.fill(GridCell::Wall);
+        game.regenerate_grid_into(&mut buffer);
+        assert_eq!(buffer, game.generate_grid());
+        // Buffer of other size is resized
+        let mut buffer = grid::new_grid(2, 9);
+        game.regenerate_grid_into(&mut buffer);
+        assert_eq!(buffer, game.generate_grid());
+        // Game keeps its grid up to date after the steps
+        game.step();
+        game.update();
+        assert_eq!(game.grid, game.generate_grid());
+    }
+
+    // Test that living snakes are listed with their bodies and dead ones are skipped
+    #[test]
+    fn test_living_snakes() {
+        let mut game = GameBuilder::new()
+            .field(8, 8)
+            .snake(Vector2i::new(2, 1), Direction::PlusX, 3)
+            .snake(Vector2i::new(5, 5), Direction::MinusY, 2)
+            .snake(Vector2i::new(6, 2), Direction::PlusY, 2)
+            .build();
+        game.players[1].kill();
+        let snakes : Vec<(PlayerIndex, Vec<Vector2i>)> = game.living_snakes()
+            .map(|(player_index, body)| (player_index, body.copied().collect()))
+            .collect();
+        assert_eq!(snakes, vec![
+            (0, vec![Vector2i::new(2, 1), Vector2i::new(1, 1), Vector2i::new(0, 1)]),
+            (2, vec![Vector2i::new(6, 2), Vector2i::new(6, 1)]),
         ]);
+    }
+
+    // Test that cells outside the play area act as walls
+    #[test]
+    fn test_play_mask() {
+        let mut game = GameBuilder::new()
+            .field(5, 5)
+            .pizza(Vector2i::new(0, 0))
+            .snake(Vector2i::new(3, 2), Direction::PlusX, 2)
+            .build();
+        // Rightmost column is outside the play area
+        let mask : Vec<bool> = (0..25).map(|i| i % 5 != 4).collect();
+        game.set_play_mask(Some(mask));
+        assert_eq!(game.cell_at(Vector2i::new(4, 0)), GridCell::Wall);
+        assert_eq!(game.generate_grid().at(4, 3), GridCell::Wall);
+        assert_eq!(game.num_empty_cells(), 25 - 5 - 2 - 1);
+        assert!(!game.is_move_safe(0, Direction::PlusX));
+        let outcome = game.step();
+        assert_eq!(outcome.actions[0], ActionStep::Die {
+            cause : events::DeathCause::Wall, position : Vector2i::new(4, 2), killer : None });
+        assert!(!game.players[0].alive());
 
+        // Players can't spawn outside the play area
+        let mut game = Game::new(Vector2i::new(10, 10));
+        game.set_play_mask(Some(grid::circle_mask(10, 10)));
+        assert!(game.try_register_player(None).is_some());
+        game.set_play_mask(Some(vec![false; 100]));
+        assert!(game.try_register_player(None).is_none());
     }
 
-    // Test sending game over event
+    // Test that only the capped number of directions is read between steps
     #[test]
-    fn test_game_over_event() {
-        // Create small 4x4 game
-        let mut game = Game::new( Vector2i::new(4, 4));
+    fn test_max_inputs_per_tick() {
+        let mut game = Game::with_config(GameConfig { max_inputs_per_tick : Some(3), ..GameConfig::default() });
+        let (control_tx, control_rx) = mpsc::channel();
+        let player_index = game.register_player(Some(Box::new(control_rx)));
+        let (event_tx, event_rx) = mpsc::channel();
+        game.register_global_event_channel(event_tx);
+        let look_direction = |game : &Game| game.players[player_index].snake.as_ref().unwrap().look_direction();
+
+        // Flood the channel. Only the first three directions are read, the rest are dropped
+        let flood = [Direction::PlusY, Direction::PlusX, Direction::MinusY];
+        for _ in 0..100 {
+            for direction in flood {
+                control_tx.send(direction).unwrap();
+            }
+        }
+        game.players[player_index].read_inputs(game.config.input_buffer, game.config.max_inputs_per_tick);
+        assert_eq!(game.players[player_index].tick_inputs, 3);
+        assert_eq!(look_direction(&game), Direction::MinusY);
+        // Dropped directions don't come back later
+        game.players[player_index].read_inputs(game.config.input_buffer, game.config.max_inputs_per_tick);
+        assert_eq!(look_direction(&game), Direction::MinusY);
+        game.step();
+        // The next step accepts directions again
+        control_tx.send(Direction::PlusX).unwrap();
+        game.players[player_index].read_inputs(game.config.input_buffer, game.config.max_inputs_per_tick);
+        assert_eq!(look_direction(&game), Direction::PlusX);
+
+        // Flooding on several consecutive steps is reported once
+        for _ in 0..INPUT_FLOOD_STEPS + 2 {
+            for _ in 0..10 {
+                control_tx.send(Direction::PlusX).unwrap();
+            }
+            game.players[player_index].read_inputs(game.config.input_buffer, game.config.max_inputs_per_tick);
+            game.step();
+        }
+        let floods : Vec<_> = event_rx.try_iter()
+            .filter_map(|e| match e {
+                events::GlobalEvent::InputFlood(flood) => Some(flood),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(floods, vec![events::InputFlood { player_index, steps : INPUT_FLOOD_STEPS }]);
+    }
+
+    // Test that thumbnails are sent on schedule and show the snakes
+    #[test]
+    fn test_thumbnail() {
+        let config = GameConfig {
+            thumbnail : Some(ThumbnailConfig { interval : 2, max_width : 5, max_height : 5 }),
+            ..GameConfig::default()
+        };
+        let mut game = GameBuilder::with_config(config)
+            .field(20, 10)
+            .pizza(Vector2i::new(0, 9))
+            .snake(Vector2i::new(15, 2), Direction::PlusY, 2)
+            .build();
+        let (event_tx, event_rx) = mpsc::channel();
+        game.register_global_event_channel(event_tx);
+        for _ in 0..4 {
+            game.update();
+        }
+        let thumbnails : Vec<_> = event_rx.try_iter()
+            .filter_map(|e| match e {
+                events::GlobalEvent::Thumbnail(thumbnail) => Some(thumbnail),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(thumbnails.len(), 2);
+        let thumbnail = &thumbnails[1];
+        assert_eq!((thumbnail.width, thumbnail.height), (5, 5));
+        assert_eq!(thumbnail.cells.len(), 25);
+        // Blocks are 4x2 cells. Snake at (15, 6) and (15, 5) covers the blocks (3, 3) and (3, 2)
+        assert_eq!(thumbnail.cells[3 * 5 + 3], 1);
+        assert_eq!(thumbnail.cells[2 * 5 + 3], 1);
+        assert_eq!(thumbnail.cells.iter().filter(|cell| **cell == 1).count(), 2);
+        assert_eq!(thumbnail.cells[4 * 5], 2);
+    }
+
+    // Test that pizzas spawn away from the heads when possible
+    #[test]
+    fn test_pizza_spawn_distance() {
+        let config = GameConfig {
+            spawn_policy : SpawnPolicy { snake_distance : 2, pizza_distance : 6 },
+            ..GameConfig::default()
+        };
+        for seed in 0..20 {
+            let mut game = GameBuilder::with_config(config.clone())
+                .field(7, 7)
+                .seed(seed)
+                .snake(Vector2i::new(2, 3), Direction::PlusX, 2)
+                .build();
+            let outcome = game.step();
+            // Head is at (3, 3), only the corners are far enough
+            let pizza = outcome.pizzas_spawned[0];
+            assert!((pizza.x == 0 || pizza.x == 6) && (pizza.y == 0 || pizza.y == 6), "Pizza at {:?}", pizza);
+        }
+        // No cell is far enough, any free cell is used
+        let mut game = GameBuilder::with_config(GameConfig {
+            spawn_policy : SpawnPolicy { snake_distance : 2, pizza_distance : 100 },
+            ..GameConfig::default()
+        })
+            .field(7, 7)
+            .snake(Vector2i::new(2, 3), Direction::PlusX, 2)
+            .build();
+        assert_eq!(game.step().pizzas_spawned.len(), 1);
+    }
+
+    // Test that respawn avoids the spawn points taken by other snakes
+    #[test]
+    fn test_respawn_distance() {
+        let mut game = Game::new(Vector2i::new(10, 10));
         let player_index0 = game.register_player(None);
-        game.players[player_index0].score = 10;
-        
-        // Create another dead player
         let player_index1 = game.register_player(None);
-        game.players[player_index1].kill();
- 
-        // Create a channel for global event
-        let channel = mpsc::channel::<events::GlobalEvent>();
-        game.register_global_event_channel(channel.0);
-        // Trigger event
-        game.send_game_over_event();
-        // Receive event
-        let event = channel.1.recv().unwrap();
-        // Make sure it's a GameOver event
-        match event {
-            events::GlobalEvent::GameOver(data) => {
-                // Assert there are 2 players
-                assert_eq!(data.players_summary.len(), 2);
-                // First is alive and has score
-                assert_eq!(data.players_summary[0].alive, true);
-                assert_eq!(data.players_summary[0].score, 10);
-                // Second is dead
-                assert_eq!(data.players_summary[1].alive, false);
-                assert_eq!(data.players_summary[1].score, 0);
-                
+        let own_spawn = game.make_spawn_snake(player_index0).head();
+        game.players[player_index0].kill();
+        // Second snake parks on the spawn point of the first one
+        game.players[player_index1].snake = Some(Snake::new(own_spawn + Vector2i::new(0, 1), Direction::PlusX, 3));
+        game.respawn_player(player_index0);
+        let snake = game.players[player_index0].snake.as_ref().unwrap();
+        assert_ne!(snake.head(), own_spawn);
+        for pos in snake.body() {
+            for other in game.players[player_index1].snake.as_ref().unwrap().body() {
+                let offset = *pos - *other;
+                assert!(offset.x.abs() + offset.y.abs() >= 2);
+            }
+        }
+
+        // Without free spawn points the own one is used
+        let mut game = Game::with_config(GameConfig {
+            field_size : Vector2i::new(3, 3),
+            spawn_policy : SpawnPolicy { snake_distance : 0, pizza_distance : 0 },
+            ..GameConfig::default()
+        });
+        let player_index0 = game.register_player(None);
+        game.register_player(None);
+        game.config.spawn_policy.snake_distance = 10;
+        game.players[player_index0].kill();
+        game.respawn_player(player_index0);
+        assert_eq!(game.players[player_index0].snake.as_ref().unwrap().head(), game.make_spawn_snake(player_index0).head());
+    }
+
+    // Test that safe moves exclude deadly directions and the reverse
+    #[test]
+    fn test_safe_moves() {
+        // Boxed on three sides
+        let game = GameBuilder::new()
+            .field(5, 5)
+            .wall(Vector2i::new(2, 2))
+            .wall(Vector2i::new(1, 1))
+            .snake(Vector2i::new(1, 2), Direction::PlusX, 2)
+            .build();
+        assert_eq!(game.safe_moves(0), vec![Direction::PlusY]);
+        let grid = game.generate_grid();
+        for direction in Direction::all() {
+            assert_eq!(game.is_move_safe_in(&grid, 0, direction), game.is_move_safe(0, direction));
+        }
+
+        // Open space
+        let game = GameBuilder::new()
+            .field(5, 5)
+            .snake(Vector2i::new(1, 2), Direction::PlusX, 2)
+            .build();
+        let moves = game.safe_moves(0);
+        assert_eq!(moves.len(), 3);
+        assert!(!moves.contains(&Direction::MinusX));
+    }
+
+    // Test that the body of the dead snake kills other snakes and clears after the lifetime
+    #[test]
+    fn test_remains() {
+        let mut game = GameBuilder::with_config(GameConfig { remains_lifetime : Some(2), ..GameConfig::default() })
+            .field(6, 6)
+            .seed(3)
+            .wall(Vector2i::new(2, 1))
+            .snake(Vector2i::new(1, 1), Direction::PlusX, 2)
+            .snake(Vector2i::new(1, 3), Direction::MinusY, 2)
+            .build();
+        // First snake hits the wall and leaves its body behind
+        game.step();
+        assert!(!game.players[0].alive());
+        assert_eq!(game.cell_at(Vector2i::new(1, 1)), GridCell::Remains);
+        assert_eq!(game.generate_grid().at(0, 1), GridCell::Remains);
+        assert_eq!(game.remains().len(), 2);
+
+        // Second snake dies on the remains
+        assert_eq!(game.predict_next_action(1), ActionStep::Die {
+            cause : events::DeathCause::Wall, position : Vector2i::new(1, 1), killer : None });
+        game.step();
+        assert!(!game.players[1].alive());
+        assert_eq!(game.cell_at(Vector2i::new(1, 2)), GridCell::Remains);
+
+        // Remains clear after the lifetime
+        game.step();
+        assert_eq!(game.cell_at(Vector2i::new(0, 1)), GridCell::Empty);
+        assert_eq!(game.cell_at(Vector2i::new(1, 2)), GridCell::Remains);
+        game.step();
+        assert!(game.remains().is_empty());
+        assert_eq!(game.cell_at(Vector2i::new(1, 2)), GridCell::Empty);
+    }
+
+    // Test that the tick hook runs at the end of every step
+    #[test]
+    fn test_tick_hook() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicU32, Ordering};
+        let mut game = GameBuilder::new()
+            .field(8, 8)
+            .snake(Vector2i::new(2, 3), Direction::PlusX, 2)
+            .build();
+        let calls = Arc::new(AtomicU32::new(0));
+        let hook_calls = calls.clone();
+        let pizza_pos = Vector2i::new(7, 7);
+        game.set_tick_hook(Box::new(move |game : &mut Game| {
+            hook_calls.fetch_add(1, Ordering::Relaxed);
+            if game.cell_at(pizza_pos) == GridCell::Empty {
+                game.pizzas.push(Pizza::normal(pizza_pos));
             }
-            _ => assert!(false)
+        }));
+        for _ in 0..3 {
+            game.step();
+            assert!(matches!(game.cell_at(pizza_pos), GridCell::Pizza(_)));
+        }
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+    }
+
+    // Test that the longer snake wins the contested cell and the shorter one dies
+    #[test]
+    fn test_contest_longer_wins() {
+        let config = GameConfig {
+            contest_rule : ContestRule::LongerWins { tie_dies : false },
+            ..GameConfig::default()
         };
+        let mut game = GameBuilder::with_config(config)
+            .field(7, 7)
+            .snake(Vector2i::new(1, 3), Direction::PlusX, 2)
+            .snake(Vector2i::new(3, 3), Direction::MinusX, 3)
+            .build();
+        let contested = Vector2i::new(2, 3);
+        assert_eq!(game.predict_next_action(0), ActionStep::Die {
+            cause : events::DeathCause::OtherSnake, position : contested, killer : Some(1) });
+        assert_eq!(game.predict_next_action(1), ActionStep::Move);
+        game.step();
+        assert!(!game.players[0].alive());
+        assert_eq!(game.players[1].snake.as_ref().unwrap().head(), contested);
     }
 
-    // Test the update event
+    // Test that snakes of equal length hold or both die depending on the rule
     #[test]
-    fn test_update_event() {
-        // Create small 4x4 game
-        let mut game = Game::new( Vector2i::new(4, 4));
-        let _player_index0 = game.register_player(None);
+    fn test_contest_tie() {
+        for tie_dies in [false, true] {
+            let config = GameConfig {
+                contest_rule : ContestRule::LongerWins { tie_dies },
+                ..GameConfig::default()
+            };
+            let mut game = GameBuilder::with_config(config)
+                .field(7, 7)
+                .snake(Vector2i::new(1, 3), Direction::PlusX, 2)
+                .snake(Vector2i::new(3, 3), Direction::MinusX, 2)
+                .build();
+            game.step();
+            assert_eq!(game.players[0].alive(), !tie_dies);
+            assert_eq!(game.players[1].alive(), !tie_dies);
+            if !tie_dies {
+                assert_eq!(game.players[0].snake.as_ref().unwrap().head(), Vector2i::new(1, 3));
+                assert_eq!(game.players[1].snake.as_ref().unwrap().head(), Vector2i::new(3, 3));
+            }
+        }
+    }
 
-        // Create a channel for global event
-        let channel = mpsc::channel::<events::GlobalEvent>();
-        game.register_global_event_channel(channel.0);
-        // Make sure grid is up to date
-        game.grid = game.generate_grid();
-        // Trigger event
-        game.send_update_event();
-        // Receive event
-        let event = channel.1.recv().unwrap();
-        // Make sure it's a GameOver event
-        match event {
-            events::GlobalEvent::Update(data) => {
-                // Assert there is 1 player
-                assert_eq!(data.players_summary.len(), 1);
-                // Assert there is grid of correct size
-                assert_eq!(data.grid.dim(), (4, 4));
+    // Test the field bounds and wrapping at every edge
+    #[test]
+    fn test_in_bounds_and_wrap() {
+        let game = Game::new(Vector2i::new(4, 3));
+        assert!(game.in_bounds(Vector2i::new(0, 0)));
+        assert!(game.in_bounds(Vector2i::new(3, 2)));
+        assert!(!game.in_bounds(Vector2i::new(-1, 0)));
+        assert!(!game.in_bounds(Vector2i::new(4, 0)));
+        assert!(!game.in_bounds(Vector2i::new(0, -1)));
+        assert!(!game.in_bounds(Vector2i::new(0, 3)));
+        // No wrapping by default
+        assert_eq!(game.wrap_pos(Vector2i::new(-1, 0)), Vector2i::new(-1, 0));
+
+        let game = Game::with_config(GameConfig {
+            field_size : Vector2i::new(4, 3),
+            wrap_around : true,
+            ..GameConfig::default()
+        });
+        assert_eq!(game.wrap_pos(Vector2i::new(-1, 1)), Vector2i::new(3, 1));
+        assert_eq!(game.wrap_pos(Vector2i::new(4, 1)), Vector2i::new(0, 1));
+        assert_eq!(game.wrap_pos(Vector2i::new(2, -1)), Vector2i::new(2, 2));
+        assert_eq!(game.wrap_pos(Vector2i::new(2, 3)), Vector2i::new(2, 0));
+        assert_eq!(game.wrap_pos(Vector2i::new(1, 1)), Vector2i::new(1, 1));
+    }
+
+    // Test that the facing override turns the spawned snake
+    #[test]
+    fn test_register_player_facing() {
+        let mut game = Game::with_config(GameConfig {
+            field_size : Vector2i::new(10, 10),
+            initial_length : 3,
+            ..GameConfig::default()
+        });
+        let default_snake = game.make_spawn_snake(0);
+        assert_eq!(default_snake.look_direction(), Direction::PlusX);
+        let player_index = game.register_player_facing(None, Direction::PlusY);
+        let snake = game.players[player_index].snake.as_ref().unwrap();
+        assert_eq!(snake.look_direction(), Direction::PlusY);
+        assert_eq!(snake.head(), default_snake.head());
+        let head = snake.head();
+        let body : Vec<Vector2i> = snake.body().copied().collect();
+        assert_eq!(body, vec![head, head - Vector2i::new(0, 1), head - Vector2i::new(0, 2)]);
+
+        // Body that doesn't fit is shortened
+        let mut game = Game::with_config(GameConfig {
+            field_size : Vector2i::new(10, 10),
+            initial_length : 8,
+            ..GameConfig::default()
+        });
+        let player_index = game.register_player_facing(None, Direction::MinusY);
+        let snake = game.players[player_index].snake.as_ref().unwrap();
+        assert_eq!(snake.look_direction(), Direction::MinusY);
+        assert!(snake.body().all(|pos| game.in_bounds(*pos)));
+    }
+
+    // Test that eating the last pizza of the limited supply clears the board
+    #[test]
+    fn test_board_cleared() {
+        let mut game = GameBuilder::with_config(GameConfig { pizza_supply : Some(1), ..GameConfig::default() })
+            .field(8, 8)
+            .snake(Vector2i::new(1, 1), Direction::PlusX, 2)
+            .build();
+        game.set_pizza_spawner(Box::new(FirstFreeSpawner));
+        let (event_tx, event_rx) = mpsc::channel();
+        game.register_global_event_channel(event_tx);
+        let board_cleared = |event_rx : &mpsc::Receiver<events::GlobalEvent>| event_rx.try_iter()
+            .filter_map(|event| match event {
+                events::GlobalEvent::BoardCleared(cleared) => Some(cleared.step),
+                _ => None,
+            })
+            .collect::<Vec<u64>>();
+        // The only pizza spawns at (0, 0)
+        assert_eq!(game.step().pizzas_spawned, vec![Vector2i::new(0, 0)]);
+        assert!(board_cleared(&event_rx).is_empty());
+        // Go eat it
+        game.players[0].snake.as_mut().unwrap().try_set_look_direction(Direction::MinusY);
+        game.step();
+        game.players[0].snake.as_mut().unwrap().try_set_look_direction(Direction::MinusX);
+        game.step();
+        assert!(board_cleared(&event_rx).is_empty());
+        let outcome = game.step();
+        assert_eq!(outcome.pizzas_eaten.len(), 1);
+        assert!(outcome.pizzas_spawned.is_empty());
+        assert_eq!(board_cleared(&event_rx), vec![4]);
+        // No more pizzas and no more events
+        game.players[0].snake.as_mut().unwrap().try_set_look_direction(Direction::PlusY);
+        assert!(game.step().pizzas_spawned.is_empty());
+        assert!(board_cleared(&event_rx).is_empty());
+    }
+
+    // Test that players get distinct RGB colors that can be configured
+    #[test]
+    fn test_player_rgb() {
+        let mut game = Game::new(Vector2i::new(20, 20));
+        for _ in 0..MAX_PLAYERS {
+            game.register_player(None);
+        }
+        let mut summary = Vec::new();
+        game.players_summary_into(&mut summary);
+        for (i, a) in summary.iter().enumerate() {
+            assert_eq!(a.rgb, a.color.rgb());
+            for b in &summary[i + 1..] {
+                assert_ne!(a.rgb, b.rgb);
+            }
+        }
+        game.set_player_rgb(1, (12, 34, 56));
+        game.players_summary_into(&mut summary);
+        assert_eq!(summary[1].rgb, (12, 34, 56));
+        assert_eq!(summary[0].rgb, PlayerColor::for_index(0).rgb());
+    }
+
+    // Test that without growth eating only scores
+    #[test]
+    fn test_grow_on_eat_off() {
+        let mut game = GameBuilder::with_config(GameConfig { grow_on_eat : false, pizza_supply : Some(0), ..GameConfig::default() })
+            .field(8, 8)
+            .pizza(Vector2i::new(3, 1))
+            .pizza(Vector2i::new(4, 1))
+            .snake(Vector2i::new(2, 1), Direction::PlusX, 3)
+            .build();
+        for _ in 0..4 {
+            game.step();
+            assert_eq!(game.snake_length(0), 3);
+        }
+        assert_eq!(game.players[0].score, 2);
+    }
+
+    // Test that players with equal score are ranked by length, then survival, then index
+    #[test]
+    fn test_ranking_tie_break() {
+        let mut game = GameBuilder::new()
+            .field(10, 10)
+            .snake(Vector2i::new(1, 1), Direction::PlusX, 2)
+            .snake(Vector2i::new(1, 3), Direction::PlusX, 2)
+            .snake(Vector2i::new(3, 5), Direction::PlusX, 4)
+            .snake(Vector2i::new(1, 7), Direction::PlusX, 2)
+            .snake(Vector2i::new(1, 8), Direction::PlusX, 2)
+            .build();
+        // Equal scores and lengths keep the index order
+        assert_eq!(game.ranking(), vec![2, 0, 1, 3, 4]);
+        // Player 0 dies first, player 1 later
+        game.kill_player(0);
+        game.step();
+        game.kill_player(1);
+        // Longer snake is ahead even if dead
+        game.kill_player(2);
+        assert_eq!(game.ranking(), vec![2, 3, 4, 1, 0]);
+        // Score still goes first
+        game.set_score(0, 1);
+        assert_eq!(game.ranking(), vec![0, 2, 3, 4, 1]);
+    }
+
+    // Test the look direction of living, dead and unknown players
+    #[test]
+    fn test_player_look_direction() {
+        let mut game = GameBuilder::new()
+            .field(8, 8)
+            .snake(Vector2i::new(2, 2), Direction::PlusX, 2)
+            .snake(Vector2i::new(2, 5), Direction::PlusX, 2)
+            .build();
+        assert_eq!(game.player_look_direction(0), Some(Direction::PlusX));
+        assert_eq!(game.steer_player(0, Direction::PlusX), DirChange::Unchanged);
+        assert_eq!(game.steer_player(0, Direction::MinusY), DirChange::Applied);
+        assert_eq!(game.player_look_direction(0), Some(Direction::MinusY));
+        game.kill_player(1);
+        assert_eq!(game.steer_player(1, Direction::PlusY), DirChange::Rejected);
+        assert_eq!(game.player_look_direction(1), None);
+        assert_eq!(game.player_look_direction(2), None);
+    }
+
+    // Test that the game ends when only one player is left
+    #[test]
+    fn test_end_last_standing() {
+        let mut game = GameBuilder::with_config(GameConfig {
+            end_conditions : vec![GameEndCondition::LastStanding],
+            ..GameConfig::default()
+        })
+            .field(8, 8)
+            .snake(Vector2i::new(2, 2), Direction::PlusX, 2)
+            .snake(Vector2i::new(7, 5), Direction::PlusX, 2)
+            .build();
+        assert_eq!(game.game_over_reason(), None);
+        // Second snake hits the wall
+        game.step();
+        assert_eq!(game.game_over_reason(), Some(events::GameOverReason::LastStanding));
+    }
+
+    // Test that the game ends on the step the target score is reached
+    #[test]
+    fn test_end_score_target() {
+        let mut game = GameBuilder::with_config(GameConfig {
+            end_conditions : vec![GameEndCondition::StepLimit(100), GameEndCondition::ScoreTarget(2)],
+            ..GameConfig::default()
+        })
+            .field(8, 8)
+            .pizza(Vector2i::new(3, 2))
+            .pizza(Vector2i::new(5, 2))
+            .snake(Vector2i::new(2, 2), Direction::PlusX, 2)
+            .build();
+        game.set_pizza_spawner(Box::new(FirstFreeSpawner));
+        game.step();
+        assert_eq!(game.players[0].score, 1);
+        assert_eq!(game.game_over_reason(), None);
+        game.step();
+        assert_eq!(game.game_over_reason(), None);
+        game.step();
+        assert_eq!(game.players[0].score, 2);
+        assert_eq!(game.game_over_reason(), Some(events::GameOverReason::ScoreTarget));
+    }
+
+    // Test that snake on ice ignores turns for the slide duration and then obeys again
+    #[test]
+    fn test_ice() {
+        let mut game = GameBuilder::with_config(GameConfig { ice_slide_steps : 2, ..GameConfig::default() })
+            .field(10, 10)
+            .snake(Vector2i::new(2, 2), Direction::PlusX, 2)
+            .build();
+        game.add_ice(Vector2i::new(3, 2));
+        assert_eq!(game.cell_at(Vector2i::new(3, 2)), GridCell::Ice);
+        let (control_tx, control_rx) = mpsc::channel();
+        game.set_player_control(0, Some(Box::new(control_rx)));
+        let (event_tx, event_rx) = mpsc::channel();
+        game.register_global_event_channel(event_tx);
+        let turn = |game : &mut Game| {
+            control_tx.send(Direction::PlusY).unwrap();
+            game.players[0].read_inputs(game.config.input_buffer, game.config.max_inputs_per_tick);
+            game.step();
+            game.snake_head(0).unwrap()
+        };
+        let ice_events = |event_rx : &mpsc::Receiver<events::GlobalEvent>| event_rx.try_iter()
+            .filter(|event| matches!(event, events::GlobalEvent::IceEntered(_) | events::GlobalEvent::IceLeft(_)))
+            .collect::<Vec<_>>();
+
+        // Enter the ice
+        game.step();
+        assert_eq!(ice_events(&event_rx), vec![events::GlobalEvent::IceEntered(events::IceEntered {
+            player_index : 0, position : Vector2i::new(3, 2), slide_steps : 2 })]);
+        assert_eq!(game.steer_player(0, Direction::PlusY), DirChange::Rejected);
+        // Turns are ignored for two steps
+        assert_eq!(turn(&mut game), Vector2i::new(4, 2));
+        assert!(ice_events(&event_rx).is_empty());
+        assert_eq!(turn(&mut game), Vector2i::new(5, 2));
+        assert_eq!(ice_events(&event_rx), vec![events::GlobalEvent::IceLeft(events::IceLeft { player_index : 0 })]);
+        // Then the snake obeys again
+        assert_eq!(turn(&mut game), Vector2i::new(5, 3));
+    }
+
+    // Test that the public prediction matches the step outcome
+    #[test]
+    fn test_predicted_action() {
+        let mut game = GameBuilder::new()
+            .field(6, 6)
+            .wall(Vector2i::new(3, 1))
+            .snake(Vector2i::new(2, 1), Direction::PlusX, 2)
+            .snake(Vector2i::new(2, 3), Direction::PlusX, 2)
+            .snake(Vector2i::new(3, 4), Direction::MinusY, 2)
+            .snake(Vector2i::new(1, 5), Direction::PlusX, 2)
+            .build();
+        game.kill_player(3);
+        let predicted : Vec<Option<ActionStep>> = (0..5).map(|player_index| game.predicted_action(player_index)).collect();
+        assert_eq!(predicted, vec![
+            Some(ActionStep::Die { cause : events::DeathCause::Wall, position : Vector2i::new(3, 1), killer : None }),
+            Some(ActionStep::Hold),
+            Some(ActionStep::Hold),
+            None,
+            None,
+        ]);
+        let outcome = game.step();
+        for (player_index, action) in predicted.iter().enumerate().take(3) {
+            assert_eq!(outcome.actions[player_index], action.unwrap());
+        }
+    }
+
+    // Test that without self collisions the snake passes through itself
+    #[test]
+    fn test_self_collision_off() {
+        let body = vec![
+            Vector2i::new(1, 2),
+            Vector2i::new(2, 2),
+            Vector2i::new(2, 1),
+            Vector2i::new(1, 1),
+            Vector2i::new(0, 1),
+            Vector2i::new(0, 0),
+        ];
+        let mut game = Game::with_config(GameConfig {
+            field_size : Vector2i::new(4, 4),
+            spawn_policy : SpawnPolicy { snake_distance : 0, pizza_distance : 0 },
+            ..GameConfig::default()
+        });
+        game.register_player(None);
+        game.players[0].snake = Some(Snake::with_body(body.clone(), Direction::MinusY));
+        assert!(matches!(game.predict_next_action(0), ActionStep::Die { cause : events::DeathCause::OwnSnake, .. }));
+
+        game.config.self_collision = false;
+        assert_eq!(game.predict_next_action(0), ActionStep::Move);
+        game.step();
+        assert!(game.players[0].alive());
+        // Head is drawn over the body it passes
+        let head = Vector2i::new(1, 1);
+        assert_eq!(game.snake_head(0), Some(head));
+        assert_eq!(game.cell_at(head), GridCell::Snake(SnakeRec{ player_index : 0, body_part : SnakeBodyPart::Head }));
+        assert_eq!(game.generate_grid().at(1, 1), game.cell_at(head));
+        // Overlapping cell is counted once
+        assert_eq!(game.players[0].snake.as_ref().unwrap().num_cells(), 5);
+        game.step();
+        assert!(game.players[0].alive());
+        assert_eq!(game.snake_head(0), Some(Vector2i::new(1, 0)));
+    }
+
+    // Test that fast-forwarding stops at game over and reports it
+    #[test]
+    fn test_step_n() {
+        let mut game = GameBuilder::new()
+            .field(10, 10)
+            .snake(Vector2i::new(2, 2), Direction::PlusX, 2)
+            .build();
+        assert_eq!(game.step_n(3), None);
+        assert_eq!(game.step_count(), 3);
+        // Snake hits the wall on the step 8
+        let (event_tx, event_rx) = mpsc::channel();
+        game.register_global_event_channel(event_tx);
+        let game_over = game.step_n(100).unwrap();
+        assert_eq!(game_over.reason, events::GameOverReason::AllDead);
+        assert_eq!(game_over.total_steps, 8);
+        assert_eq!(game.step_count(), 8);
+        // Stepping the finished game reports nothing more
+        assert_eq!(game.step_n(10), None);
+        assert_eq!(game.step_n(10), None);
+        assert_eq!(game.step_count(), 8);
+        let game_overs : Vec<_> = event_rx.try_iter()
+            .filter(|event| matches!(event, events::GlobalEvent::GameOver(_)))
+            .collect();
+        assert_eq!(game_overs, vec![events::GlobalEvent::GameOver(game_over)]);
+    }
+
+    // Test that a poison pizza shrinks the snake down to length 2
+    #[test]
+    fn test_poison_pizza() {
+        let mut game = GameBuilder::new()
+            .field(10, 10)
+            .pizza_of(Vector2i::new(4, 1), PizzaKind::Poison)
+            .pizza_of(Vector2i::new(6, 1), PizzaKind::Poison)
+            .snake(Vector2i::new(3, 1), Direction::PlusX, 3)
+            .build();
+        game.step();
+        assert_eq!(game.snake_length(0), 2);
+        assert_eq!(game.players[0].score, 0);
+        game.step();
+        game.step();
+        assert_eq!(game.snake_length(0), 2);
+        assert_eq!(game.players[0].snake.as_ref().unwrap().head(), Vector2i::new(6, 1));
+    }
+
+    // Test that long snakes block each other with the collisions looked up in the grid
+    #[test]
+    fn test_predict_long_snakes() {
+        let length = 2_000;
+        let mut game = GameBuilder::new()
+            .field(length + 2, 6)
+            .snake(Vector2i::new(length, 1), Direction::PlusX, length as u32)
+            .snake(Vector2i::new(length, 2), Direction::PlusX, length as u32)
+            .build();
+        let grid = game.generate_grid();
+        // Tails vacate their cells
+        assert_eq!(game.snake_blocking_at(Some(&grid), Vector2i::new(2, 2), 0), Some(1));
+        assert_eq!(game.snake_blocking_at(Some(&grid), Vector2i::new(1, 2), 0), None);
+        assert_eq!(game.snake_blocking_at(Some(&grid), Vector2i::new(1, 1), 0), None);
+
+        game.players[0].snake.as_mut().unwrap().set_look_direction(Direction::PlusY);
+        game.players[1].snake.as_mut().unwrap().set_look_direction(Direction::MinusY);
+        assert_eq!(game.predict_next_action(0), ActionStep::Die {
+            cause : events::DeathCause::OtherSnake, position : Vector2i::new(length, 2), killer : Some(1) });
+        assert_eq!(game.predict_next_action(1), ActionStep::Die {
+            cause : events::DeathCause::OtherSnake, position : Vector2i::new(length, 1), killer : Some(0) });
+
+        game.players[0].snake.as_mut().unwrap().set_look_direction(Direction::PlusX);
+        game.players[1].snake.as_mut().unwrap().set_look_direction(Direction::PlusY);
+        let outcome = game.step();
+        assert_eq!(outcome.actions, vec![ActionStep::Move, ActionStep::Move]);
+    }
+
+    // Test that the steps of long snakes stay fast. Timing depends on the machine,
+    // so it only runs on request
+    #[test]
+    #[ignore = "timing check, run with --ignored"]
+    fn test_predict_long_snakes_timing() {
+        let length = 2_000;
+        let mut builder = GameBuilder::with_config(GameConfig { pizza_supply : Some(0), ..GameConfig::default() })
+            .field(2 * length + 2, 8);
+        for y in 0..4 {
+            builder = builder.snake(Vector2i::new(length, 2 * y), Direction::PlusX, length as u32);
+        }
+        let mut game = builder.build();
+        let start = std::time::Instant::now();
+        for _ in 0..1_000 {
+            game.step();
+        }
+        assert_eq!(game.alive_count(), 4);
+        assert!(start.elapsed() < std::time::Duration::from_secs(2), "Took {:?}", start.elapsed());
+    }
+
+    // Test that the collisions looked up in the grid match scanning every body,
+    // on the scenarios of test_predict_next_action
+    #[test]
+    fn test_snake_blocking_matches_scan() {
+        // The owner of the first blocking body part, the way the bodies used to be scanned
+        fn scan(game : &Game, pos : Vector2i, player_index : PlayerIndex) -> Option<PlayerIndex> {
+            for (any_player_index, player) in game.players.iter().enumerate() {
+                if !player.alive() { continue; }
+                let any_snake = player.snake.as_ref().unwrap();
+                let blocking_len = if any_snake.will_grow() { any_snake.length() } else { any_snake.length() - 1 };
+                if any_player_index == player_index && !game.config.self_collision {
+                    continue;
+                }
+                if any_snake.body().take(blocking_len).any(|body_part| *body_part == pos) {
+                    return Some(any_player_index);
+                }
+            }
+            None
+        }
+        fn check(game : &Game, player_index : PlayerIndex, expected : ActionStep) {
+            let snake = game.players[player_index].snake.as_ref().unwrap();
+            let new_head = snake.head() + Vector2i::from_direction(snake.look_direction());
+            let grid = game.generate_grid();
+            if game.in_bounds(new_head) {
+                assert_eq!(game.snake_blocking_at(Some(&grid), new_head, player_index), scan(game, new_head, player_index));
+                assert_eq!(game.snake_blocking_at(None, new_head, player_index), scan(game, new_head, player_index));
             }
-            _ => assert!(false)
+            assert_eq!(game.predict_next_action(player_index), expected);
+        }
+        let mut game = Game::with_config(GameConfig {
+            field_size : Vector2i::new(4, 4),
+            spawn_policy : SpawnPolicy { snake_distance : 0, pizza_distance : 0 },
+            ..GameConfig::default()
+        });
+        let player_index0 = game.register_player(None);
+        let set_snake = |game : &mut Game, player_index : PlayerIndex, body : Vec<Vector2i>, direction : Direction| {
+            let snake = game.players[player_index].snake.as_mut().unwrap();
+            snake.set_body(body);
+            assert!(snake.try_set_look_direction(direction));
         };
+        let v = Vector2i::new;
+
+        set_snake(&mut game, player_index0, vec![v(0, 3), v(0, 2)], Direction::PlusY);
+        check(&game, player_index0, ActionStep::Die {
+            cause : events::DeathCause::Wall, position : v(0, 4), killer : None });
+        // Own tail vacates, unless the snake grows
+        set_snake(&mut game, player_index0, vec![v(1, 2), v(2, 2), v(2, 1), v(1, 1)], Direction::MinusY);
+        check(&game, player_index0, ActionStep::Move);
+        game.players[player_index0].snake.as_mut().unwrap().eat(1);
+        check(&game, player_index0, ActionStep::Die {
+            cause : events::DeathCause::OwnSnake, position : v(1, 1), killer : None });
+        set_snake(&mut game, player_index0, vec![v(1, 2), v(2, 2), v(2, 1), v(1, 1), v(0, 1)], Direction::MinusY);
+        check(&game, player_index0, ActionStep::Die {
+            cause : events::DeathCause::OwnSnake, position : v(1, 1), killer : None });
+
+        let player_index1 = game.register_player(None);
+        set_snake(&mut game, player_index1, vec![v(3, 2), v(3, 1), v(3, 0)], Direction::PlusY);
+        let player_index2 = game.register_player(None);
+        game.players[player_index2].snake = None;
+        assert_eq!(game.predict_next_action(player_index2), ActionStep::Hold);
+
+        set_snake(&mut game, player_index0, vec![v(2, 2), v(1, 2)], Direction::PlusX);
+        check(&game, player_index0, ActionStep::Die {
+            cause : events::DeathCause::OtherSnake, position : v(3, 2), killer : Some(player_index1) });
+        set_snake(&mut game, player_index0, vec![v(2, 3), v(1, 3)], Direction::PlusX);
+        check(&game, player_index0, ActionStep::Hold);
+        set_snake(&mut game, player_index0, vec![v(2, 0), v(1, 0)], Direction::PlusX);
+        check(&game, player_index0, ActionStep::Move);
+        // Other snake that held on the tail is found under it
+        set_snake(&mut game, player_index1, vec![v(2, 1), v(2, 0), v(3, 0), v(3, 1)], Direction::PlusX);
+        game.players[player_index0].snake.as_mut().unwrap().set_body(vec![v(3, 1), v(3, 2)]);
+        check(&game, player_index1, ActionStep::Die {
+            cause : events::DeathCause::OtherSnake, position : v(3, 1), killer : Some(player_index0) });
+    }
+
+    // Test that every cell matches the generated grid when the board objects overlap
+    #[test]
+    fn test_cell_at_matches_grid() {
+        let mut game = GameBuilder::with_config(GameConfig { remains_lifetime : Some(10), ..GameConfig::default() })
+            .field(8, 6)
+            .wall(Vector2i::new(7, 1))
+            .wall(Vector2i::new(0, 4))
+            .pizza(Vector2i::new(2, 4))
+            .snake(Vector2i::new(6, 1), Direction::PlusX, 3)
+            .snake(Vector2i::new(3, 3), Direction::PlusX, 3)
+            .build();
+        // The first snake dies into the wall and leaves remains
+        game.step();
+        assert!(!game.players[0].alive());
+        // Walls under the remains, ice and hazards under everything
+        game.add_wall(Vector2i::new(5, 1));
+        for pos in [Vector2i::new(4, 1), Vector2i::new(1, 4), Vector2i::new(2, 4), Vector2i::new(0, 4), Vector2i::new(3, 3)] {
+            game.add_ice(pos);
+        }
+        for (pos, warn_step) in [(Vector2i::new(1, 4), 0), (Vector2i::new(0, 0), 0), (Vector2i::new(6, 1), 0), (Vector2i::new(2, 0), 5)] {
+            game.add_hazard(Hazard { position : pos, warn_step, active_step : warn_step + 1, clear_step : warn_step + 10 });
+        }
+        let grid = game.generate_grid();
+        assert_eq!(grid.at(5, 1), GridCell::Remains);
+        for x in 0..8 {
+            for y in 0..6 {
+                assert_eq!(game.cell_at(Vector2i::new(x, y)), grid.at(x as usize, y as usize), "at ({}, {})", x, y);
+            }
+        }
     }
-}
\ No newline at end of file
+
+    // Test that num_empty_cells counts every taken cell once when the remains lie on the walls
+    #[test]
+    fn test_num_empty_cells_shrink_remains() {
+        let config = GameConfig { shrink_interval : Some(2), remains_lifetime : Some(100), ..GameConfig::default() };
+        let mut game = GameBuilder::with_config(config)
+            .field(7, 7)
+            .snake(Vector2i::new(3, 3), Direction::PlusY, 2)
+            .snake(Vector2i::new(4, 1), Direction::PlusX, 2)
+            .build();
+        game.step();
+        game.step();
+        // Second snake died in the new wall ring and left its remains there
+        assert!(!game.players[1].alive());
+        assert!(game.remains.iter().any(|remains| game.walls.contains(&remains.position)));
+        // Duplicate remains don't take more cells
+        let remains = game.remains[0];
+        game.remains.push(remains);
+        let empty = game.generate_grid().iter().filter(|cell| **cell == GridCell::Empty).count();
+        assert_eq!(game.num_empty_cells(), empty as i32);
+    }
+}
@@ -1,9 +1,18 @@
 pub mod base;
-mod snake;
+pub mod config;
+pub mod snake;
 pub mod game;
 pub mod grid;
 pub mod player;
 pub mod events;
+pub mod spawner;
+pub mod demo;
+pub mod tournament;
+pub mod replay;
+#[cfg(feature = "wire")]
+pub mod wire;
 
 pub use base::Vector2i;
-pub use game::Game;
\ No newline at end of file
+pub use game::Game;
+pub use config::GameConfig;
+pub use snake::DirChange;
\ No newline at end of file
@@ -1,19 +1,61 @@
-use crate::base::{Direction, Vector2i};
+use crate::base::{Direction, PlayerColor, Rgb, Vector2i};
 use crate::snake::Snake;
 use crate::events;
 use std::sync::mpsc;
+use std::collections::VecDeque;
 
 
 pub type UserControlRx = mpsc::Receiver<Direction>;
 pub type UserControlTx = mpsc::Sender<Direction>;
 
+/// Source of the direction changes of the player, e.g. keyboard, network or AI
+pub trait Controller {
+    /// Returns the next pending direction change. None if there are no more changes
+    /// at the moment. Called in a loop until it returns None, so it must not
+    /// return directions forever
+    fn poll_direction(&mut self) -> Option<Direction>;
+}
+
+/// Controller fed through the channel
+impl Controller for UserControlRx {
+    fn poll_direction(&mut self) -> Option<Direction> {
+        self.try_recv().ok()
+    }
+}
+
+/// The highest score multiplier player can get from combos
+const MAX_MULTIPLIER : u32 = 5;
+/// Number of consecutive steps with dropped inputs after which the player is reported as flooding
+pub const INPUT_FLOOD_STEPS : u32 = 3;
+
 /// The object that stores data associated with single player in the game
 pub(crate) struct Player
 {
     /// There is no snake if player is dead
     pub snake : Option<Snake>,
     pub score : u32,
-    pub control : Option<UserControlRx>,
+    pub control : Option<Box<dyn Controller + Send>>,
+    /// Directions waiting to be applied on the next steps
+    pub input_queue : VecDeque<Direction>,
+    /// The step the player has eaten pizza last time
+    pub last_eat_step : Option<u64>,
+    /// Score multiplier for the next pizza
+    pub multiplier : u32,
+    pub color : PlayerColor,
+    /// Overrides the RGB value of the color
+    pub rgb : Option<Rgb>,
+    /// Number of directions read since the last step
+    pub tick_inputs : usize,
+    /// Whether directions were dropped since the last step
+    pub inputs_dropped : bool,
+    /// Number of consecutive steps with dropped directions
+    pub flooded_steps : u32,
+    /// Length of the snake when the player died last time
+    pub death_length : usize,
+    /// The step the player died on. None while alive
+    pub death_step : Option<u64>,
+    /// The last step the snake slides on ice and ignores turns. None if not sliding
+    pub slide_until : Option<u64>,
 }
 
 
@@ -24,29 +66,134 @@ impl Player{
                 Direction::PlusX, 2)),
             score : 0,
             control : None,
+            input_queue : VecDeque::new(),
+            last_eat_step : None,
+            multiplier : 1,
+            color : PlayerColor::White,
+            rgb : None,
+            tick_inputs : 0,
+            inputs_dropped : false,
+            flooded_steps : 0,
+            death_length : 0,
+            death_step : None,
+            slide_until : None,
+        }
+    }
+
+    /// Awards the score for eating pizza of given value at given step. Eating within
+    /// the combo window after the previous pizza increases the multiplier. Returns the awarded score
+    pub fn award_pizza(&mut self, value : u32, step : u64, combo_window : Option<u64>) -> u32 {
+        if let Some(window) = combo_window {
+            self.expire_combo(step, window);
+            if self.last_eat_step.is_some() {
+                self.multiplier = (self.multiplier + 1).min(MAX_MULTIPLIER);
+            }
+            self.last_eat_step = Some(step);
         }
+        let awarded = value * self.multiplier;
+        self.score += awarded;
+        awarded
     }
 
-    // Read inputs for players
-    pub fn read_inputs(&mut self) {
-        if let Some(control) = &self.control {
+    /// Resets the combo if the player hasn't eaten within the window
+    pub fn expire_combo(&mut self, step : u64, window : u64) {
+        if self.last_eat_step.is_some_and(|last_eat_step| step - last_eat_step > window) {
+            self.last_eat_step = None;
+            self.multiplier = 1;
+        }
+    }
+
+    // Read inputs for players. Inputs received while dead are discarded.
+    // If input_buffer is 0 the inputs are applied immediately, so the last valid
+    // input read before the step wins. Reverse inputs are invalid and skipped, so
+    // the previous valid input stays. Otherwise up to input_buffer direction changes
+    // are queued to be applied on next steps. Reverses of the last queued direction
+    // are skipped the same way, as they would be rejected when applied.
+    // Only max_inputs directions are read between two steps, the rest are dropped.
+    // Inputs read while sliding on ice are discarded
+    pub fn read_inputs(&mut self, input_buffer : usize, max_inputs : Option<usize>) {
+        if let Some(control) = &mut self.control {
             // Read all inputs.
-            while let Ok(input) = control.try_recv() {
-                if self.alive() {
-                    self.snake.as_mut().unwrap().try_set_look_direction(input);
+            while let Some(input) = control.poll_direction() {
+                if max_inputs.is_some_and(|max_inputs| self.tick_inputs >= max_inputs) {
+                    self.inputs_dropped = true;
+                    continue;
+                }
+                self.tick_inputs += 1;
+                let Some(snake) = self.snake.as_mut() else {
+                    continue;
+                };
+                if self.slide_until.is_some() {
+                    continue;
+                }
+                if input_buffer == 0 {
+                    snake.try_set_look_direction(input);
+                    continue;
+                }
+                // Repeated direction is not a change
+                let last_direction = self.input_queue.back().copied()
+                    .unwrap_or(snake.look_direction());
+                if input != last_direction && input != last_direction.opposite() &&
+                   self.input_queue.len() < input_buffer {
+                    self.input_queue.push_back(input);
                 }
             }
         }
     }
 
+    /// Starts counting the inputs for the next step. Returns the number of
+    /// consecutive steps the player had inputs dropped
+    pub fn start_tick(&mut self) -> u32 {
+        self.flooded_steps = if self.inputs_dropped { self.flooded_steps + 1 } else { 0 };
+        self.tick_inputs = 0;
+        self.inputs_dropped = false;
+        self.flooded_steps
+    }
+
+    /// Applies the next queued direction, if any.
+    /// Reverse directions are rejected at this point. Sliding snake drops the direction
+    pub fn apply_queued_input(&mut self) {
+        let input = self.input_queue.pop_front();
+        if self.slide_until.is_some() {
+            return;
+        }
+        if let (Some(snake), Some(input)) = (self.snake.as_mut(), input) {
+            snake.try_set_look_direction(input);
+        }
+    }
+
+    /// Discards all pending inputs
+    fn discard_inputs(&mut self) {
+        if let Some(control) = &mut self.control {
+            while control.poll_direction().is_some() {}
+        }
+        self.input_queue.clear();
+    }
+
+    /// Brings the player back to life with the given snake.
+    /// Inputs queued while player was dead are discarded, so the
+    /// snake starts in its spawn direction.
+    pub fn respawn(&mut self, snake : Snake) {
+        self.discard_inputs();
+        self.snake = Some(snake);
+        self.last_eat_step = None;
+        self.multiplier = 1;
+        self.death_step = None;
+        self.slide_until = None;
+    }
+
     /// Returns if player is alive
     pub fn alive(&self) -> bool {
-        return self.snake.is_some();
+        self.snake.is_some()
     }
 
     /// Kills the player
     pub fn kill(&mut self) {
-        self.snake = None;
+        if let Some(snake) = self.snake.take() {
+            self.death_length = snake.length();
+        }
+        self.slide_until = None;
+        self.input_queue.clear();
     }
 
     /// Generates event summary
@@ -54,6 +201,9 @@ impl Player{
         events::PlayerSummary {
             score : self.score,
             alive : self.alive(),
+            multiplier : self.multiplier,
+            color : self.color,
+            rgb : self.rgb.unwrap_or(self.color.rgb()),
         }
     }
 }
@@ -0,0 +1,90 @@
+use crate::events::GlobalEvent;
+use crate::game::{GlobalUpdateTx, UPDATE_INTERVAL};
+use std::time::{Duration, Instant};
+
+/// Re-emits recorded events with the cadence of the original game, so existing
+/// frontends can watch the replay unchanged. Every event is recorded with the
+/// step it was sent on. Events of the same step are sent together
+pub struct ReplayPlayer
+{
+    events : Vec<(u64, GlobalEvent)>,
+    interval : Duration,
+}
+
+impl ReplayPlayer {
+    /// Creates the player that sends the steps with the game update interval
+    pub fn new(events : Vec<(u64, GlobalEvent)>) -> ReplayPlayer {
+        ReplayPlayer::with_interval(events, UPDATE_INTERVAL)
+    }
+
+    /// Creates the player that sends the steps with given interval
+    pub fn with_interval(events : Vec<(u64, GlobalEvent)>, interval : Duration) -> ReplayPlayer {
+        ReplayPlayer { events, interval }
+    }
+
+    /// Sends all events in the recorded order. The event is sent when the time of
+    /// its step comes, counting from the step of the first event. Blocks until all
+    /// events are sent. Returns false if the receiver was dropped before that
+    pub fn play(self, channel : &GlobalUpdateTx) -> bool {
+        let start = Instant::now();
+        let first_step = match self.events.first() {
+            Some((step, _)) => *step,
+            None => return true,
+        };
+        for (step, event) in self.events {
+            let due = self.interval * step.saturating_sub(first_step) as u32;
+            std::thread::sleep(due.saturating_sub(start.elapsed()));
+            if channel.send(event).is_err() {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{CountdownTick, Heartbeat};
+    use std::sync::mpsc;
+
+    // Test that the events are sent in order with the step cadence
+    #[test]
+    fn test_replay_player() {
+        let heartbeat = |step| GlobalEvent::Heartbeat(Heartbeat { step, alive_count : 1 });
+        let events = vec![
+            (5, GlobalEvent::CountdownTick(CountdownTick { remaining : 0 })),
+            (5, heartbeat(5)),
+            (7, heartbeat(7)),
+            (8, heartbeat(8)),
+        ];
+        let interval = Duration::from_millis(40);
+        let player = ReplayPlayer::with_interval(events.clone(), interval);
+        let (event_tx, event_rx) = mpsc::channel();
+        let start = Instant::now();
+        let handle = std::thread::spawn(move || player.play(&event_tx));
+        let mut received = Vec::new();
+        while let Ok(event) = event_rx.recv() {
+            received.push((start.elapsed(), event));
+        }
+        assert!(handle.join().unwrap());
+
+        assert_eq!(received.iter().map(|(_, event)| event.clone()).collect::<Vec<_>>(),
+            events.iter().map(|(_, event)| event.clone()).collect::<Vec<_>>());
+        // Every event comes not earlier than its step and not much later
+        for ((elapsed, _), (step, _)) in received.iter().zip(&events) {
+            let due = interval * (step - 5) as u32;
+            assert!(*elapsed >= due && *elapsed < due + interval * 2, "Step {} came at {:?}", step, elapsed);
+        }
+    }
+
+    // Test that the replay stops when nobody listens
+    #[test]
+    fn test_replay_player_disconnected() {
+        let (event_tx, event_rx) = mpsc::channel();
+        drop(event_rx);
+        let events = vec![(0, GlobalEvent::CountdownTick(CountdownTick { remaining : 0 }))];
+        assert!(!ReplayPlayer::new(events).play(&event_tx));
+        assert!(ReplayPlayer::new(Vec::new()).play(&event_tx));
+    }
+}
@@ -1,16 +1,59 @@
 use crate::base::Vector2i;
 use crate::base::Direction;
+use std::collections::{HashSet, VecDeque};
+#[cfg(feature = "validate_body")]
+use std::collections::HashMap;
+
+/// Builds the body of a freshly spawned snake.
+/// The head is at given position and the body trails straight behind it,
+/// opposite to the facing direction. First element is the head.
+pub fn build_initial_body(head: Vector2i, facing: Direction, length: u32) -> Vec<Vector2i> {
+    let dir_vec = Vector2i::from_direction(facing);
+    (0..length as i32).map(|i| head - dir_vec * i).collect()
+}
+
+/// Counts the body parts in every cell
+#[cfg(feature = "validate_body")]
+fn count_cells(body: &VecDeque<Vector2i>) -> HashMap<Vector2i, u32> {
+    let mut counts = HashMap::new();
+    for pos in body {
+        *counts.entry(*pos).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// Result of the request to change the look direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirChange {
+    /// Look direction was changed to the requested one
+    Applied,
+    /// Requested direction reverses the last move, so look direction is kept
+    Rejected,
+    /// Snake already looks in the requested direction
+    Unchanged,
+}
 
 /// Snake struct.
 /// look_direction: The direction the snake is looking to move.
 /// body: The body of the snake. First element represents head.
 /// grow_counter: The number of steps the snake can make with growth.
 /// When snake does a "grow" step - the head moves, but tail doesn't. 
+/// committed_direction: The direction of the last move. Snake can't reverse it.
+/// wrap_size: The field size if the snake has wrapped across the field edge.
+/// Body parts on the opposite edges of such field are neighbours.
+/// self_overlap: The body may pass through itself, when self collisions are off.
+/// cell_counts: The number of body parts in every cell, so the moves are validated
+/// without going over the whole body. Only kept with `validate_body` feature.
 #[derive(Debug, Clone)]
 pub struct Snake {
     look_direction: Direction,
-    body: Vec<Vector2i>,
+    body: VecDeque<Vector2i>,
     grow_counter : i32,
+    committed_direction: Direction,
+    wrap_size: Option<Vector2i>,
+    self_overlap: bool,
+    #[cfg(feature = "validate_body")]
+    cell_counts: HashMap<Vector2i, u32>,
 }
 
 impl Snake
@@ -19,37 +62,179 @@ impl Snake
     pub fn look_direction(&self) -> Direction {
         self.look_direction
     }
-    /// Returns backward direction. This is the direction snake came from.
-    /// Basically it's a difference between head and second element of the body.
-    pub fn backward_direction(&self) -> Vector2i {
-        let res = self.body[1] - self.body[0];
-        // Make sure the length is 1
-        assert!(res.x.abs() + res.y.abs() == 1, "Add support for gaps between snake body parts");
-        
-        res
+    /// Returns backward direction. This is the direction from the head to
+    /// the second element of the body, so the snake came from there.
+    /// #panics
+    /// If the head and the second element are not neighbours
+    pub fn backward_direction(&self) -> Direction {
+        let offset = self.step_between(self.body[0], self.body[1]);
+        Direction::from_offset(offset).expect("Add support for gaps between snake body parts")
     }
 
-    /// Getter for body.
-    pub fn body(&self) -> &Vec<Vector2i> {
-        &self.body
+    /// Returns the offset from one cell to the other. Crossing the field edge
+    /// counts as a single step if the snake has wrapped
+    fn step_between(&self, from: Vector2i, to: Vector2i) -> Vector2i {
+        let mut step = to - from;
+        if let Some(size) = self.wrap_size {
+            if step.x.abs() == size.x - 1 { step.x = -step.x.signum(); }
+            if step.y.abs() == size.y - 1 { step.y = -step.y.signum(); }
+        }
+        step
     }
-    /// Setter for body
+
+    /// Checks that every body part is a neighbour of the previous one and no cell
+    /// is taken twice, unless self overlap is allowed. Such body can only appear
+    /// because of a logic error
+    /// #panics
+    /// If body is invalid
+    #[cfg(any(debug_assertions, feature = "validate_body", test))]
+    fn validate_body(&self) {
+        for (from, to) in self.body.iter().zip(self.body.iter().skip(1)) {
+            let step = self.step_between(*from, *to);
+            assert!(step.x.abs() + step.y.abs() == 1, "Snake body has a gap between {:?} and {:?}", from, to);
+        }
+        if self.self_overlap {
+            return;
+        }
+        let mut cells : Vec<(i32, i32)> = self.body.iter().map(|pos| (pos.x, pos.y)).collect();
+        cells.sort_unstable();
+        if let Some(pair) = cells.windows(2).find(|pair| pair[0] == pair[1]) {
+            panic!("Snake body overlaps itself at {:?}", pair[0]);
+        }
+    }
+
+    /// Counts the body part added to or removed from the cell
+    #[cfg(feature = "validate_body")]
+    fn count_cell(&mut self, pos: Vector2i, added: bool) {
+        let count = self.cell_counts.entry(pos).or_insert(0);
+        if added {
+            *count += 1;
+        }
+        else {
+            *count -= 1;
+            if *count == 0 {
+                self.cell_counts.remove(&pos);
+            }
+        }
+    }
+
+    /// Same checks as `validate_body`, but only for the head. The rest of
+    /// the body was validated when it was the head
+    /// #panics
+    /// If the head is not a neighbour of the next body part or overlaps the body
+    #[cfg(feature = "validate_body")]
+    fn validate_head(&self) {
+        let (head, next) = (self.body[0], self.body[1]);
+        let step = self.step_between(head, next);
+        assert!(step.x.abs() + step.y.abs() == 1, "Snake body has a gap between {:?} and {:?}", head, next);
+        if !self.self_overlap && self.cell_counts.get(&head).copied().unwrap_or(0) > 1 {
+            panic!("Snake body overlaps itself at {:?}", (head.x, head.y));
+        }
+    }
+
+    /// Iterates the body from the head to the tail
+    pub fn body(&self) -> impl DoubleEndedIterator<Item = &Vector2i> + ExactSizeIterator + Clone {
+        self.body.iter()
+    }
+    /// Allows the body to pass through itself
+    pub fn allow_self_overlap(&mut self, allow: bool) {
+        self.self_overlap = allow;
+    }
+    /// Returns number of distinct cells occupied by the snake.
+    /// Same as the length unless the body overlaps itself
+    pub fn num_cells(&self) -> usize {
+        if !self.self_overlap {
+            return self.body.len();
+        }
+        self.body.iter().collect::<HashSet<_>>().len()
+    }
+    /// Returns position of the head
+    pub fn head(&self) -> Vector2i {
+        self.body[0]
+    }
+    /// Returns number of cells occupied by the snake
+    pub fn length(&self) -> usize {
+        self.body.len()
+    }
+    /// Returns the number of steps along the body from the head to the tail.
+    /// Every body part is one step from the previous one, so it's the length minus one
+    pub fn path_length(&self) -> usize {
+        self.body.len() - 1
+    }
+    /// Returns the body parts where the body changes direction, from the head to the tail.
+    /// Crossing the field edge is not a turn
+    pub fn turning_points(&self) -> Vec<Vector2i> {
+        let body : Vec<Vector2i> = self.body.iter().copied().collect();
+        body.windows(3)
+            .filter(|cells| self.step_between(cells[0], cells[1]) != self.step_between(cells[1], cells[2]))
+            .map(|cells| cells[1])
+            .collect()
+    }
+    /// Setter for body. Committed direction is derived from the body shape
+    /// #panics
+    /// If the body has gaps or overlaps itself. Only checked in debug builds
+    /// or with `validate_body` feature
     pub fn set_body(&mut self, body: Vec<Vector2i>) {
-        self.body = body;
+        self.body = body.into();
+        #[cfg(any(debug_assertions, feature = "validate_body"))]
+        self.validate_body();
+        #[cfg(feature = "validate_body")]
+        {
+            self.cell_counts = count_cells(&self.body);
+        }
+        self.committed_direction = self.backward_direction().opposite();
     }
 
-    /// Tries to set new look direction if possible.
-    /// It is not possible to set look direction that is the same
-    /// as backward direction.
-    /// Returns true if resulting direction is same as specified
-    pub fn try_set_look_direction(&mut self, direction: Direction) -> bool {
-        let backward_dir = self.backward_direction();
-        let new_dir = Vector2i::from_direction(direction);
-        if backward_dir != new_dir {
+    /// Makes straight snake for test scenarios. Same shape as the spawned one
+    #[cfg(test)]
+    pub fn straight(head: Vector2i, direction: Direction, length: u32) -> Snake {
+        Snake::with_body(build_initial_body(head, direction, length), direction)
+    }
+
+    /// Makes the snake with given body for test scenarios.
+    /// Committed direction is derived from the body shape
+    /// #panics
+    /// If body is shorter than 2, has gaps or look direction reverses the last move
+    #[cfg(test)]
+    pub fn with_body(body: Vec<Vector2i>, look_direction: Direction) -> Snake {
+        assert!(body.len() >= 2, "Snake length must be >= 2");
+        for pair in body.windows(2) {
+            let step = pair[0] - pair[1];
+            assert!(step.x.abs() + step.y.abs() == 1, "Snake body has a gap");
+        }
+        let mut snake = Snake::new(body[0], look_direction, 2);
+        snake.set_body(body);
+        assert!(look_direction != snake.committed_direction.opposite(), "Look direction reverses the last move");
+        snake
+    }
+
+    /// Returns true if the snake can look to given direction, that is, it doesn't
+    /// reverse the last move. Doesn't change the look direction
+    pub fn can_look(&self, direction: Direction) -> bool {
+        direction != self.committed_direction.opposite()
+    }
+
+    /// Sets new look direction if possible.
+    /// It is not possible to reverse the direction of the last move,
+    /// no matter how many times the look direction was changed since then.
+    pub fn set_look_direction(&mut self, direction: Direction) -> DirChange {
+        // The last move is always away from the second body part
+        debug_assert_eq!(self.committed_direction.opposite(), self.backward_direction());
+        if !self.can_look(direction) {
+            DirChange::Rejected
+        }
+        else if direction == self.look_direction {
+            DirChange::Unchanged
+        }
+        else {
             self.look_direction = direction;
+            DirChange::Applied
         }
-        
-        direction == self.look_direction
+    }
+
+    /// Same as `set_look_direction`, but only tells if resulting direction is same as specified
+    pub fn try_set_look_direction(&mut self, direction: Direction) -> bool {
+        self.set_look_direction(direction) != DirChange::Rejected
     }
 
     /// Create a new snake.
@@ -62,36 +247,109 @@ impl Snake
     pub fn new(position: Vector2i, direction: Direction, length: u32) -> Snake {
         // There is a logic in system that relies on head and tail to be different cells
         assert!(length >= 2, "Snake length must be >= 2");
-        let dir_vec = Vector2i::from_direction(direction);
-        let mut body = Vec::new();
-        for i in 0..length {
-            body.push(position - dir_vec * (i as i32));
-        }
+        let body : VecDeque<Vector2i> = build_initial_body(position, direction, length).into();
         Snake {
             look_direction : direction,
+            #[cfg(feature = "validate_body")]
+            cell_counts : count_cells(&body),
             body,
             grow_counter : 0,
+            committed_direction : direction,
+            wrap_size : None,
+            self_overlap : false,
         }
     }
 
-    /// Eat specified amount of food
+    /// Create a new snake that fits into the field.
+    /// Same as `new`, but the length is clamped so the whole body stays
+    /// inside the field. Length is never clamped below 2.
+    pub fn new_clamped(position: Vector2i, direction: Direction, length: u32, field_size: Vector2i) -> Snake {
+        // Number of cells from the head to the field edge behind it
+        let available = match direction {
+            Direction::PlusX => position.x + 1,
+            Direction::MinusX => field_size.x - position.x,
+            Direction::PlusY => position.y + 1,
+            Direction::MinusY => field_size.y - position.y,
+        };
+        let length = length.min(available.max(0) as u32).max(2);
+        Snake::new(position, direction, length)
+    }
+
+    /// Eat specified amount of food. Every food makes the snake 1 cell longer
+    /// on one of the next moves
     pub fn eat(&mut self, food: i32) {
         self.grow_counter += food;
     }
 
-    /// Move the snake 1 step in current direction
-    pub fn move_forward(&mut self) {
+    /// Makes the snake `amount` cells shorter. Undigested food is dropped first,
+    /// then the tail segments are removed. The snake keeps at least 2 cells
+    pub fn shrink(&mut self, amount: usize) {
+        let pending = (self.grow_counter.max(0) as usize).min(amount);
+        self.grow_counter -= pending as i32;
+        let removable = (amount - pending).min(self.body.len().saturating_sub(2));
+        let new_length = self.body.len() - removable;
+        #[cfg(feature = "validate_body")]
+        for tail in self.body.range(new_length..).copied().collect::<Vec<_>>() {
+            self.count_cell(tail, false);
+        }
+        self.body.truncate(new_length);
+    }
+
+    /// Returns true if the tail stays in place on the next move
+    pub fn will_grow(&self) -> bool {
+        self.grow_counter > 0
+    }
+
+    /// Returns the length the snake will have once all eaten food is digested
+    pub fn pending_length(&self) -> usize {
+        self.body.len() + self.grow_counter.max(0) as usize
+    }
+
+    /// Move the snake 1 step in current direction.
+    /// Returns the cell vacated by the tail. None if snake has grown.
+    /// The new head is validated after the move with `validate_body` feature.
+    /// It costs a cell count update every move, so it's off by default
+    pub fn move_forward(&mut self) -> Option<Vector2i> {
         let move_dir = Vector2i::from_direction(self.look_direction);
         let new_head = self.body[0] + move_dir;
-        self.body.insert(0, new_head);
+        self.body.push_front(new_head);
+        self.committed_direction = self.look_direction;
 
         // Snake grows if grow_counter > 0
-        if self.grow_counter > 0 {
+        let vacated = if self.grow_counter > 0 {
             self.grow_counter -= 1;
+            None
         } 
         else {
-            self.body.pop();
+            self.body.pop_back()
+        };
+        #[cfg(feature = "validate_body")]
+        {
+            self.count_cell(new_head, true);
+            if let Some(tail) = vacated {
+                self.count_cell(tail, false);
+            }
+            self.validate_head();
+        }
+        vacated
+    }
+
+    /// Moves the head that left the field to the opposite edge.
+    /// Returns true if the head was moved
+    pub fn wrap_head(&mut self, field_size : Vector2i) -> bool {
+        let head = self.body[0];
+        let wrapped = Vector2i::new(head.x.rem_euclid(field_size.x), head.y.rem_euclid(field_size.y));
+        self.body[0] = wrapped;
+        if wrapped != head {
+            self.wrap_size = Some(field_size);
+            #[cfg(feature = "validate_body")]
+            {
+                self.count_cell(head, false);
+                self.count_cell(wrapped, true);
+                self.validate_head();
+            }
         }
+        wrapped != head
     }
     
 }
@@ -101,6 +359,15 @@ impl Snake
 #[cfg(test)]
 mod tests {
     use super::*;
+    // Test initial body trails behind the head
+    #[test]
+    fn test_build_initial_body() {
+        assert_eq!(build_initial_body(Vector2i::new(3, 3), Direction::MinusY, 3),
+            vec![Vector2i::new(3,3), Vector2i::new(3,4), Vector2i::new(3,5)]);
+        assert_eq!(build_initial_body(Vector2i::new(3, 3), Direction::PlusX, 2),
+            vec![Vector2i::new(3,3), Vector2i::new(2,3)]);
+    }
+
     #[test]
     fn test_snake_new() {
 
@@ -116,7 +383,7 @@ mod tests {
         let mut snake = Snake::new(
             Vector2i::new(0,0), 
             Direction::PlusX, 3);
-        snake.move_forward();
+        assert_eq!(snake.move_forward(), Some(Vector2i::new(-2,0)));
         assert_eq!(snake.look_direction, Direction::PlusX);
         assert_eq!(snake.body, vec![Vector2i::new(1,0), Vector2i::new(0,0), Vector2i::new(-1,0)]);
     }
@@ -128,7 +395,7 @@ mod tests {
             Vector2i::new(0,0), 
             Direction::PlusX, 3);
         snake.eat(2);
-        snake.move_forward();
+        assert_eq!(snake.move_forward(), None);
         assert_eq!(snake.look_direction, Direction::PlusX);
         assert_eq!(snake.body, vec![Vector2i::new(1,0), Vector2i::new(0,0), Vector2i::new(-1,0), Vector2i::new(-2,0)]);
         snake.move_forward();
@@ -144,14 +411,270 @@ mod tests {
         let mut snake = Snake::new(
             Vector2i::new(0,0), 
             Direction::PlusX, 3);
-        assert_eq!(snake.try_set_look_direction(Direction::PlusY), true);
+        assert!(snake.try_set_look_direction(Direction::PlusY));
         assert_eq!(snake.look_direction, Direction::PlusY);
-        assert_eq!(snake.try_set_look_direction(Direction::MinusX), false);
+        assert!(!snake.try_set_look_direction(Direction::MinusX));
         assert_eq!(snake.look_direction, Direction::PlusY);
-        assert_eq!(snake.try_set_look_direction(Direction::MinusY), true);
+        assert!(snake.try_set_look_direction(Direction::MinusY));
         assert_eq!(snake.look_direction, Direction::MinusY);
-        assert_eq!(snake.try_set_look_direction(Direction::PlusX), true);
+        assert!(snake.try_set_look_direction(Direction::PlusX));
         assert_eq!(snake.look_direction, Direction::PlusX);
         
     }
-}
\ No newline at end of file
+
+    // Test every outcome of the look direction change
+    #[test]
+    fn test_snake_set_look_direction() {
+        let mut snake = Snake::new(Vector2i::new(0, 0), Direction::PlusX, 3);
+        assert_eq!(snake.set_look_direction(Direction::PlusX), DirChange::Unchanged);
+        assert_eq!(snake.set_look_direction(Direction::PlusY), DirChange::Applied);
+        assert_eq!(snake.set_look_direction(Direction::PlusY), DirChange::Unchanged);
+        assert_eq!(snake.set_look_direction(Direction::MinusX), DirChange::Rejected);
+        assert_eq!(snake.look_direction, Direction::PlusY);
+        // Returning to the committed direction is a change
+        assert_eq!(snake.set_look_direction(Direction::PlusX), DirChange::Applied);
+        assert_eq!(snake.look_direction, Direction::PlusX);
+    }
+
+    // Test that clamped snake stays inside the field
+    #[test]
+    fn test_snake_new_clamped() {
+        let field_size = Vector2i::new(10, 10);
+        // Fits as is
+        let snake = Snake::new_clamped(Vector2i::new(5, 5), Direction::PlusX, 3, field_size);
+        assert_eq!(snake.body.len(), 3);
+        // Too long for the space behind the head
+        let snake = Snake::new_clamped(Vector2i::new(2, 5), Direction::PlusX, 8, field_size);
+        assert_eq!(snake.body, vec![Vector2i::new(2,5), Vector2i::new(1,5), Vector2i::new(0,5)]);
+        let snake = Snake::new_clamped(Vector2i::new(5, 7), Direction::MinusY, 8, field_size);
+        assert_eq!(snake.body.len(), 3);
+        assert_eq!(*snake.body.back().unwrap(), Vector2i::new(5, 9));
+        // Never shorter than 2
+        let snake = Snake::new_clamped(Vector2i::new(0, 5), Direction::PlusX, 8, field_size);
+        assert_eq!(snake.body.len(), 2);
+    }
+
+    // Test that fast inputs between moves can't reverse the last move
+    #[test]
+    fn test_snake_committed_direction() {
+        let mut snake = Snake::new(
+            Vector2i::new(0,0), 
+            Direction::PlusX, 3);
+        assert_eq!(snake.committed_direction, Direction::PlusX);
+        // Several turns before the move. Reversing the last move is rejected
+        // even when look direction is no longer opposite to it
+        assert!(snake.try_set_look_direction(Direction::PlusY));
+        assert!(!snake.can_look(Direction::MinusX));
+        assert!(!snake.try_set_look_direction(Direction::MinusX));
+        assert_eq!(snake.look_direction, Direction::PlusY);
+        // Committed direction changes only with the move
+        assert_eq!(snake.committed_direction, Direction::PlusX);
+        snake.move_forward();
+        assert_eq!(snake.committed_direction, Direction::PlusY);
+        // Now the old reverse is allowed and the new one is not
+        assert!(snake.try_set_look_direction(Direction::MinusX));
+        assert!(!snake.try_set_look_direction(Direction::MinusY));
+        assert_eq!(snake.look_direction, Direction::MinusX);
+    }
+
+    // Test that set_body derives committed direction from the body shape
+    #[test]
+    fn test_snake_set_body_committed_direction() {
+        let mut snake = Snake::new(
+            Vector2i::new(0,0), 
+            Direction::PlusX, 3);
+        snake.set_body(vec![Vector2i::new(2,2), Vector2i::new(2,3)]);
+        assert_eq!(snake.committed_direction, Direction::MinusY);
+        assert!(!snake.try_set_look_direction(Direction::PlusY));
+        assert!(snake.try_set_look_direction(Direction::MinusX));
+    }
+
+    // Test scenario helpers keep the snake invariants
+    #[test]
+    fn test_snake_scenario_helpers() {
+        let snake = Snake::straight(Vector2i::new(3, 3), Direction::MinusX, 3);
+        assert_eq!(snake.body, vec![Vector2i::new(3,3), Vector2i::new(4,3), Vector2i::new(5,3)]);
+        assert_eq!(snake.look_direction, Direction::MinusX);
+        assert_eq!(snake.committed_direction, Direction::MinusX);
+
+        // Bent body, looking sideways
+        let snake = Snake::with_body(vec![
+            Vector2i::new(1, 1),
+            Vector2i::new(1, 2),
+            Vector2i::new(2, 2),
+        ], Direction::PlusX);
+        assert_eq!(snake.body.len(), 3);
+        assert_eq!(snake.look_direction, Direction::PlusX);
+        assert_eq!(snake.committed_direction, Direction::MinusY);
+    }
+
+    // Test body with a gap is rejected
+    #[test]
+    #[should_panic]
+    fn test_snake_with_body_gap() {
+        Snake::with_body(vec![Vector2i::new(1, 1), Vector2i::new(1, 3)], Direction::MinusY);
+    }
+
+    // Test look direction reversing the last move is rejected
+    #[test]
+    #[should_panic]
+    fn test_snake_with_body_reverse() {
+        Snake::with_body(vec![Vector2i::new(1, 1), Vector2i::new(1, 2)], Direction::PlusY);
+    }
+
+    // Test body iterates from the head to the tail
+    #[test]
+    fn test_snake_body_order() {
+        let mut snake = Snake::new(Vector2i::new(2, 2), Direction::PlusY, 3);
+        snake.move_forward();
+        assert_eq!(snake.head(), Vector2i::new(2, 3));
+        assert_eq!(snake.body().next(), Some(&Vector2i::new(2, 3)));
+        assert_eq!(snake.body().next_back(), Some(&Vector2i::new(2, 1)));
+        assert_eq!(snake.body().len(), 3);
+        assert_eq!(snake.length(), 3);
+    }
+
+    // Test moving long snake keeps the whole body
+    #[test]
+    fn test_snake_move_long() {
+        let length = 100_000;
+        let mut snake = Snake::new(Vector2i::new(0, 0), Direction::PlusX, length);
+        snake.eat(length as i32);
+        for _ in 0..2 * length {
+            snake.move_forward();
+        }
+        assert_eq!(snake.length(), 2 * length as usize);
+        assert_eq!(snake.head(), Vector2i::new(2 * length as i32, 0));
+        assert_eq!(snake.body().next_back(), Some(&Vector2i::new(1, 0)));
+        snake.validate_body();
+    }
+
+    // Test moving long snake doesn't depend on its length. Timing depends on
+    // the machine, so it only runs on request
+    #[test]
+    #[ignore = "timing check, run with --ignored"]
+    fn test_snake_move_long_timing() {
+        let length = 100_000;
+        let mut snake = Snake::new(Vector2i::new(0, 0), Direction::PlusX, length);
+        snake.eat(length as i32);
+        let start = std::time::Instant::now();
+        for _ in 0..2 * length {
+            snake.move_forward();
+        }
+        // Shifting the whole body on every move would take minutes
+        assert!(start.elapsed() < std::time::Duration::from_secs(2), "Took {:?}", start.elapsed());
+    }
+
+    // Test that shrinking drops the undigested food first, then the tail
+    #[test]
+    fn test_snake_shrink() {
+        let mut snake = Snake::new(Vector2i::new(5, 0), Direction::PlusX, 5);
+        snake.eat(1);
+        snake.shrink(2);
+        assert!(!snake.will_grow());
+        assert_eq!(snake.body, vec![Vector2i::new(5,0), Vector2i::new(4,0), Vector2i::new(3,0), Vector2i::new(2,0)]);
+        assert_eq!(snake.pending_length(), 4);
+        snake.move_forward();
+        assert_eq!(*snake.body.back().unwrap(), Vector2i::new(3, 0));
+    }
+
+    // Test that the snake never gets shorter than 2
+    #[test]
+    fn test_snake_shrink_floor() {
+        let mut snake = Snake::new(Vector2i::new(5, 0), Direction::PlusX, 3);
+        snake.shrink(10);
+        assert_eq!(snake.body, vec![Vector2i::new(5,0), Vector2i::new(4,0)]);
+        snake.shrink(1);
+        assert_eq!(snake.length(), 2);
+        assert_ne!(snake.head(), *snake.body.back().unwrap());
+    }
+
+    // Test that head leaving the field is moved to the opposite edge
+    #[test]
+    fn test_snake_wrap_head() {
+        let field_size = Vector2i::new(4, 3);
+        let mut snake = Snake::new(Vector2i::new(3, 1), Direction::PlusX, 2);
+        assert!(!snake.wrap_head(field_size));
+        snake.move_forward();
+        assert!(snake.wrap_head(field_size));
+        assert_eq!(snake.head(), Vector2i::new(0, 1));
+        assert_eq!(snake.body().nth(1), Some(&Vector2i::new(3, 1)));
+        // Body stays connected across the edge
+        assert_eq!(snake.backward_direction(), Direction::MinusX);
+        assert!(snake.try_set_look_direction(Direction::PlusY));
+        assert!(!snake.try_set_look_direction(Direction::MinusX));
+        snake.validate_body();
+
+        let mut snake = Snake::new(Vector2i::new(1, 0), Direction::MinusY, 2);
+        snake.move_forward();
+        assert!(snake.wrap_head(field_size));
+        assert_eq!(snake.head(), Vector2i::new(1, 2));
+    }
+
+    // Test self-overlapping body is caught
+    #[cfg(any(debug_assertions, feature = "validate_body"))]
+    #[test]
+    #[should_panic(expected = "Snake body overlaps itself")]
+    fn test_snake_set_body_overlap() {
+        let mut snake = Snake::new(Vector2i::new(1, 1), Direction::PlusX, 2);
+        snake.set_body(vec![
+            Vector2i::new(1, 1),
+            Vector2i::new(1, 2),
+            Vector2i::new(2, 2),
+            Vector2i::new(2, 1),
+            Vector2i::new(1, 1),
+        ]);
+    }
+
+    // Test the move into own body is caught with validate_body feature
+    #[cfg(feature = "validate_body")]
+    #[test]
+    #[should_panic(expected = "Snake body overlaps itself")]
+    fn test_snake_move_overlap() {
+        let mut snake = Snake::new(Vector2i::new(1, 1), Direction::PlusX, 5);
+        snake.eat(1);
+        for direction in [Direction::PlusY, Direction::MinusX, Direction::MinusY] {
+            assert!(snake.try_set_look_direction(direction));
+            snake.move_forward();
+        }
+    }
+
+    // Test backward direction is typed and points to the second body part
+    #[test]
+    fn test_snake_backward_direction() {
+        for direction in Direction::all() {
+            let snake = Snake::new(Vector2i::new(5, 5), direction, 3);
+            assert_eq!(snake.backward_direction(), direction.opposite());
+        }
+        let snake = Snake::with_body(vec![
+            Vector2i::new(2, 1),
+            Vector2i::new(2, 2),
+            Vector2i::new(1, 2),
+        ], Direction::MinusY);
+        assert_eq!(snake.backward_direction(), Direction::PlusY);
+    }
+
+    // Test turning points and path length of straight and bent snakes
+    #[test]
+    fn test_snake_turning_points() {
+        let snake = Snake::straight(Vector2i::new(5, 5), Direction::PlusX, 4);
+        assert!(snake.turning_points().is_empty());
+        assert_eq!(snake.path_length(), 3);
+
+        let snake = Snake::with_body(vec![
+            Vector2i::new(2, 0),
+            Vector2i::new(2, 1),
+            Vector2i::new(2, 2),
+            Vector2i::new(1, 2),
+            Vector2i::new(0, 2),
+        ], Direction::MinusY);
+        assert_eq!(snake.turning_points(), vec![Vector2i::new(2, 2)]);
+        assert_eq!(snake.path_length(), 4);
+
+        // Crossing the edge is not a turn
+        let mut snake = Snake::new(Vector2i::new(3, 1), Direction::PlusX, 3);
+        snake.move_forward();
+        assert!(snake.wrap_head(Vector2i::new(4, 4)));
+        assert!(snake.turning_points().is_empty());
+    }
+}
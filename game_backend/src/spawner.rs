@@ -0,0 +1,125 @@
+use crate::base::Vector2i;
+use crate::config::PizzaSpawnDist;
+use crate::grid::{Grid, GridAccess, GridCell};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+/// Policy that picks the cell for a new pizza
+pub trait PizzaSpawner {
+    /// Picks one of the empty cells of the grid. `estimated_free_cells` is the number
+    /// of empty cells in the grid. Returns None if there are no free cells
+    fn spawn_pos(&mut self, grid : &Grid, estimated_free_cells : i32) -> Option<Vector2i>;
+}
+
+/// Default spawner that picks random free cell using given distribution
+pub struct RandomSpawner
+{
+    dist : PizzaSpawnDist,
+    rng : StdRng,
+}
+
+impl RandomSpawner {
+    /// Creates the spawner. Same seed gives same positions on same grids.
+    /// No seed makes the positions unpredictable
+    pub fn new(dist : PizzaSpawnDist, seed : Option<u64>) -> RandomSpawner {
+        let rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        RandomSpawner { dist, rng }
+    }
+
+    /// Picks random free cell. Every free cell is equally likely
+    fn uniform_pos(&mut self, grid : &Grid, estimated_free_cells : i32) -> Option<Vector2i> {
+        // Randomly generate the free cell index
+        let mut free_cell_counter = self.rng.gen_range(0..estimated_free_cells as usize);
+        // Loop the grid and find empty cell with the given index
+        for ((x, y), cell) in grid.indexed_iter() {
+            if *cell == GridCell::Empty {
+                if free_cell_counter == 0 {
+                    return Some(Vector2i::new(x as i32, y as i32));
+                }
+                else {
+                     free_cell_counter -= 1;
+                };
+            }
+        }
+        // Should never happen
+        panic!("Could not find free cell");
+    }
+
+    /// Picks random free cell. The weight of the cell falls linearly with
+    /// its manhattan distance to the center. Returns None if there are no free cells
+    fn center_weighted_pos(&mut self, grid : &Grid) -> Option<Vector2i> {
+        // Coordinates are doubled so the center is integer on even sized fields
+        let (size_x, size_y) = (grid.width() as i64, grid.height() as i64);
+        let max_distance = (size_x - 1) + (size_y - 1);
+        let weight = |x : usize, y : usize| {
+            let distance = (2 * x as i64 - (size_x - 1)).abs() + (2 * y as i64 - (size_y - 1)).abs();
+            (max_distance - distance + 1) as u64
+        };
+        let free_cells = || grid.indexed_iter().filter(|(_, cell)| **cell == GridCell::Empty);
+        let total_weight : u64 = free_cells().map(|((x, y), _)| weight(x, y)).sum();
+        if total_weight == 0 {
+            return None;
+        }
+        let mut counter = self.rng.gen_range(0..total_weight);
+        for ((x, y), _) in free_cells() {
+            let cell_weight = weight(x, y);
+            if counter < cell_weight {
+                return Some(Vector2i::new(x as i32, y as i32));
+            }
+            counter -= cell_weight;
+        }
+        // Should never happen
+        panic!("Could not find free cell");
+    }
+}
+
+impl PizzaSpawner for RandomSpawner {
+    /// #panics
+    /// If estimated free cells is < 0 or more than there are empty cells in the grid
+    fn spawn_pos(&mut self, grid : &Grid, estimated_free_cells : i32) -> Option<Vector2i> {
+        assert!(estimated_free_cells >= 0);
+        if estimated_free_cells == 0 {
+            return None;
+        }
+        match self.dist {
+            PizzaSpawnDist::Uniform => self.uniform_pos(grid, estimated_free_cells),
+            PizzaSpawnDist::CenterWeighted => self.center_weighted_pos(grid),
+        }
+    }
+}
+
+/// Spawner that always picks the first free cell in the grid order.
+/// Doesn't use the RNG, so the positions are fully predictable
+pub struct FirstFreeSpawner;
+
+impl PizzaSpawner for FirstFreeSpawner {
+    fn spawn_pos(&mut self, grid : &Grid, _estimated_free_cells : i32) -> Option<Vector2i> {
+        grid.indexed_iter()
+            .find(|(_, cell)| **cell == GridCell::Empty)
+            .map(|((x, y), _)| Vector2i::new(x as i32, y as i32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::{self, PizzaKind, PizzaRec};
+
+    // Test that first free spawner follows the grid order
+    #[test]
+    fn test_first_free_spawner() {
+        let mut grid = grid::new_grid(2, 2);
+        let mut spawner = FirstFreeSpawner;
+        assert_eq!(spawner.spawn_pos(&grid, 4), Some(Vector2i::new(0, 0)));
+        grid.set_at(0, 0, GridCell::Pizza(PizzaRec{ kind : PizzaKind::Normal }));
+        assert_eq!(spawner.spawn_pos(&grid, 3), Some(Vector2i::new(0, 1)));
+        grid.set_at(0, 1, GridCell::Wall);
+        grid.set_at(1, 0, GridCell::Wall);
+        assert_eq!(spawner.spawn_pos(&grid, 1), Some(Vector2i::new(1, 1)));
+        grid.set_at(1, 1, GridCell::Wall);
+        assert_eq!(spawner.spawn_pos(&grid, 0), None);
+    }
+}
@@ -0,0 +1,141 @@
+use crate::base::PlayerIndex;
+use crate::events::GameOver;
+
+/// Cumulative standings of a best-of-N series of games.
+/// Every game awards points to the players by their place in `GameOver::ranking`
+pub struct Tournament
+{
+    num_games : u32,
+    /// Points for the first, second, etc. place. Places past the end give no points
+    placement_points : Vec<u32>,
+    /// Points of every player, indexed by player index
+    points : Vec<u32>,
+    games_played : u32,
+}
+
+impl Tournament {
+    /// Creates the series of `num_games` games. `placement_points` are the
+    /// points for the first, second, etc. place
+    /// #panics
+    /// If num_games is 0
+    pub fn new(num_games : u32, placement_points : Vec<u32>) -> Tournament {
+        assert!(num_games > 0, "Tournament needs at least one game");
+        Tournament { num_games, placement_points, points : Vec::new(), games_played : 0 }
+    }
+
+    /// Adds the result of the next game to the standings
+    /// #panics
+    /// If all the games of the series are already played
+    pub fn record(&mut self, game_over : &GameOver) {
+        assert!(!self.is_finished(), "All games are already played");
+        for (place, player_index) in game_over.ranking.iter().enumerate() {
+            if self.points.len() <= *player_index {
+                self.points.resize(*player_index + 1, 0);
+            }
+            self.points[*player_index] += self.placement_points.get(place).copied().unwrap_or(0);
+        }
+        self.games_played += 1;
+    }
+
+    /// Cumulative points of every player, indexed by player index.
+    /// Players that were never ranked are missing at the end
+    pub fn points(&self) -> &[u32] {
+        &self.points
+    }
+
+    /// Player indices ordered by points from the best to the worst.
+    /// Ties keep the lower player index first
+    pub fn standings(&self) -> Vec<PlayerIndex> {
+        let mut standings : Vec<PlayerIndex> = (0..self.points.len()).collect();
+        standings.sort_by_key(|player_index| std::cmp::Reverse(self.points[*player_index]));
+        standings
+    }
+
+    pub fn games_played(&self) -> u32 {
+        self.games_played
+    }
+
+    /// Returns true if all the games of the series are played
+    pub fn is_finished(&self) -> bool {
+        self.games_played >= self.num_games
+    }
+
+    /// Returns the player that won the series. The series is decided once nobody
+    /// can catch up with the leader in the games left, even if the leader scores nothing.
+    /// Returns None while the series is open or if it finished with a tie
+    pub fn winner(&self) -> Option<PlayerIndex> {
+        let standings = self.standings();
+        let leader = *standings.first()?;
+        let games_left = self.num_games - self.games_played;
+        let max_gain = games_left * self.placement_points.iter().copied().max().unwrap_or(0);
+        let decided = standings[1..].iter()
+            .all(|player_index| self.points[*player_index] + max_gain < self.points[leader]);
+        decided.then_some(leader)
+    }
+
+    /// Returns true if the series has a winner
+    pub fn is_decided(&self) -> bool {
+        self.winner().is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::GameOverReason;
+    use std::time::Duration;
+
+    fn game_over(ranking : Vec<PlayerIndex>) -> GameOver {
+        GameOver {
+            reason : GameOverReason::AllDead,
+            players_summary : Vec::new(),
+            duration : Duration::ZERO,
+            total_steps : 0,
+            ranking,
+        }
+    }
+
+    // Test the standings and the winner of best of three series
+    #[test]
+    fn test_tournament() {
+        let mut tournament = Tournament::new(3, vec![3, 1]);
+        assert_eq!(tournament.winner(), None);
+
+        tournament.record(&game_over(vec![1, 0, 2]));
+        assert_eq!(tournament.points(), &[1, 3, 0]);
+        assert_eq!(tournament.standings(), vec![1, 0, 2]);
+        assert!(!tournament.is_decided());
+
+        tournament.record(&game_over(vec![0, 2, 1]));
+        assert_eq!(tournament.points(), &[4, 3, 1]);
+        assert_eq!(tournament.standings(), vec![0, 1, 2]);
+        // Player 1 can still catch up
+        assert!(!tournament.is_decided());
+        assert!(!tournament.is_finished());
+
+        tournament.record(&game_over(vec![0, 1, 2]));
+        assert_eq!(tournament.points(), &[7, 4, 1]);
+        assert_eq!(tournament.standings(), vec![0, 1, 2]);
+        assert!(tournament.is_finished());
+        assert_eq!(tournament.winner(), Some(0));
+        assert_eq!(tournament.games_played(), 3);
+    }
+
+    // Test that the series is decided early when nobody can catch up,
+    // and that a tie has no winner
+    #[test]
+    fn test_tournament_decided() {
+        let mut tournament = Tournament::new(3, vec![1]);
+        tournament.record(&game_over(vec![0, 1]));
+        assert!(!tournament.is_decided());
+        tournament.record(&game_over(vec![0, 1]));
+        assert_eq!(tournament.winner(), Some(0));
+        assert!(!tournament.is_finished());
+
+        let mut tournament = Tournament::new(2, vec![1]);
+        tournament.record(&game_over(vec![0, 1]));
+        tournament.record(&game_over(vec![1, 0]));
+        assert!(tournament.is_finished());
+        assert_eq!(tournament.winner(), None);
+    }
+}
@@ -0,0 +1,373 @@
+//! Compact binary encoding of the events. Meant for sending high frequency
+//! updates over the network with minimal bandwidth.
+//!
+//! Layout of the encoded `Update`:
+//! - grid dimensions (x, y)
+//! - number of runs followed by the runs. Every run is a count and a cell.
+//!   Cells are stored in the grid iteration order (x is the first axis).
+//!   A cell is a tag byte followed by its data: player index and body part byte
+//!   for snakes, kind byte for pizzas, active byte for hazards, nothing for the rest
+//! - number of player summaries followed by the summaries. A summary is the score,
+//!   alive byte, multiplier, color byte and three RGB bytes
+//! - step number
+//! - elapsed time as whole seconds and nanoseconds, so it decodes exactly
+//! - number of players wrapped across the field edge followed by their indices
+//!
+//! All integers are encoded as LEB128 varints, bytes are written as is.
+use crate::base::PlayerColor;
+use crate::events::{PlayerSummary, Update};
+use crate::grid::{Grid, GridCell, PizzaKind, PizzaRec, SnakeBodyPart, SnakeRec};
+use std::time::Duration;
+
+const TAG_EMPTY : u8 = 0;
+const TAG_SNAKE : u8 = 1;
+const TAG_PIZZA : u8 = 2;
+const TAG_HAZARD : u8 = 3;
+const TAG_WALL : u8 = 4;
+const TAG_REMAINS : u8 = 5;
+const TAG_ICE : u8 = 6;
+
+/// Helper that appends encoded values to the buffer
+struct Writer
+{
+    buf : Vec<u8>,
+}
+
+impl Writer {
+    /// Writes unsigned varint
+    fn write_uint(&mut self, mut value : u64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.buf.push(byte);
+                return;
+            }
+            self.buf.push(byte | 0x80);
+        }
+    }
+
+    /// Writes single cell
+    fn write_cell(&mut self, cell : &GridCell) {
+        match cell {
+            GridCell::Empty => self.buf.push(TAG_EMPTY),
+            GridCell::Snake(snake_rec) => {
+                self.buf.push(TAG_SNAKE);
+                self.write_uint(snake_rec.player_index as u64);
+                self.buf.push(match snake_rec.body_part {
+                    SnakeBodyPart::Head => 0,
+                    SnakeBodyPart::Body => 1,
+                    SnakeBodyPart::Tail => 2,
+                    SnakeBodyPart::Neck => 3,
+                });
+            }
+            GridCell::Pizza(pizza_rec) => {
+                self.buf.push(TAG_PIZZA);
+                self.buf.push(match pizza_rec.kind {
+                    PizzaKind::Normal => 0,
+                    PizzaKind::Golden => 1,
+                    PizzaKind::Speed => 2,
+                    PizzaKind::Poison => 3,
+                });
+            }
+            GridCell::Hazard { active } => {
+                self.buf.push(TAG_HAZARD);
+                self.buf.push(*active as u8);
+            }
+            GridCell::Wall => self.buf.push(TAG_WALL),
+            GridCell::Remains => self.buf.push(TAG_REMAINS),
+            GridCell::Ice => self.buf.push(TAG_ICE),
+        }
+    }
+}
+
+/// Helper that reads encoded values from the buffer.
+/// Every function returns None if data is malformed
+struct Reader<'a>
+{
+    bytes : &'a [u8],
+    pos : usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Reads single byte
+    fn read_byte(&mut self) -> Option<u8> {
+        let byte = *self.bytes.get(self.pos)?;
+        self.pos += 1;
+        Some(byte)
+    }
+
+    /// Reads unsigned varint
+    fn read_uint(&mut self) -> Option<u64> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.read_byte()?;
+            if shift >= 64 {
+                return None;
+            }
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+            shift += 7;
+        }
+    }
+
+    /// Reads unsigned varint that is expected to fit usize
+    fn read_usize(&mut self) -> Option<usize> {
+        usize::try_from(self.read_uint()?).ok()
+    }
+
+    /// Reads single cell
+    fn read_cell(&mut self) -> Option<GridCell> {
+        match self.read_byte()? {
+            TAG_EMPTY => Some(GridCell::Empty),
+            TAG_SNAKE => {
+                let player_index = self.read_usize()?;
+                let body_part = match self.read_byte()? {
+                    0 => SnakeBodyPart::Head,
+                    1 => SnakeBodyPart::Body,
+                    2 => SnakeBodyPart::Tail,
+                    3 => SnakeBodyPart::Neck,
+                    _ => return None,
+                };
+                Some(GridCell::Snake(SnakeRec{ player_index, body_part }))
+            }
+            TAG_PIZZA => {
+                let kind = match self.read_byte()? {
+                    0 => PizzaKind::Normal,
+                    1 => PizzaKind::Golden,
+                    2 => PizzaKind::Speed,
+                    3 => PizzaKind::Poison,
+                    _ => return None,
+                };
+                Some(GridCell::Pizza(PizzaRec{ kind }))
+            }
+            TAG_HAZARD => {
+                let active = match self.read_byte()? {
+                    0 => false,
+                    1 => true,
+                    _ => return None,
+                };
+                Some(GridCell::Hazard { active })
+            }
+            TAG_WALL => Some(GridCell::Wall),
+            TAG_REMAINS => Some(GridCell::Remains),
+            TAG_ICE => Some(GridCell::Ice),
+            _ => None,
+        }
+    }
+}
+
+impl Update {
+    /// Encodes the update into compact binary form
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer { buf : Vec::new() };
+        // Dimensions
+        let (size_x, size_y) = self.grid.dim();
+        writer.write_uint(size_x as u64);
+        writer.write_uint(size_y as u64);
+
+        // Collect runs of equal cells
+        let mut runs : Vec<(u64, &GridCell)> = Vec::new();
+        for cell in self.grid.iter() {
+            match runs.last_mut() {
+                Some((count, last_cell)) if *last_cell == cell => *count += 1,
+                _ => runs.push((1, cell)),
+            }
+        }
+        writer.write_uint(runs.len() as u64);
+        for (count, cell) in runs {
+            writer.write_uint(count);
+            writer.write_cell(cell);
+        }
+
+        // Summaries
+        writer.write_uint(self.players_summary.len() as u64);
+        for summary in &self.players_summary {
+            writer.write_uint(summary.score as u64);
+            writer.buf.push(summary.alive as u8);
+            writer.write_uint(summary.multiplier as u64);
+            writer.buf.push(match summary.color {
+                PlayerColor::White => 0,
+                PlayerColor::Green => 1,
+                PlayerColor::Cyan => 2,
+                PlayerColor::Magenta => 3,
+                PlayerColor::Blue => 4,
+                PlayerColor::Yellow => 5,
+                PlayerColor::Red => 6,
+            });
+            writer.buf.extend([summary.rgb.0, summary.rgb.1, summary.rgb.2]);
+        }
+
+        // Timing
+        writer.write_uint(self.step);
+        writer.write_uint(self.elapsed.as_secs());
+        writer.write_uint(self.elapsed.subsec_nanos() as u64);
+
+        // Wrapped players
+        writer.write_uint(self.wrapped.len() as u64);
+        for player_index in &self.wrapped {
+            writer.write_uint(*player_index as u64);
+        }
+        writer.buf
+    }
+
+    /// Decodes the update previously encoded with `to_bytes`.
+    /// Returns None if data is malformed
+    pub fn from_bytes(bytes : &[u8]) -> Option<Update> {
+        let mut reader = Reader { bytes, pos : 0 };
+        // Dimensions
+        let size_x = reader.read_usize()?;
+        let size_y = reader.read_usize()?;
+        let num_cells = size_x.checked_mul(size_y)?;
+
+        // Runs
+        let num_runs = reader.read_usize()?;
+        let mut cells = Vec::new();
+        for _ in 0..num_runs {
+            let count = reader.read_usize()?;
+            let cell = reader.read_cell()?;
+            // Don't trust the count before it's validated against the grid size
+            if count > num_cells - cells.len() {
+                return None;
+            }
+            cells.extend(std::iter::repeat_n(cell, count));
+        }
+        let grid = Grid::from_shape_vec((size_x, size_y), cells).ok()?;
+
+        // Summaries
+        let num_players = reader.read_usize()?;
+        let mut players_summary = Vec::new();
+        for _ in 0..num_players {
+            let score = u32::try_from(reader.read_uint()?).ok()?;
+            let alive = match reader.read_byte()? {
+                0 => false,
+                1 => true,
+                _ => return None,
+            };
+            let multiplier = u32::try_from(reader.read_uint()?).ok()?;
+            let color = match reader.read_byte()? {
+                0 => PlayerColor::White,
+                1 => PlayerColor::Green,
+                2 => PlayerColor::Cyan,
+                3 => PlayerColor::Magenta,
+                4 => PlayerColor::Blue,
+                5 => PlayerColor::Yellow,
+                6 => PlayerColor::Red,
+                _ => return None,
+            };
+            let rgb = (reader.read_byte()?, reader.read_byte()?, reader.read_byte()?);
+            players_summary.push(PlayerSummary { score, alive, multiplier, color, rgb });
+        }
+
+        // Timing
+        let step = reader.read_uint()?;
+        let secs = reader.read_uint()?;
+        let nanos = u32::try_from(reader.read_uint()?).ok().filter(|nanos| *nanos < 1_000_000_000)?;
+        let elapsed = Duration::new(secs, nanos);
+
+        // Wrapped players
+        let num_wrapped = reader.read_usize()?;
+        let mut wrapped = Vec::new();
+        for _ in 0..num_wrapped {
+            wrapped.push(reader.read_usize()?);
+        }
+
+        // All data must be consumed
+        if reader.pos != bytes.len() {
+            return None;
+        }
+        Some(Update { grid, players_summary, step, elapsed, wrapped })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Makes small update with every kind of cell
+    fn make_update() -> Update {
+        let mut grid = Grid::from_elem((5, 4), GridCell::Empty);
+        grid[[1, 1]] = GridCell::Snake(SnakeRec{ player_index : 0, body_part : SnakeBodyPart::Head });
+        grid[[1, 2]] = GridCell::Snake(SnakeRec{ player_index : 0, body_part : SnakeBodyPart::Neck });
+        grid[[0, 2]] = GridCell::Snake(SnakeRec{ player_index : 0, body_part : SnakeBodyPart::Body });
+        grid[[0, 3]] = GridCell::Snake(SnakeRec{ player_index : 0, body_part : SnakeBodyPart::Tail });
+        grid[[4, 0]] = GridCell::Snake(SnakeRec{ player_index : 300, body_part : SnakeBodyPart::Head });
+        grid[[3, 3]] = GridCell::Pizza(PizzaRec{ kind : PizzaKind::Normal });
+        grid[[3, 2]] = GridCell::Pizza(PizzaRec{ kind : PizzaKind::Poison });
+        grid[[2, 0]] = GridCell::Hazard { active : false };
+        grid[[2, 1]] = GridCell::Hazard { active : true };
+        grid[[4, 3]] = GridCell::Wall;
+        grid[[4, 2]] = GridCell::Remains;
+        grid[[4, 1]] = GridCell::Ice;
+        Update {
+            grid,
+            players_summary : vec![
+                PlayerSummary { score : 1000, alive : true, multiplier : 3, color : PlayerColor::Red, rgb : (220, 30, 30) },
+                PlayerSummary { score : 0, alive : false, multiplier : 1, color : PlayerColor::Cyan, rgb : (1, 2, 3) },
+            ],
+            step : 12345,
+            elapsed : Duration::new(3_086, 250_123_457),
+            wrapped : vec![1],
+        }
+    }
+
+    // Test that decoding encoded update gives the same update
+    #[test]
+    fn test_update_round_trip() {
+        let update = make_update();
+        let bytes = update.to_bytes();
+        assert_eq!(Update::from_bytes(&bytes), Some(update));
+    }
+
+    // Test that the elapsed time survives the round trip exactly
+    #[test]
+    fn test_update_elapsed_exact() {
+        for elapsed in [Duration::ZERO, Duration::from_nanos(1), Duration::from_micros(1_500), Duration::MAX] {
+            let update = Update { elapsed, ..make_update() };
+            assert_eq!(Update::from_bytes(&update.to_bytes()).unwrap().elapsed, elapsed);
+        }
+        // Nanoseconds past the whole second are rejected
+        let update = Update { elapsed : Duration::ZERO, wrapped : Vec::new(), ..make_update() };
+        let mut bytes = update.to_bytes();
+        assert_eq!(bytes[bytes.len() - 3..], [0, 0, 0]);
+        bytes.truncate(bytes.len() - 2);
+        let mut writer = Writer { buf : bytes };
+        writer.write_uint(1_000_000_000);
+        writer.write_uint(0);
+        assert_eq!(Update::from_bytes(&writer.buf), None);
+    }
+
+    // Test that mostly empty grid is encoded compactly
+    #[test]
+    fn test_update_compact() {
+        let mut grid = Grid::from_elem((100, 100), GridCell::Empty);
+        grid[[50, 50]] = GridCell::Pizza(PizzaRec{ kind : PizzaKind::Normal });
+        let update = Update {
+            grid,
+            players_summary : vec![PlayerSummary { score : 3, alive : true, multiplier : 1, color : PlayerColor::White, rgb : (255, 255, 255) }],
+            step : 7,
+            elapsed : Duration::from_millis(1750),
+            wrapped : Vec::new(),
+        };
+        let bytes = update.to_bytes();
+        assert!(bytes.len() < 32, "Encoded into {} bytes", bytes.len());
+        assert_eq!(Update::from_bytes(&bytes), Some(update));
+    }
+
+    // Test that malformed data is rejected
+    #[test]
+    fn test_update_malformed() {
+        let bytes = make_update().to_bytes();
+        // Truncated data
+        assert_eq!(Update::from_bytes(&bytes[..bytes.len() - 1]), None);
+        // Trailing data
+        let mut extended = bytes.clone();
+        extended.push(0);
+        assert_eq!(Update::from_bytes(&extended), None);
+        // Empty data
+        assert_eq!(Update::from_bytes(&[]), None);
+    }
+}
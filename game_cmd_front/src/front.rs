@@ -1,14 +1,96 @@
-use console_engine::crossterm::event::KeyEvent;
-use game_backend::game::GlobalUpdateRx;
+use game_backend::game::{GlobalUpdateRx, GameCommand, GameCommandTx, UPDATE_INTERVAL};
+use game_backend::config::GameEndCondition;
 use game_backend::player::UserControlTx;
-use game_backend::base::Direction;
+use game_backend::base::{Direction, PlayerColor, Vector2i};
 use game_backend::grid;
+use game_backend::grid::GridAccess;
 use game_backend::events;
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::option::Option;
+use std::time::{Duration, Instant};
 use console_engine::*;
 use console_engine::events::*;
 
-const ASPECT_RATIO : i32 = 3;
+// Position of the field on the screen, in cells. Leaves a row for the title bar
+const FIELD_OFFSET_X : i32 = 1;
+const FIELD_OFFSET_Y : i32 = 2;
+// Gap between the field border and the scoreboard, in characters
+const SCOREBOARD_MARGIN : i32 = 2;
+// The game plays itself after this long without any key pressed
+const DEMO_IDLE_TIMEOUT : Duration = Duration::from_secs(30);
+// Largest size of the mini-map, in cells. Fits the screen with the default aspect ratio
+const MINI_MAP_WIDTH : usize = 30;
+const MINI_MAP_HEIGHT : usize = 25;
+
+/// Mapping of the keys to the player actions.
+/// Default uses arrow keys, P to pause, R to resume, Space to step and ESC to quit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBindings
+{
+    pub directions : HashMap<KeyCode, Direction>,
+    pub commands : HashMap<KeyCode, GameCommand>,
+    pub quit : KeyCode,
+}
+
+impl Default for KeyBindings {
+    fn default() -> KeyBindings {
+        KeyBindings {
+            directions : HashMap::from([
+                (KeyCode::Left, Direction::MinusX),
+                (KeyCode::Right, Direction::PlusX),
+                (KeyCode::Up, Direction::MinusY),
+                (KeyCode::Down, Direction::PlusY),
+            ]),
+            commands : HashMap::from([
+                (KeyCode::Char('p'), GameCommand::Pause),
+                (KeyCode::Char('P'), GameCommand::Pause),
+                (KeyCode::Char('r'), GameCommand::Resume),
+                (KeyCode::Char('R'), GameCommand::Resume),
+                (KeyCode::Char(' '), GameCommand::StepOnce),
+            ]),
+            quit : KeyCode::Esc,
+        }
+    }
+}
+
+/// Style of the field border
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BorderKind
+{
+    Single,
+    Double,
+    Rounded,
+}
+
+/// Frontend options. Start from `FrontOptions::default()` and
+/// override the fields that matter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrontOptions
+{
+    /// Number of characters used for the width of a single cell.
+    /// Compensates for terminal characters being taller than wide
+    pub aspect_ratio : i32,
+    pub key_bindings : KeyBindings,
+    pub border : BorderKind,
+    /// Color of the empty cells. None lets the terminal background show through
+    pub background : Option<Color>,
+    /// End conditions of the played game. The title bar shows the match mode
+    /// and the time left by them
+    pub end_conditions : Vec<GameEndCondition>,
+}
+
+impl Default for FrontOptions {
+    fn default() -> FrontOptions {
+        FrontOptions {
+            aspect_ratio : 3,
+            key_bindings : KeyBindings::default(),
+            border : BorderKind::Double,
+            background : None,
+            end_conditions : Vec::new(),
+        }
+    }
+}
 
 /// Front represents a frontend object.
 pub struct Front {
@@ -16,114 +98,382 @@ pub struct Front {
     user_control_tx: UserControlTx,
     // Global update channel
     global_update_rx: GlobalUpdateRx,
+    // Game command channel
+    game_command_tx: GameCommandTx,
     // Console engine for rendering
     engine: ConsoleEngine,
+    // Number of characters per cell width
+    aspect_ratio: i32,
+    // Keys for directions and commands
+    key_bindings: KeyBindings,
+    // Field border style and the color of empty cells
+    border: BorderKind,
+    background: Option<Color>,
+    // End conditions of the game, for the title bar
+    end_conditions: Vec<GameEndCondition>,
 
     // Last recieved grid. Optional
     last_grid: Option<grid::Grid>,
     // Vector of last player infos
     last_player_summary: Vec<events::PlayerSummary>,
+    // Step number and elapsed time from the last update
+    last_step: u64,
+    last_elapsed: Duration,
+    // Last start countdown value. Cleared once the snakes are on the move
+    countdown: Option<u32>,
+    // Whether the title bar is shown
+    show_title: bool,
+    // Whether the field is downscaled to fit the screen
+    mini_map: bool,
+    // The grid currently on the screen and the colors it was drawn with.
+    // Only the cells changed since then are redrawn
+    drawn_grid: Option<grid::Grid>,
+    drawn_colors: Vec<PlayerColor>,
+    // Whether the whole screen must be redrawn on the next frame
+    redraw: bool,
+    // The moment of the last key press and whether the game plays itself since then
+    last_key_time: Instant,
+    demo: bool,
 }
 
 // Impl for Front
 impl Front {
-    /// Creates a new Front object.
+    /// Creates a new Front object with default options.
     pub fn new( user_control_tx: UserControlTx,
-                global_update_rx: GlobalUpdateRx)
+                global_update_rx: GlobalUpdateRx,
+                game_command_tx: GameCommandTx)
+                -> Front {
+        Front::with_options(user_control_tx, global_update_rx, game_command_tx, FrontOptions::default())
+    }
+
+    /// Creates a new Front object with given options.
+    pub fn with_options( user_control_tx: UserControlTx,
+                global_update_rx: GlobalUpdateRx,
+                game_command_tx: GameCommandTx,
+                options: FrontOptions)
                 -> Front {
 
         let engine = ConsoleEngine::init(120, 30, 10).unwrap();
 
         Front { user_control_tx,
                 global_update_rx,
+                game_command_tx,
                 engine,
+                aspect_ratio: options.aspect_ratio,
+                key_bindings: options.key_bindings,
+                border: options.border,
+                background: options.background,
+                end_conditions: options.end_conditions,
                 last_grid: None,
                 last_player_summary: Vec::new(),
+                last_step: 0,
+                last_elapsed: Duration::ZERO,
+                countdown: None,
+                show_title: true,
+                mini_map: false,
+                drawn_grid: None,
+                drawn_colors: Vec::new(),
+                redraw: true,
+                last_key_time: Instant::now(),
+                demo: false,
         }
     }
 
     /// Function that is drawing a boder for field. Accepts position and size
-    fn draw_border(engine : &mut ConsoleEngine, x: i32, y: i32, width: i32, height: i32) {
-        let x1 = x * ASPECT_RATIO - 1;
+    fn draw_border(engine : &mut ConsoleEngine, aspect_ratio: i32, border: BorderKind, x: i32, y: i32, width: i32, height: i32) {
+        let (x1, y1, x2, y2) = Self::border_rect(aspect_ratio, x, y, width, height);
+
+        let [horizontal, vertical, top_left, top_right, bottom_left, bottom_right] = Self::border_glyphs(border);
+        engine.fill_rect(x1, y1, x2, y1, pixel::pxl(horizontal));
+        engine.fill_rect(x1, y2, x2, y2, pixel::pxl(horizontal));
+        engine.fill_rect(x1, y1, x1, y2, pixel::pxl(vertical));
+        engine.fill_rect(x2, y1, x2, y2, pixel::pxl(vertical));
+        engine.set_pxl(x1, y1, pixel::pxl(top_left));
+        engine.set_pxl(x2, y1, pixel::pxl(top_right));
+        engine.set_pxl(x1, y2, pixel::pxl(bottom_left));
+        engine.set_pxl(x2, y2, pixel::pxl(bottom_right));
+    }
+
+    /// Function that returns the border glyphs: horizontal and vertical lines, then
+    /// top left, top right, bottom left and bottom right corners
+    fn border_glyphs(border: BorderKind) -> [char; 6] {
+        match border {
+            BorderKind::Single => ['─', '│', '┌', '┐', '└', '┘'],
+            BorderKind::Double => ['═', '║', '╔', '╗', '╚', '╝'],
+            BorderKind::Rounded => ['─', '│', '╭', '╮', '╰', '╯'],
+        }
+    }
+
+    /// Function that returns the pixel of the empty cell
+    fn empty_pixel(background: Option<Color>) -> pixel::Pixel {
+        match background {
+            Some(color) => pixel::pxl_bg(' ', color),
+            None => pixel::pxl(' '),
+        }
+    }
+
+    /// Function that calculates the screen rectangle of the field border.
+    /// Accepts field position and size in cells
+    fn border_rect(aspect_ratio: i32, x: i32, y: i32, width: i32, height: i32) -> (i32, i32, i32, i32) {
+        let x1 = x * aspect_ratio - 1;
         let y1 = y- 1;
-        let x2 = x1 + width * ASPECT_RATIO + 1 - 1;
-        let y2 = y1 + height + 1 - 1;
+        // The border goes right after the last cell
+        let x2 = x1 + width * aspect_ratio + 1;
+        let y2 = y1 + height + 1;
+        (x1, y1, x2, y2)
+    }
+
+    /// Function that calculates the screen position of the scoreboard.
+    /// Scoreboard is placed to the right of the field border. Accepts grid size
+    fn scoreboard_origin(aspect_ratio: i32, grid_width: i32, grid_height: i32) -> (i32, i32) {
+        let (_, y1, x2, _) = Self::border_rect(aspect_ratio, FIELD_OFFSET_X, FIELD_OFFSET_Y, grid_width, grid_height);
+        (x2 + 1 + SCOREBOARD_MARGIN, y1 + 1)
+    }
+
+    /// Function that calculates the screen position of the title bar.
+    /// Title bar is placed right above the field border
+    fn title_origin(aspect_ratio: i32) -> (i32, i32) {
+        let (x1, y1, _, _) = Self::border_rect(aspect_ratio, FIELD_OFFSET_X, FIELD_OFFSET_Y, 0, 0);
+        (x1, y1 - 1)
+    }
 
-        let border_style = console_engine::rect_style::BorderStyle::new_double();
-        engine.rect_border(x1, y1, x2, y2, border_style);
+    /// Function that makes the title bar text. Accepts end conditions, step, elapsed time and player summaries.
+    /// The time left is shown with a step limit
+    fn title_text(end_conditions: &[GameEndCondition], step: u64, elapsed: Duration,
+                  summaries: &[events::PlayerSummary], countdown: Option<u32>) -> String {
+        let alive = summaries.iter().filter(|summary| summary.alive).count();
+        let mut text = format!("{}  Step {}  Time {}", Self::mode_text(end_conditions), step, Self::clock_text(elapsed));
+        let step_limit = end_conditions.iter()
+            .filter_map(|condition| match condition {
+                GameEndCondition::StepLimit(limit) => Some(*limit),
+                _ => None,
+            })
+            .min();
+        if let Some(limit) = step_limit {
+            let steps_left = u32::try_from(limit.saturating_sub(step)).unwrap_or(u32::MAX);
+            text += &format!("  Left {}", Self::clock_text(UPDATE_INTERVAL * steps_left));
+        }
+        text += &format!("  Alive {}/{}", alive, summaries.len());
+        match countdown {
+            Some(0) => format!("{}  GO", text),
+            Some(remaining) => format!("{}  {}...", text, remaining),
+            None => text,
+        }
     }
 
-    /// Function that is drawing snake head
-    fn draw_snake_head(engine : &mut ConsoleEngine, x: i32, y: i32) {
-        let x1 = x * ASPECT_RATIO;
+    /// Function that makes the match mode text from the end conditions
+    fn mode_text(end_conditions: &[GameEndCondition]) -> String {
+        if end_conditions.is_empty() {
+            return "Free play".to_string();
+        }
+        end_conditions.iter()
+            .map(|condition| match condition {
+                GameEndCondition::LastStanding => "Last standing".to_string(),
+                GameEndCondition::ScoreTarget(score) => format!("Score {}", score),
+                GameEndCondition::StepLimit(limit) => format!("{} steps", limit),
+                GameEndCondition::BoardCleared => "Clear the board".to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Function that formats the time as minutes and seconds
+    fn clock_text(time: Duration) -> String {
+        let seconds = time.as_secs();
+        format!("{:02}:{:02}", seconds / 60, seconds % 60)
+    }
+
+    /// Function that calculates the screen rectangle of the cell. Accepts cell position
+    fn cell_rect(aspect_ratio: i32, x: i32, y: i32) -> (i32, i32, i32, i32) {
+        let x1 = x * aspect_ratio;
         let y1 = y;
-        let x2 = x1 + ASPECT_RATIO - 1;
+        let x2 = x1 + aspect_ratio - 1;
         let y2 = y1;
-        engine.rect(x1, y1, x2, y2, pixel::pxl_bg(' ', Color::Red));
+        (x1, y1, x2, y2)
+    }
+
+    /// Function that is drawing snake head. The glyph tells the head from the body
+    fn draw_snake_head(engine : &mut ConsoleEngine, aspect_ratio: i32, color: Color, x: i32, y: i32) {
+        let (x1, y1, x2, y2) = Self::cell_rect(aspect_ratio, x, y);
+        engine.rect(x1, y1, x2, y2, pixel::pxl_bg(' ', color));
+        engine.set_pxl(x1 + aspect_ratio / 2, y1, pixel::pxl_fbg('@', Color::Black, color));
     }
     /// Function for drawing snake body
-    fn draw_snake_body(engine : &mut ConsoleEngine, x: i32, y: i32) {
-        let x1 = x * ASPECT_RATIO;
-        let y1 = y;
-        let x2 = x1 + ASPECT_RATIO - 1;
-        let y2 = y1;
-        engine.rect(x1, y1, x2, y2, pixel::pxl_bg(' ', Color::White));
+    fn draw_snake_body(engine : &mut ConsoleEngine, aspect_ratio: i32, color: Color, x: i32, y: i32) {
+        let (x1, y1, x2, y2) = Self::cell_rect(aspect_ratio, x, y);
+        engine.rect(x1, y1, x2, y2, pixel::pxl_bg(' ', color));
     }
-    /// Function for drawing pizza
-    fn draw_pizza(engine : &mut ConsoleEngine, x: i32, y: i32) {
-        let x1 = x * ASPECT_RATIO;
-        let y1 = y;
-        let x2 = x1 + ASPECT_RATIO - 1;
-        let y2 = y1;
-        engine.rect(x1, y1, x2, y2, pixel::pxl_bg(' ', Color::Yellow));
+    /// Function that returns the screen color of the player color
+    fn player_color(color: PlayerColor) -> Color {
+        match color {
+            PlayerColor::White => Color::White,
+            PlayerColor::Green => Color::Green,
+            PlayerColor::Cyan => Color::Cyan,
+            PlayerColor::Magenta => Color::Magenta,
+            PlayerColor::Blue => Color::Blue,
+            PlayerColor::Yellow => Color::Yellow,
+            PlayerColor::Red => Color::Red,
+        }
+    }
+    /// Function for drawing pizza. Color and glyph depend on pizza kind
+    fn draw_pizza(engine : &mut ConsoleEngine, aspect_ratio: i32, pizza_rec: &grid::PizzaRec, x: i32, y: i32) {
+        let (x1, y1, _, _) = Self::cell_rect(aspect_ratio, x, y);
+        Self::draw_pizza_at(engine, aspect_ratio, pizza_rec.kind, x1, y1);
+    }
+
+    /// Function for drawing pizza at given screen position, in characters
+    fn draw_pizza_at(engine : &mut ConsoleEngine, aspect_ratio: i32, kind: grid::PizzaKind, x: i32, y: i32) {
+        let (color, _) = Self::pizza_style(kind);
+        engine.rect(x, y, x + aspect_ratio - 1, y, pixel::pxl_bg(' ', color));
+        // Label in the cell center tells the kind and value without relying on color
+        if let Some(label) = Self::pizza_label(kind) {
+            let start = x + (aspect_ratio - label.len() as i32).max(0) / 2;
+            for (i, glyph) in label.chars().take(aspect_ratio as usize).enumerate() {
+                engine.set_pxl(start + i as i32, y, pixel::pxl_fbg(glyph, Color::Black, color));
+            }
+        }
+    }
+
+    /// Function for drawing hazard. Warning and active hazards look differently
+    fn draw_hazard(engine : &mut ConsoleEngine, aspect_ratio: i32, active: bool, x: i32, y: i32) {
+        let (x1, y1, x2, y2) = Self::cell_rect(aspect_ratio, x, y);
+        let (color, glyph) = Self::hazard_style(active);
+        engine.rect(x1, y1, x2, y2, pixel::pxl_bg(' ', color));
+        engine.set_pxl(x1 + aspect_ratio / 2, y1, pixel::pxl_fbg(glyph, Color::Black, color));
+    }
+
+    /// Function for drawing wall
+    fn draw_wall(engine : &mut ConsoleEngine, aspect_ratio: i32, x: i32, y: i32) {
+        let (x1, y1, x2, y2) = Self::cell_rect(aspect_ratio, x, y);
+        engine.rect(x1, y1, x2, y2, pixel::pxl_bg(' ', Color::DarkGrey));
+    }
+
+    /// Function for drawing the body of the dead snake
+    fn draw_remains(engine : &mut ConsoleEngine, aspect_ratio: i32, x: i32, y: i32) {
+        let (x1, y1, x2, y2) = Self::cell_rect(aspect_ratio, x, y);
+        engine.rect(x1, y1, x2, y2, pixel::pxl_fbg('~', Color::DarkGrey, Color::Grey));
+    }
+
+    /// Function for drawing ice
+    fn draw_ice(engine : &mut ConsoleEngine, aspect_ratio: i32, x: i32, y: i32) {
+        let (x1, y1, x2, y2) = Self::cell_rect(aspect_ratio, x, y);
+        engine.rect(x1, y1, x2, y2, pixel::pxl_fbg('=', Color::White, Color::DarkCyan));
+    }
+
+    /// Function that returns background color and glyph for hazard state
+    fn hazard_style(active: bool) -> (Color, char) {
+        if active {
+            (Color::DarkRed, 'X')
+        }
+        else {
+            (Color::DarkYellow, '!')
+        }
+    }
+
+    /// Function that returns background color and optional glyph for pizza kind
+    fn pizza_style(kind: grid::PizzaKind) -> (Color, Option<char>) {
+        match kind {
+            grid::PizzaKind::Normal => (Color::Yellow, None),
+            grid::PizzaKind::Golden => (Color::Yellow, Some('$')),
+            grid::PizzaKind::Speed => (Color::Cyan, Some('>')),
+            grid::PizzaKind::Poison => (Color::Magenta, Some('x')),
+        }
+    }
+
+    /// Function that returns the label of the pizza cell: the kind glyph followed by the value.
+    /// Normal pizza is the most common, so it's left unlabeled
+    fn pizza_label(kind: grid::PizzaKind) -> Option<String> {
+        let (_, glyph) = Self::pizza_style(kind);
+        glyph.map(|glyph| format!("{}{}", glyph, kind.value()))
+    }
+
+    /// Function that returns the legend line of the pizza kind
+    fn pizza_legend_text(kind: grid::PizzaKind) -> String {
+        format!("{:?} {}", kind, kind.value())
     }
 
     /// Draw a summary for specified player. Accepts summary object and position
     fn draw_player_summary(engine : &mut ConsoleEngine, summary: &events::PlayerSummary, player_index: i32, x: i32, y: i32) {
-        let text =format!("Player {}: {}", player_index, summary.score);
+        let mut text =format!("Player {}: {}", player_index, summary.score);
+        // Show the combo multiplier only when it matters
+        if summary.multiplier > 1 {
+            text += &format!(" x{}", summary.multiplier);
+        }
         engine.print(x, y, &text);
     }
    
-    // Function that is drawing the entire grid
-    fn draw_grid(&mut self) {
-        // If there is no grid, return
-        if self.last_grid.is_none() {
-            return;
-        }
-        // Get the grid
-        let grid = self.last_grid.as_ref().unwrap();
-        let offset_x = 1;
-        let offset_y = 1;
-        // First draw the border based on the grid size
-        Self::draw_border(&mut self.engine, offset_x, offset_y, grid.dim().0 as i32, grid.dim().1 as i32);
+    /// Returns the grid to draw. Mini-map mode downscales the last grid
+    fn shown_grid(last_grid: Option<&grid::Grid>, mini_map: bool) -> Option<Cow<'_, grid::Grid>> {
+        let grid = last_grid?;
+        if mini_map {
+            Some(Cow::Owned(grid::downscale(grid, MINI_MAP_WIDTH, MINI_MAP_HEIGHT)))
+        }
+        else {
+            Some(Cow::Borrowed(grid))
+        }
+    }
+
+    /// Returns the cells to redraw. All cells are dirty if the grid is drawn from scratch
+    fn dirty_cells(grid : &grid::Grid, drawn_grid : Option<&grid::Grid>) -> Vec<(Vector2i, grid::GridCell)> {
+        match drawn_grid {
+            Some(drawn_grid) => grid.changed_cells_since(drawn_grid),
+            None => grid.changed_cells_since(&grid::new_grid(0, 0)),
+        }
+    }
+
+    // Function that is drawing the given cells of the grid
+    fn draw_grid(engine : &mut ConsoleEngine, aspect_ratio: i32, background: Option<Color>,
+                 dirty : &[(Vector2i, grid::GridCell)], summaries: &[events::PlayerSummary]) {
+        let offset_x = FIELD_OFFSET_X;
+        let offset_y = FIELD_OFFSET_Y;
 
         // Draw grid cells
-        for y in 0..grid.dim().1 {
-            for x in 0..grid.dim().0 {
-                // Get the cell
-                let cell = grid[[x, y]];
-                // Match the cell type
-                match cell {
-                    // If it's empty, do nothing
-                    grid::GridCell::Empty => {}
-                    // If it's a snake, draw it
-                    grid::GridCell::Snake(snake_rec) => {
-                        // Match the snake body part
-                        match snake_rec.body_part {
-                            // If it's a head, draw it
-                            grid::SnakeBodyPart::Head => {
-                                Self::draw_snake_head(&mut self.engine, x as i32 + offset_x, y as i32 + offset_y);
-                            }
-                            // Body or tail
-                            _ => {
-                                Self::draw_snake_body(&mut self.engine, x as i32 + offset_x, y as i32 + offset_y);
-                            }
+        for (position, cell) in dirty {
+            let (x, y) = (position.x, position.y);
+            // Match the cell type
+            match *cell {
+                // If it's empty, erase whatever was there
+                grid::GridCell::Empty => {
+                    let (x1, y1, x2, y2) = Self::cell_rect(aspect_ratio, x + offset_x, y + offset_y);
+                    engine.fill_rect(x1, y1, x2, y2, Self::empty_pixel(background));
+                }
+                // If it's a snake, draw it
+                grid::GridCell::Snake(snake_rec) => {
+                    // Players without summary yet get the first color
+                    let color = summaries.get(snake_rec.player_index)
+                        .map_or(PlayerColor::White, |summary| summary.color);
+                    let color = Self::player_color(color);
+                    // Match the snake body part
+                    match snake_rec.body_part {
+                        // If it's a head, draw it
+                        grid::SnakeBodyPart::Head => {
+                            Self::draw_snake_head(engine, aspect_ratio, color, x + offset_x, y + offset_y);
+                        }
+                        // Neck, body or tail
+                        _ => {
+                            Self::draw_snake_body(engine, aspect_ratio, color, x + offset_x, y + offset_y);
                         }
                     }
-                    // If it's a pizza, draw it
-                    grid::GridCell::Pizza(_pizza_rec) => {
-                        Self::draw_pizza(&mut self.engine, x as i32 + offset_x, y as i32 + offset_y);
-                    }
+                }
+                // If it's a pizza, draw it
+                grid::GridCell::Pizza(pizza_rec) => {
+                    Self::draw_pizza(engine, aspect_ratio, &pizza_rec, x + offset_x, y + offset_y);
+                }
+                // If it's a wall, draw it
+                grid::GridCell::Wall => {
+                    Self::draw_wall(engine, aspect_ratio, x + offset_x, y + offset_y);
+                }
+                // If it's a hazard, draw it
+                grid::GridCell::Hazard { active } => {
+                    Self::draw_hazard(engine, aspect_ratio, active, x + offset_x, y + offset_y);
+                }
+                // If it's a body of the dead snake, draw it
+                grid::GridCell::Remains => {
+                    Self::draw_remains(engine, aspect_ratio, x + offset_x, y + offset_y);
+                }
+                // If it's ice, draw it
+                grid::GridCell::Ice => {
+                    Self::draw_ice(engine, aspect_ratio, x + offset_x, y + offset_y);
                 }
             }
         }
@@ -131,8 +481,6 @@ impl Front {
 
     /// Function to handle frame update
     fn handle_frame(&mut self) {
-        // Clear the screen
-        self.engine.clear_screen();
         // Read the global update channel
         if let Ok(global_update) = self.global_update_rx.try_recv() {
             // Match the global update message type
@@ -147,15 +495,71 @@ impl Front {
                     self.last_grid = Some(update.grid);
                     // Remember player infos
                     self.last_player_summary = update.players_summary;
+                    // Remember timing
+                    self.last_step = update.step;
+                    self.last_elapsed = update.elapsed;
+                    // GO is shown for the first step only
+                    if update.step > 1 {
+                        self.countdown = None;
+                    }
+                }
+                // Remember the countdown to show it in the title
+                events::GlobalEvent::CountdownTick(tick) => {
+                    self.countdown = Some(tick.remaining);
                 }
+                // Other events are not rendered
+                _ => {}
             }
         }
-        // Render
-        self.draw_grid();
-        // Draw player summary
+        // Render. The whole screen is redrawn if the layout or the colors change
+        let shown_grid = Self::shown_grid(self.last_grid.as_ref(), self.mini_map);
+        let colors : Vec<PlayerColor> = self.last_player_summary.iter().map(|summary| summary.color).collect();
+        let same_layout = match (&shown_grid, &self.drawn_grid) {
+            (Some(grid), Some(drawn_grid)) => grid.dim() == drawn_grid.dim(),
+            (None, None) => true,
+            _ => false,
+        };
+        if self.redraw || !same_layout || colors != self.drawn_colors {
+            self.engine.clear_screen();
+            self.drawn_grid = None;
+            self.redraw = false;
+            if let Some(grid) = &shown_grid {
+                Self::draw_border(&mut self.engine, self.aspect_ratio, self.border, FIELD_OFFSET_X, FIELD_OFFSET_Y,
+                    grid.width() as i32, grid.height() as i32);
+            }
+        }
+        let screen_width = self.engine.get_width() as i32;
+        if self.show_title {
+            let (title_x, title_y) = Self::title_origin(self.aspect_ratio);
+            let text = Self::title_text(&self.end_conditions, self.last_step, self.last_elapsed, &self.last_player_summary, self.countdown);
+            self.engine.fill_rect(title_x, title_y, screen_width - 1, title_y, pixel::pxl(' '));
+            self.engine.print(title_x, title_y, &text);
+        }
+        if let Some(grid) = &shown_grid {
+            let dirty = Self::dirty_cells(grid, self.drawn_grid.as_ref());
+            Self::draw_grid(&mut self.engine, self.aspect_ratio, self.background, &dirty, &self.last_player_summary);
+        }
+        // Draw player summary next to the field
+        let (grid_width, grid_height) = match &shown_grid {
+            Some(grid) => (grid.width() as i32, grid.height() as i32),
+            None => (0, 0),
+        };
+        let (scoreboard_x, scoreboard_y) = Self::scoreboard_origin(self.aspect_ratio, grid_width, grid_height);
         for (i, summary) in self.last_player_summary.iter().enumerate() {
-            Self::draw_player_summary(&mut self.engine, summary, i as i32 + 1, 80, 2 + i as i32);
+            let y = scoreboard_y + i as i32;
+            self.engine.fill_rect(scoreboard_x, y, screen_width - 1, y, pixel::pxl(' '));
+            Self::draw_player_summary(&mut self.engine, summary, i as i32 + 1, scoreboard_x, y);
+        }
+        // Draw pizza values under the scoreboard
+        let legend_y = scoreboard_y + self.last_player_summary.len() as i32 + 1;
+        for (i, kind) in grid::PizzaKind::all().into_iter().enumerate() {
+            let y = legend_y + i as i32;
+            self.engine.fill_rect(scoreboard_x, y, screen_width - 1, y, pixel::pxl(' '));
+            Self::draw_pizza_at(&mut self.engine, self.aspect_ratio, kind, scoreboard_x, y);
+            self.engine.print(scoreboard_x + self.aspect_ratio + 1, y, &Self::pizza_legend_text(kind));
         }
+        self.drawn_grid = shown_grid.map(Cow::into_owned);
+        self.drawn_colors = colors;
 
         self.engine.draw();
     }
@@ -169,21 +573,52 @@ impl Front {
             match self.engine.poll() {
                 // A frame has passed
                 Event::Frame => {
+                    // Start the demo if nobody plays
+                    if !self.demo && self.last_key_time.elapsed() > DEMO_IDLE_TIMEOUT {
+                        self.demo = true;
+                        let _ = self.game_command_tx.send(GameCommand::StartDemo);
+                    }
                     // Handle frame
                     self.handle_frame();
                 }
         
                 // A Key has been pressed
                 Event::Key(keyevent) => {
-                    // Exit if ESC
-                    if keyevent.code == console_engine::KeyCode::Esc {
+                    // Any key returns the control to the player
+                    self.last_key_time = Instant::now();
+                    if self.demo {
+                        self.demo = false;
+                        let _ = self.game_command_tx.send(GameCommand::StopDemo);
+                    }
+                    // Exit if quit key
+                    if keyevent.code == self.key_bindings.quit {
                         break;
                     }    
+                    // Toggle the title bar if T
+                    if let KeyCode::Char('t') | KeyCode::Char('T') = keyevent.code {
+                        self.show_title = !self.show_title;
+                        self.redraw = true;
+                    }
+                    // Toggle the mini-map if M
+                    if let KeyCode::Char('m') | KeyCode::Char('M') = keyevent.code {
+                        self.mini_map = !self.mini_map;
+                        self.redraw = true;
+                    }
+                    // Debug aid: dump the board to stderr if G. Redirect stderr
+                    // to a file to capture the exact state for bug reports
+                    if let KeyCode::Char('g') | KeyCode::Char('G') = keyevent.code {
+                        self.dump_grid();
+                    }
                     // Read direction input
-                    if let Some(direction) = Self::key_to_direction(keyevent.code) {
+                    if let Some(direction) = Self::key_to_direction(&self.key_bindings, keyevent.code) {
                         // Send to user, ignore errors
                         let _ = self.user_control_tx.send(direction);
                     }
+                    // Read game command input
+                    if let Some(command) = Self::key_to_command(&self.key_bindings, keyevent.code) {
+                        // Send to game, ignore errors
+                        let _ = self.game_command_tx.send(command);
+                    }
                 }
         
                 // Mouse has been moved or clicked
@@ -193,15 +628,184 @@ impl Front {
         
     }
 
-    /// Function that converts key code into direction
+    /// Writes the last received grid to stderr as text. Doesn't touch the screen
+    fn dump_grid(&self) {
+        if let Some(grid) = &self.last_grid {
+            eprintln!("Step {}\n{}", self.last_step, grid::to_ascii(grid));
+        }
+    }
+
+    /// Function that converts key code into direction using given bindings
     /// Returns None if no direction is pressed
-    fn key_to_direction(key: KeyCode) -> Option<Direction> {
-        match key {
-            KeyCode::Left => Some(Direction::MinusX),
-            KeyCode::Right => Some(Direction::PlusX),
-            KeyCode::Up => Some(Direction::MinusY),
-            KeyCode::Down => Some(Direction::PlusY),
-            _ => None,
+    fn key_to_direction(key_bindings: &KeyBindings, key: KeyCode) -> Option<Direction> {
+        key_bindings.directions.get(&key).copied()
+    }
+
+    /// Function that converts key code into game command using given bindings
+    /// Returns None if no command is pressed
+    fn key_to_command(key_bindings: &KeyBindings, key: KeyCode) -> Option<GameCommand> {
+        key_bindings.commands.get(&key).copied()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test pizza kinds are rendered distinctly
+    #[test]
+    fn test_pizza_style() {
+        // Normal pizza keeps plain yellow
+        assert_eq!(Front::pizza_style(grid::PizzaKind::Normal), (Color::Yellow, None));
+        // Special kinds have glyphs
+        assert_eq!(Front::pizza_style(grid::PizzaKind::Golden), (Color::Yellow, Some('$')));
+        assert_eq!(Front::pizza_style(grid::PizzaKind::Speed), (Color::Cyan, Some('>')));
+        assert_eq!(Front::pizza_style(grid::PizzaKind::Poison), (Color::Magenta, Some('x')));
+    }
+
+    // Test only special pizzas are labeled with their values, and the legend lists all kinds
+    #[test]
+    fn test_pizza_label() {
+        assert_eq!(Front::pizza_label(grid::PizzaKind::Normal), None);
+        assert_eq!(Front::pizza_label(grid::PizzaKind::Golden), Some("$5".to_string()));
+        assert_eq!(Front::pizza_label(grid::PizzaKind::Speed), Some(">2".to_string()));
+        assert_eq!(Front::pizza_label(grid::PizzaKind::Poison), Some("x0".to_string()));
+        // Labels fit the default cell width
+        let aspect_ratio = FrontOptions::default().aspect_ratio as usize;
+        for kind in grid::PizzaKind::all() {
+            assert!(Front::pizza_label(kind).map_or(0, |label| label.len()) <= aspect_ratio);
+        }
+        assert_eq!(Front::pizza_legend_text(grid::PizzaKind::Normal), "Normal 1");
+        assert_eq!(Front::pizza_legend_text(grid::PizzaKind::Golden), "Golden 5");
+    }
+
+    // Test warning and active hazards are rendered distinctly
+    #[test]
+    fn test_hazard_style() {
+        assert_ne!(Front::hazard_style(false), Front::hazard_style(true));
+        assert_eq!(Front::hazard_style(true), (Color::DarkRed, 'X'));
+    }
+
+    // Test scoreboard never overlaps the field border
+    #[test]
+    fn test_scoreboard_origin() {
+        let aspect_ratio = FrontOptions::default().aspect_ratio;
+        for (width, height) in [(0, 0), (5, 5), (20, 20), (40, 10)] {
+            let (_, y1, x2, _) = Front::border_rect(aspect_ratio, FIELD_OFFSET_X, FIELD_OFFSET_Y, width, height);
+            let (x, y) = Front::scoreboard_origin(aspect_ratio, width, height);
+            assert!(x > x2, "Scoreboard overlaps the border for {}x{}", width, height);
+            assert_eq!(y, y1 + 1);
         }
+        // 20 cells wide field: border ends at 63
+        assert_eq!(Front::scoreboard_origin(aspect_ratio, 20, 20), (66, 2));
+    }
+
+    // Test title bar is above the field border and shows match info
+    #[test]
+    fn test_title_bar() {
+        let aspect_ratio = FrontOptions::default().aspect_ratio;
+        let (_, y1, _, _) = Front::border_rect(aspect_ratio, FIELD_OFFSET_X, FIELD_OFFSET_Y, 20, 20);
+        let (x, y) = Front::title_origin(aspect_ratio);
+        assert!(y >= 0 && y < y1, "Title bar overlaps the border");
+        assert!(x >= 0);
+
+        let summaries = [
+            events::PlayerSummary { score : 3, alive : true, multiplier : 1, color : PlayerColor::White, rgb : (255, 255, 255) },
+            events::PlayerSummary { score : 1, alive : false, multiplier : 1, color : PlayerColor::Green, rgb : (0, 200, 0) },
+        ];
+        assert_eq!(Front::title_text(&[], 42, Duration::from_secs(75), &summaries, None),
+            "Free play  Step 42  Time 01:15  Alive 1/2");
+        assert_eq!(Front::title_text(&[], 0, Duration::ZERO, &summaries, Some(3)),
+            "Free play  Step 0  Time 00:00  Alive 1/2  3...");
+        assert_eq!(Front::title_text(&[], 1, Duration::ZERO, &summaries, Some(0)),
+            "Free play  Step 1  Time 00:00  Alive 1/2  GO");
+        // Time left is counted from the step limit
+        let end_conditions = [GameEndCondition::LastStanding, GameEndCondition::StepLimit(400)];
+        assert_eq!(Front::title_text(&end_conditions, 160, Duration::from_secs(41), &summaries, None),
+            "Last standing, 400 steps  Step 160  Time 00:41  Left 01:00  Alive 1/2");
+        assert_eq!(Front::title_text(&end_conditions, 500, Duration::from_secs(130), &summaries, None),
+            "Last standing, 400 steps  Step 500  Time 02:10  Left 00:00  Alive 1/2");
+    }
+
+    // Test border styles are distinct and double is the default
+    #[test]
+    fn test_border_style() {
+        assert_eq!(FrontOptions::default().border, BorderKind::Double);
+        assert_eq!(Front::border_glyphs(BorderKind::Double), ['═', '║', '╔', '╗', '╚', '╝']);
+        let single = Front::border_glyphs(BorderKind::Single);
+        let rounded = Front::border_glyphs(BorderKind::Rounded);
+        // Rounded border only differs in the corners
+        assert_eq!(single[..2], rounded[..2]);
+        assert!(single[2..].iter().zip(&rounded[2..]).all(|(a, b)| a != b));
+    }
+
+    // Test empty cells show the background only if it's set
+    #[test]
+    fn test_empty_pixel() {
+        assert_eq!(FrontOptions::default().background, None);
+        // Pixel doesn't implement Debug, so it can't be compared with assert_eq
+        assert!(Front::empty_pixel(None) == pixel::pxl(' '));
+        assert!(Front::empty_pixel(Some(Color::DarkGrey)) == pixel::pxl_bg(' ', Color::DarkGrey));
+    }
+
+    // Test cell and border coordinates scale with the aspect ratio
+    #[test]
+    fn test_aspect_ratio() {
+        assert_eq!(FrontOptions::default().aspect_ratio, 3);
+        // Cell spans aspect_ratio characters
+        assert_eq!(Front::cell_rect(3, 2, 5), (6, 5, 8, 5));
+        assert_eq!(Front::cell_rect(2, 2, 5), (4, 5, 5, 5));
+        assert_eq!(Front::cell_rect(1, 2, 5), (2, 5, 2, 5));
+        // Border wraps the cells tightly for any ratio
+        for aspect_ratio in [1, 2, 3] {
+            let (x1, y1, x2, y2) = Front::border_rect(aspect_ratio, 1, 1, 4, 4);
+            let (first_x1, first_y1, _, _) = Front::cell_rect(aspect_ratio, 1, 1);
+            let (_, _, last_x2, last_y2) = Front::cell_rect(aspect_ratio, 4, 4);
+            assert_eq!((x1, y1), (first_x1 - 1, first_y1 - 1));
+            assert_eq!((x2, y2), (last_x2 + 1, last_y2 + 1));
+        }
+    }
+
+    // Test that keys are routed by the bindings
+    #[test]
+    fn test_key_bindings() {
+        // Arrows by default
+        let default = KeyBindings::default();
+        assert_eq!(Front::key_to_direction(&default, KeyCode::Left), Some(Direction::MinusX));
+        assert_eq!(Front::key_to_direction(&default, KeyCode::Char('a')), None);
+        assert_eq!(Front::key_to_command(&default, KeyCode::Char('P')), Some(GameCommand::Pause));
+
+        // Custom map replaces the defaults
+        let custom = KeyBindings {
+            directions : HashMap::from([(KeyCode::Char('a'), Direction::MinusX)]),
+            commands : HashMap::from([(KeyCode::Char('q'), GameCommand::Pause)]),
+            quit : KeyCode::Char('x'),
+        };
+        assert_eq!(Front::key_to_direction(&custom, KeyCode::Char('a')), Some(Direction::MinusX));
+        assert_eq!(Front::key_to_direction(&custom, KeyCode::Left), None);
+        assert_eq!(Front::key_to_command(&custom, KeyCode::Char('q')), Some(GameCommand::Pause));
+        assert_eq!(Front::key_to_command(&custom, KeyCode::Char('p')), None);
+    }
+
+    // Test player colors are distinct on screen
+    #[test]
+    fn test_player_color() {
+        let colors : Vec<Color> = PlayerColor::all().iter().map(|color| Front::player_color(*color)).collect();
+        for (i, color) in colors.iter().enumerate() {
+            assert!(!colors[i + 1..].contains(color), "{:?} is used twice", color);
+        }
+        assert_eq!(Front::player_color(PlayerColor::Red), Color::Red);
+    }
+
+    // Test that only the changed cells are redrawn
+    #[test]
+    fn test_dirty_cells() {
+        let mut grid = grid::new_grid(4, 3);
+        // Nothing drawn yet - everything is dirty
+        assert_eq!(Front::dirty_cells(&grid, None).len(), 12);
+        let drawn_grid = grid.clone();
+        grid.set_at(1, 2, grid::GridCell::Wall);
+        assert_eq!(Front::dirty_cells(&grid, Some(&drawn_grid)), vec![(Vector2i::new(1, 2), grid::GridCell::Wall)]);
     }
 }
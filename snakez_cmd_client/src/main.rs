@@ -2,6 +2,7 @@ use std::sync::mpsc;
 use game_backend::Vector2i;
 use game_backend::base::Direction;
 use game_backend::events::GlobalEvent;
+use game_backend::game::GameCommand;
 use game_cmd_front::front;
 
 
@@ -11,20 +12,25 @@ fn main() {
     // Create a player control channel
     let (user_control_tx, user_control_rx) = mpsc::channel::<Direction>();
     // Register player
-    game.register_player(Some(user_control_rx));
+    game.register_player(Some(Box::new(user_control_rx)));
 
     // Create global events channel
     let (global_update_tx, global_update_rx) = mpsc::channel::<GlobalEvent>();
     game.register_global_event_channel(global_update_tx);
 
+    // Create game command channel
+    let (game_command_tx, game_command_rx) = mpsc::channel::<GameCommand>();
+    game.register_command_channel(game_command_rx);
+
     // Create frontend object
-    let mut front = front::Front::new(user_control_tx, global_update_rx);
+    let options = front::FrontOptions { end_conditions : game.config().end_conditions.clone(), ..front::FrontOptions::default() };
+    let mut front = front::Front::with_options(user_control_tx, global_update_rx, game_command_tx, options);
 
     // Create shutdown channel for game
     let (shutdown_tx, shutdown_rx) = mpsc::channel::<()>();
     // Run the game in separate thread
     let join_handle = std::thread::spawn(move || {
-        game.game_loop(shutdown_rx);
+        game.game_loop(shutdown_rx)
     });
 
 
@@ -34,7 +40,8 @@ fn main() {
     // Send shutdown signal
     let _ = shutdown_tx.send(());
 
-    // And wait for the game to finish
-    let _ = join_handle.join();
+    // And wait for the game to finish. Game is usually shut down
+    // before it's over, so there is no result to show
+    let _game_over = join_handle.join();
 
 }